@@ -0,0 +1,176 @@
+// Agent daemon for fleets, started by the `agent` subcommand.
+//
+// Controllers connect over TCP and must prove they hold the secret key that
+// matches `--controller-key` by signing a random challenge. After that they
+// send one JSON request per line and get one JSON reply per line:
+//   {"command": "push", "script": "<name>", "content": "<csv>"}
+//   {"command": "run", "script": "<name>"}
+//   {"command": "status"} | {"command": "pause"} | {"command": "resume"} | {"command": "stop"}
+//   {"command": "step", "step": <n>, "action": {<script row>}}
+//
+// `step` requests come from `run --target` and execute one row at a time on
+// this machine; while a connection is stepping, scripts can't be started, and
+// `status`, `pause` and `stop` apply to the stream. Rows are refused like the
+// rows of a script started here: denied categories, dangerous actions without
+// `--yes`, and every row when signatures are required, as rows carry none.
+//
+// The channel is authenticated but not encrypted, so use a VPN or SSH tunnel
+// on untrusted networks.
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::runs::{Runs, Stream};
+use crate::{signing, MouseAction, Options};
+
+pub const DEFAULT_AGENT_PORT: u16 = 47900;
+
+// Prefixed to challenges before signing so a challenge signature can never double as a script signature
+pub const CHALLENGE_CONTEXT: &[u8] = b"mouse-automation agent challenge\n";
+
+// How long a controller has to answer the challenge
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+pub struct Challenge {
+    pub challenge: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentRequest {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<MouseAction>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AgentReply {
+    pub ok: bool,
+    pub message: String,
+}
+
+pub fn serve(address: &str, controller_key: &str, options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    let verifying_key = Arc::new(signing::load_verifying_key(controller_key)?);
+    let runs = Runs::new(options, config)?;
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Failed to listen for controllers on {}: {}", address, e))?;
+    info!("Agent listening on {} (scripts in {})", address, runs.scripts_dir().display());
+
+    for stream in listener.incoming().flatten() {
+        let runs = Arc::clone(&runs);
+        let verifying_key = Arc::clone(&verifying_key);
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|peer| peer.to_string()).unwrap_or_default();
+            if let Err(error) = handle(stream, &runs, &verifying_key) {
+                warn!("Controller {} disconnected: {}", peer, error);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(stream: TcpStream, runs: &Runs, verifying_key: &VerifyingKey) -> Result<(), Box<dyn Error>> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    writer.set_read_timeout(Some(AUTH_TIMEOUT))?;
+    let mut challenge = [0u8; 32];
+    getrandom::getrandom(&mut challenge)?;
+    write_message(&mut writer, &Challenge { challenge: hex::encode(challenge) })?;
+
+    let response: ChallengeResponse = read_message(&mut reader)?.ok_or("closed before authenticating")?;
+    if check_signature(verifying_key, &challenge, &response.signature).is_err() {
+        write_message(&mut writer, &AgentReply { ok: false, message: "authentication failed".to_string() })?;
+        return Err("authentication failed".into());
+    }
+    write_message(&mut writer, &AgentReply { ok: true, message: "authenticated".to_string() })?;
+    writer.set_read_timeout(None)?;
+
+    // Set up by the first `step` request and kept for the rest of the connection
+    let mut stream = None;
+    while let Some(request) = read_message::<AgentRequest>(&mut reader)? {
+        let reply = match apply(&request, runs, &mut stream) {
+            Ok(message) => AgentReply { ok: true, message },
+            Err(error) => AgentReply { ok: false, message: error.to_string() },
+        };
+        write_message(&mut writer, &reply)?;
+    }
+    Ok(())
+}
+
+fn apply<'a>(request: &AgentRequest, runs: &'a Runs, stream: &mut Option<Stream<'a>>) -> Result<String, Box<dyn Error>> {
+    let script = || request.script.as_deref().ok_or_else(|| format!("'{}' needs a script name", request.command));
+    match request.command.as_str() {
+        "push" => {
+            runs.save_script(script()?, request.content.as_deref().unwrap_or(""))?;
+            Ok("script saved".to_string())
+        },
+        "run" => {
+            runs.start(script()?)?;
+            Ok("started".to_string())
+        },
+        "status" => {
+            let status = runs.status();
+            Ok(match (&status.script, &status.error) {
+                (None, _) => status.state,
+                (Some(script), None) => format!("{} {} step {}/{}", status.state, script, status.step, status.total_steps),
+                (Some(script), Some(error)) => format!("{} {} step {}/{}: {}", status.state, script, status.step, status.total_steps, error),
+            })
+        },
+        "step" => {
+            let action = request.action.as_ref().ok_or("step needs an action")?;
+            let stream = match stream {
+                Some(stream) => stream,
+                None => stream.insert(runs.stream()?),
+            };
+            stream.step(request.step.unwrap_or(1), action)?;
+            Ok("done".to_string())
+        },
+        "pause" => runs.with_active(|controls| controls.set_paused(true)).map(|_| "paused".to_string()).map_err(Into::into),
+        "resume" => runs.with_active(|controls| controls.request_resume()).map(|_| "resumed".to_string()).map_err(Into::into),
+        "stop" => runs.with_active(|controls| controls.request_abort()).map(|_| "stopping".to_string()).map_err(Into::into),
+        other => Err(format!("unknown command '{}'", other).into()),
+    }
+}
+
+fn check_signature(verifying_key: &VerifyingKey, challenge: &[u8], signature: &str) -> Result<(), Box<dyn Error>> {
+    let signature: [u8; 64] = hex::decode(signature)?.try_into().map_err(|_| "signature must be 64 bytes")?;
+    verifying_key.verify(&[CHALLENGE_CONTEXT, challenge].concat(), &Signature::from_bytes(&signature))?;
+    Ok(())
+}
+
+// Write one JSON message as a line
+pub fn write_message<T: Serialize>(stream: &mut TcpStream, message: &T) -> Result<(), Box<dyn Error>> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+// Read one JSON message line, or None once the peer has closed the connection
+pub fn read_message<T: DeserializeOwned>(reader: &mut BufReader<TcpStream>) -> Result<Option<T>, Box<dyn Error>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
@@ -0,0 +1,64 @@
+// Append-only JSONL log of every event injected into the OS.
+
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tracing::{info, warn};
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    // Seconds since the Unix epoch, microsecond precision
+    time: f64,
+    // CSV row that produced the event
+    row: usize,
+    event: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    y: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    button: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount: Option<i32>,
+}
+
+pub struct AuditLog {
+    file: File,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> Result<AuditLog, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("Failed to open audit log '{}': {}", path, e))?;
+        info!("Auditing injected events to {}", path);
+        Ok(AuditLog { file })
+    }
+
+    pub fn record(&mut self, row: usize, event: &str, position: Option<(i32, i32)>, button: Option<String>, key: Option<String>, amount: Option<i32>) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| {
+            elapsed.as_micros() as f64 / 1_000_000.0
+        });
+        let entry = Entry {
+            time,
+            row,
+            event,
+            x: position.map(|(x, _)| x),
+            y: position.map(|(_, y)| y),
+            button,
+            key,
+            amount,
+        };
+
+        // One write per line so a crash never leaves a partial entry behind
+        let written = serde_json::to_string(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| self.file.write_all(format!("{}\n", line).as_bytes()).map_err(|e| e.to_string()));
+        if let Err(error) = written {
+            warn!("failed to write audit log entry: {}", error);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Auto-clicker, the `click-at` subcommand.
+//
+// Clicks every `--interval` at the cursor, or at `--at x,y`, until Ctrl+C or
+// the stop key is pressed or `--for` has elapsed. Clicks go through the same
+// rate limit and audit log as playback, and pause while the session is locked.
+
+use std::error::Error;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use enigo::MouseButton;
+use rdev::{listen, EventType};
+use tracing::{info, warn};
+use crate::controls::{self, Controls};
+use crate::injector::Injector;
+use crate::{parse_button, session};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+const DEFAULT_STOP_KEY: &str = "F10";
+
+#[derive(Debug, Clone, Default)]
+pub struct ClickAtOptions {
+    pub interval: Option<Duration>,
+    pub button: Option<String>,
+    pub at: Option<(i32, i32)>,
+    // Stop on its own after this long
+    pub duration: Option<Duration>,
+    pub stop_key: Option<String>,
+}
+
+pub fn run(mut injector: Injector, options: ClickAtOptions) -> Result<(), Box<dyn Error>> {
+    let interval = options.interval.unwrap_or(DEFAULT_INTERVAL);
+    let button = match options.button.as_deref() {
+        Some(name) => parse_button(name).ok_or_else(|| format!("Unknown button '{}'; use left, right, middle, back, forward or 1-9", name))?,
+        None => MouseButton::Left,
+    };
+    if let Some((x, y)) = options.at {
+        let (width, height) = injector.display_size();
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return Err(format!("--at {},{} is outside the {}x{} screen", x, y, width, height).into());
+        }
+    }
+    let key_name = options.stop_key.as_deref().unwrap_or(DEFAULT_STOP_KEY);
+    let stop_key = controls::parse_key(key_name).ok_or_else(|| format!("Unknown stop key: {}", key_name))?;
+
+    let controls = Controls::new();
+    controls.handle_ctrl_c()?;
+    let listener = Arc::clone(&controls);
+    thread::spawn(move || {
+        let result = listen(move |event| {
+            if event.event_type == EventType::KeyPress(stop_key) {
+                listener.request_abort();
+            }
+        });
+        if let Err(error) = result {
+            warn!("stop key listener failed: {:?}", error);
+        }
+    });
+
+    let target = options.at.map_or("the cursor".to_string(), |(x, y)| format!("({}, {})", x, y));
+    info!("Clicking {:?} at {} every {} ms; press {} or Ctrl+C to stop", button, target, interval.as_millis(), key_name);
+
+    let started = Instant::now();
+    let mut clicks: u64 = 0;
+    let mut locked = false;
+    while !controls.abort_requested() {
+        if options.duration.is_some_and(|duration| started.elapsed() >= duration) {
+            break;
+        }
+        if session::is_locked() {
+            if !locked {
+                info!("Session locked; clicking paused");
+                locked = true;
+            }
+            controls.sleep(Duration::from_millis(500));
+            continue;
+        }
+        if locked {
+            info!("Session unlocked; clicking resumed");
+            locked = false;
+        }
+
+        let next = Instant::now() + interval;
+        if let Some((x, y)) = options.at {
+            injector.move_to(x, y);
+        }
+        injector.click(button);
+        clicks += 1;
+        // Measured from the previous click so the rate doesn't drift with the click's own cost
+        controls.sleep(next.saturating_duration_since(Instant::now()));
+    }
+    info!("Stopped after {} click(s) in {:.1}s", clicks, started.elapsed().as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,418 @@
+// What turns the injector's calls into input events, chosen with `--backend`.
+//
+// The injector keeps the click rate limit, the keys and buttons left held,
+// the report journal and the audit log; a backend only delivers the events,
+// and checks before the run that it can. `enigo` uses SendInput on Windows,
+// Quartz events on macOS and XTest on Linux. `uinput` creates virtual input
+// devices, for Linux without X (see uinput.rs). `enigo` is the default;
+// `auto` is `uinput` in a Wayland session, where XTest only reaches XWayland
+// windows, if /dev/uinput can be written to, and `enigo` otherwise. Picking
+// uinput is warned about, since it can't see the cursor and what needs it is
+// refused. No backend speaks a Wayland protocol itself: wlr-virtual-pointer
+// only exists on wlroots compositors and libei needs the RemoteDesktop
+// portal, which asks the user every run, while uinput works under any
+// compositor.
+// `simulated` delivers nothing: `SimulatedBackend` records every event in
+// order, logging each with -v, and keeps track of where the cursor would be,
+// so a script can be played and what it would have done checked without
+// touching the mouse, e.g. in an embedder's tests. Clones share the
+// recording, so the caller keeps one while the run plays into another.
+// Actions that look at the screen, such as `if_pixel` or the window actions,
+// still look at the real one.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use enigo::{Enigo, Key, KeyboardControllable, MouseButton, MouseControllable};
+use serde::Deserialize;
+use tracing::{debug, warn};
+use crate::preflight;
+use crate::uinput::{self, UinputBackend};
+
+// Screen size assumed when the real one can't be found
+const FALLBACK_SCREEN: (i32, i32) = (1920, 1080);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    Auto,
+    #[default]
+    Enigo,
+    Uinput,
+    Simulated,
+}
+
+impl BackendKind {
+    pub fn parse(name: &str) -> Option<BackendKind> {
+        match name {
+            "auto" => Some(BackendKind::Auto),
+            "enigo" => Some(BackendKind::Enigo),
+            "uinput" => Some(BackendKind::Uinput),
+            "simulated" => Some(BackendKind::Simulated),
+            _ => None,
+        }
+    }
+
+    // The backend `auto` stands for in this session; resolved once per run,
+    // and what preflight and open are called on
+    pub fn resolve(self) -> BackendKind {
+        match self {
+            BackendKind::Auto if wayland_session() && uinput::check().is_ok() => {
+                warn!("Wayland session: injecting through uinput, which can't see the cursor; relative moves need a move to a position first, \
+                    and --restore-cursor, --pause-on-user-input, --failsafe and cursor assertions are refused. Use --backend enigo to inject through XWayland instead");
+                BackendKind::Uinput
+            },
+            BackendKind::Auto => BackendKind::Enigo,
+            kind => kind,
+        }
+    }
+
+    // Make sure injected events will actually arrive; needs_elevation is for --block-input
+    pub fn preflight(self, needs_elevation: bool) -> Result<(), Box<dyn Error>> {
+        match self {
+            BackendKind::Auto | BackendKind::Enigo => preflight::check(needs_elevation),
+            BackendKind::Uinput => uinput::check(),
+            BackendKind::Simulated => Ok(()),
+        }
+    }
+
+    pub fn open(self) -> Result<Box<dyn InputBackend>, Box<dyn Error>> {
+        match self {
+            BackendKind::Auto | BackendKind::Enigo => Ok(Box::new(Enigo::new())),
+            BackendKind::Uinput => {
+                let size = screen_size().unwrap_or_else(|| {
+                    warn!("screen size unavailable, assuming {}x{}; positions are off if it's different", FALLBACK_SCREEN.0, FALLBACK_SCREEN.1);
+                    FALLBACK_SCREEN
+                });
+                Ok(Box::new(UinputBackend::open(size)?))
+            },
+            BackendKind::Simulated => {
+                // The real screen's size if there is one, so bounds checks play out the same
+                let (width, height) = screen_size().unwrap_or(FALLBACK_SCREEN);
+                Ok(Box::new(SimulatedBackend::new(width, height)))
+            },
+        }
+    }
+}
+
+// Whether this is a Wayland session, where XTest only reaches XWayland windows
+pub fn wayland_session() -> bool {
+    env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland")
+        || env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty())
+}
+
+// The main display's size from X, or the framebuffer's on a console
+fn screen_size() -> Option<(i32, i32)> {
+    Some(Enigo::new().main_display_size())
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .or_else(|| {
+            let size = fs::read_to_string("/sys/class/graphics/fb0/virtual_size").ok()?;
+            let (width, height) = size.trim().split_once(',')?;
+            Some((width.parse().ok()?, height.parse().ok()?)).filter(|&(width, height)| width > 0 && height > 0)
+        })
+}
+
+pub trait InputBackend {
+    fn location(&self) -> (i32, i32);
+    fn display_size(&self) -> (i32, i32);
+    fn move_to(&mut self, x: i32, y: i32);
+    fn move_relative(&mut self, dx: i32, dy: i32);
+    fn click(&mut self, button: MouseButton);
+    fn button_down(&mut self, button: MouseButton);
+    fn button_up(&mut self, button: MouseButton);
+    fn scroll_x(&mut self, amount: i32);
+    fn scroll_y(&mut self, amount: i32);
+    fn key_down(&mut self, key: Key);
+    fn key_up(&mut self, key: Key);
+    fn key_click(&mut self, key: Key);
+    // Text typed as it is, whatever the keyboard layout
+    fn key_sequence(&mut self, text: &str);
+    // False if location() is only where this backend last put the cursor
+    fn reads_cursor(&self) -> bool {
+        true
+    }
+}
+
+impl InputBackend for Enigo {
+    fn location(&self) -> (i32, i32) {
+        self.mouse_location()
+    }
+
+    fn display_size(&self) -> (i32, i32) {
+        self.main_display_size()
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.mouse_move_to(x, y);
+    }
+
+    fn move_relative(&mut self, dx: i32, dy: i32) {
+        self.mouse_move_relative(dx, dy);
+    }
+
+    fn click(&mut self, button: MouseButton) {
+        self.mouse_click(button);
+    }
+
+    fn button_down(&mut self, button: MouseButton) {
+        self.mouse_down(button);
+    }
+
+    fn button_up(&mut self, button: MouseButton) {
+        self.mouse_up(button);
+    }
+
+    fn scroll_x(&mut self, amount: i32) {
+        self.mouse_scroll_x(amount);
+    }
+
+    fn scroll_y(&mut self, amount: i32) {
+        self.mouse_scroll_y(amount);
+    }
+
+    fn key_down(&mut self, key: Key) {
+        KeyboardControllable::key_down(self, key);
+    }
+
+    fn key_up(&mut self, key: Key) {
+        KeyboardControllable::key_up(self, key);
+    }
+
+    fn key_click(&mut self, key: Key) {
+        KeyboardControllable::key_click(self, key);
+    }
+
+    fn key_sequence(&mut self, text: &str) {
+        KeyboardControllable::key_sequence(self, text);
+    }
+}
+
+// One event a simulated run would have injected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulatedEvent {
+    MoveTo(i32, i32),
+    MoveRelative(i32, i32),
+    Click(MouseButton),
+    ButtonDown(MouseButton),
+    ButtonUp(MouseButton),
+    ScrollX(i32),
+    ScrollY(i32),
+    KeyDown(Key),
+    KeyUp(Key),
+    KeyClick(Key),
+    Text(String),
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    events: Vec<SimulatedEvent>,
+    cursor: (i32, i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatedBackend {
+    size: (i32, i32),
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl SimulatedBackend {
+    // A screen of this size, with the cursor in its top-left corner
+    pub fn new(width: i32, height: i32) -> SimulatedBackend {
+        SimulatedBackend { size: (width, height), recording: Arc::default() }
+    }
+
+    // Every event so far, in the order injected
+    pub fn events(&self) -> Vec<SimulatedEvent> {
+        self.recording().events.clone()
+    }
+
+    pub fn clear(&self) {
+        self.recording().events.clear();
+    }
+
+    fn recording(&self) -> MutexGuard<'_, Recording> {
+        self.recording.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&mut self, event: SimulatedEvent) {
+        debug!("Simulated {:?}", event);
+        self.recording().events.push(event);
+    }
+}
+
+impl InputBackend for SimulatedBackend {
+    fn location(&self) -> (i32, i32) {
+        self.recording().cursor
+    }
+
+    fn display_size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.recording().cursor = (x, y);
+        self.push(SimulatedEvent::MoveTo(x, y));
+    }
+
+    fn move_relative(&mut self, dx: i32, dy: i32) {
+        let mut recording = self.recording();
+        recording.cursor = (recording.cursor.0 + dx, recording.cursor.1 + dy);
+        debug!("Simulated {:?}", SimulatedEvent::MoveRelative(dx, dy));
+        recording.events.push(SimulatedEvent::MoveRelative(dx, dy));
+    }
+
+    fn click(&mut self, button: MouseButton) {
+        self.push(SimulatedEvent::Click(button));
+    }
+
+    fn button_down(&mut self, button: MouseButton) {
+        self.push(SimulatedEvent::ButtonDown(button));
+    }
+
+    fn button_up(&mut self, button: MouseButton) {
+        self.push(SimulatedEvent::ButtonUp(button));
+    }
+
+    fn scroll_x(&mut self, amount: i32) {
+        self.push(SimulatedEvent::ScrollX(amount));
+    }
+
+    fn scroll_y(&mut self, amount: i32) {
+        self.push(SimulatedEvent::ScrollY(amount));
+    }
+
+    fn key_down(&mut self, key: Key) {
+        self.push(SimulatedEvent::KeyDown(key));
+    }
+
+    fn key_up(&mut self, key: Key) {
+        self.push(SimulatedEvent::KeyUp(key));
+    }
+
+    fn key_click(&mut self, key: Key) {
+        self.push(SimulatedEvent::KeyClick(key));
+    }
+
+    fn key_sequence(&mut self, text: &str) {
+        self.push(SimulatedEvent::Text(text.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use enigo::{Key, MouseButton};
+    use crate::bounds::OutOfBounds;
+    use crate::{Config, MouseAction, Script, ScriptRunner};
+    use super::{InputBackend, SimulatedBackend, SimulatedEvent};
+
+    fn row(action: &str, x: i32, y: i32) -> MouseAction {
+        MouseAction { action: action.to_string(), x_position: Some(x), y_position: Some(y), ..Default::default() }
+    }
+
+    fn with_modifiers(record: MouseAction, modifiers: &str) -> MouseAction {
+        MouseAction { modifiers: Some(modifiers.to_string()), ..record }
+    }
+
+    // Play rows into a fresh simulated screen, without waiting for their delays
+    fn play(rows: Vec<MouseAction>) -> (SimulatedBackend, Result<(), String>) {
+        play_with(Config::default(), rows)
+    }
+
+    fn play_with(config: Config, rows: Vec<MouseAction>) -> (SimulatedBackend, Result<(), String>) {
+        let backend = SimulatedBackend::new(1920, 1080);
+        let mut runner = ScriptRunner::with_config(config);
+        runner.simulate(&backend);
+        runner.controls().use_virtual_time();
+        let result = runner.run(&Script::from(rows)).map_err(|e| e.to_string());
+        (backend, result)
+    }
+
+    #[test]
+    fn click_moves_then_clicks() {
+        let (backend, result) = play(vec![row("click", 200, 300), MouseAction { button: Some("right".to_string()), ..row("click", 400, 500) }]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(backend.events(), [
+            SimulatedEvent::MoveTo(200, 300),
+            SimulatedEvent::Click(MouseButton::Left),
+            SimulatedEvent::MoveTo(400, 500),
+            SimulatedEvent::Click(MouseButton::Right),
+        ]);
+        assert_eq!(backend.location(), (400, 500));
+    }
+
+    #[test]
+    fn modifiers_surround_the_click_and_release_in_reverse() {
+        let (backend, result) = play(vec![with_modifiers(row("click", 200, 300), "ctrl+shift")]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(backend.events(), [
+            SimulatedEvent::KeyDown(Key::Control),
+            SimulatedEvent::KeyDown(Key::Shift),
+            SimulatedEvent::MoveTo(200, 300),
+            SimulatedEvent::Click(MouseButton::Left),
+            SimulatedEvent::KeyUp(Key::Shift),
+            SimulatedEvent::KeyUp(Key::Control),
+        ]);
+    }
+
+    #[test]
+    fn drag_holds_button_and_modifiers_until_release() {
+        let (backend, result) = play(vec![with_modifiers(row("drag", 100, 100), "ctrl"), row("release", 400, 100)]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(backend.events(), [
+            SimulatedEvent::KeyDown(Key::Control),
+            SimulatedEvent::MoveTo(100, 100),
+            SimulatedEvent::ButtonDown(MouseButton::Left),
+            SimulatedEvent::MoveTo(400, 100),
+            SimulatedEvent::ButtonUp(MouseButton::Left),
+            SimulatedEvent::KeyUp(Key::Control),
+        ]);
+    }
+
+    #[test]
+    fn relative_moves_start_from_the_cursor() {
+        let (backend, result) = play(vec![row("move", 100, 100), row("move_relative", 50, -20), row("move_relative", -10, 5)]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(backend.events(), [
+            SimulatedEvent::MoveTo(100, 100),
+            SimulatedEvent::MoveRelative(50, -20),
+            SimulatedEvent::MoveRelative(-10, 5),
+        ]);
+        assert_eq!(backend.location(), (140, 85));
+    }
+
+    #[test]
+    fn failed_row_lets_go_of_its_modifiers() {
+        // The click is off the screen, which fails the row after its keys went down
+        let config = Config { out_of_bounds: Some(OutOfBounds::Error), no_failure_screenshots: true, ..Config::default() };
+        let (backend, result) = play_with(config, vec![with_modifiers(row("click", 5000, 5000), "shift")]);
+        assert!(result.is_err());
+        assert_eq!(backend.events(), [SimulatedEvent::KeyDown(Key::Shift), SimulatedEvent::KeyUp(Key::Shift)]);
+    }
+
+    #[test]
+    fn abort_releases_a_held_drag() {
+        let backend = SimulatedBackend::new(1920, 1080);
+        let mut runner = ScriptRunner::with_config(Config::default());
+        runner.simulate(&backend);
+        // Stopped a second into the release row's delay, with the button and ctrl still held
+        runner.controls().use_virtual_time();
+        runner.controls().abort_after(Duration::from_secs(1));
+        let release = MouseAction { delay_ms: Some(5000), ..row("release", 400, 100) };
+        let result = runner.run(&Script::from(vec![with_modifiers(row("drag", 100, 100), "ctrl"), release]));
+
+        assert!(result.is_err_and(|error| error.to_string().contains("Aborted at step 2")));
+        assert_eq!(runner.controls().virtual_elapsed(), Duration::from_secs(1));
+        let events = backend.events();
+        assert_eq!(events[..3], [
+            SimulatedEvent::KeyDown(Key::Control),
+            SimulatedEvent::MoveTo(100, 100),
+            SimulatedEvent::ButtonDown(MouseButton::Left),
+        ]);
+        // The release row never moved the cursor, but everything held was let go
+        let mut released = events[3..].to_vec();
+        released.sort_by_key(|event| format!("{:?}", event));
+        assert_eq!(released, [SimulatedEvent::ButtonUp(MouseButton::Left), SimulatedEvent::KeyUp(Key::Control)]);
+    }
+}
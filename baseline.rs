@@ -0,0 +1,67 @@
+// Screenshot regression checks, for the `assert_region_matches` action.
+//
+// The row's region is captured and compared pixel by pixel with its baseline
+// image; a pixel matches when no color channel differs by more than the row's
+// `tolerance`. On a mismatch the capture is saved next to the baseline as
+// `<name>.actual.png` along with `<name>.diff.png`, which shows the differing
+// pixels in red over a faded copy of the baseline. A missing baseline is
+// created from the screen, and `--update-baselines` recreates them all.
+
+use std::error::Error;
+use std::path::Path;
+use xcap::image::{self, Rgba, RgbaImage};
+use tracing::info;
+use crate::screen::{self, Region};
+
+pub fn check(region: Region, baseline: &Path, tolerance: u8, update: bool) -> Result<(), Box<dyn Error>> {
+    let actual = screen::capture_region(region)?;
+    if update || !baseline.exists() {
+        save(&actual, baseline)?;
+        info!("Saved baseline {}", baseline.display());
+        return Ok(());
+    }
+
+    let expected = image::open(baseline)
+        .map_err(|e| format!("Failed to read baseline '{}': {}", baseline.display(), e))?
+        .to_rgba8();
+    if expected.dimensions() != actual.dimensions() {
+        return Err(format!("Baseline '{}' is {}x{} but the region is {}x{}; rerun with --update-baselines if the region changed on purpose",
+            baseline.display(), expected.width(), expected.height(), actual.width(), actual.height()).into());
+    }
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut differing = 0u64;
+    for y in 0..actual.height() {
+        for x in 0..actual.width() {
+            let (Rgba(wanted), Rgba(got)) = (*expected.get_pixel(x, y), *actual.get_pixel(x, y));
+            // Alpha is ignored; screen captures are always opaque
+            if wanted.iter().zip(got).take(3).any(|(&wanted, got)| wanted.abs_diff(got) > tolerance) {
+                differing += 1;
+                diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            } else {
+                let faded = 170 + ((u16::from(wanted[0]) + u16::from(wanted[1]) + u16::from(wanted[2])) / 9) as u8;
+                diff.put_pixel(x, y, Rgba([faded, faded, faded, 255]));
+            }
+        }
+    }
+    if differing == 0 {
+        info!("Region matches {}", baseline.display());
+        return Ok(());
+    }
+
+    let actual_path = baseline.with_extension("actual.png");
+    let diff_path = baseline.with_extension("diff.png");
+    save(&actual, &actual_path)?;
+    save(&diff, &diff_path)?;
+    let total = u64::from(actual.width()) * u64::from(actual.height());
+    Err(format!("{} of {} pixels differ from '{}' (tolerance {}); see {} and {}",
+        differing, total, baseline.display(), tolerance, actual_path.display(), diff_path.display()).into())
+}
+
+fn save(image: &RgbaImage, path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    image.save(path).map_err(|e| format!("Failed to save '{}': {}", path.display(), e))?;
+    Ok(())
+}
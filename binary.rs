@@ -0,0 +1,151 @@
+// Compact binary scripts (`.mab`), for long recorded sessions.
+//
+// A recording with full mouse trajectories can run to hundreds of thousands
+// of rows, which are slow to parse and large as CSV. A binary script is a
+// magic number followed by length-prefixed records, each a MessagePack array
+// of the `MouseAction` fields in order. `read` decodes a record each time the
+// next row is asked for, so a straight-line recording plays as it is read and
+// never has to fit in memory (see `stream_script` in lib.rs); scripts that
+// need every row at once, e.g. for loops or reports, collect them first. A
+// record length longer than `MAX_RECORD` or than what is left of the file is
+// refused before anything is allocated for it. Fields are only ever added at
+// the end of `MouseAction`, so a record from an older version is padded with
+// empty fields before it is decoded.
+//
+// Binary scripts have no `${name}` placeholders; `convert` turns them into
+// CSV or YAML and back.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use crate::MouseAction;
+
+const MAGIC: &[u8; 4] = b"MAB1";
+const EXTENSION: &str = "mab";
+// MessagePack nil, for fields a record predates
+const NIL: u8 = 0xc0;
+// Longest record accepted; a row is a few hundred bytes unless it types a lot of text
+const MAX_RECORD: u64 = 1 << 20;
+
+pub fn is_binary(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == EXTENSION)
+}
+
+// The rows of a binary script, decoded as they are asked for
+pub struct Records {
+    reader: BufReader<File>,
+    path: String,
+    fields: usize,
+    // Bytes of the file not read yet
+    remaining: u64,
+    record: Vec<u8>,
+    count: usize,
+    done: bool,
+}
+
+pub fn read(path: &Path) -> Result<Records, Box<dyn Error>> {
+    let file = File::open(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+        return Err(format!("{} is not a binary script", path.display()).into());
+    }
+    Ok(Records {
+        reader,
+        path: path.display().to_string(),
+        fields: field_count()?,
+        remaining: size.saturating_sub(MAGIC.len() as u64),
+        record: Vec::new(),
+        count: 0,
+        done: false,
+    })
+}
+
+impl Records {
+    fn next_record(&mut self) -> Result<Option<MouseAction>, String> {
+        let mut length = [0u8; 4];
+        match self.reader.read_exact(&mut length) {
+            Ok(()) => {},
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(format!("{}: {}", self.path, error)),
+        }
+        self.count += 1;
+        self.remaining = self.remaining.saturating_sub(length.len() as u64);
+        let length = u64::from(u32::from_le_bytes(length));
+        if length > self.remaining {
+            return Err(format!("{}: record {} is cut short", self.path, self.count));
+        }
+        if length > MAX_RECORD {
+            return Err(format!("{}: record {} is {} bytes, more than the {} a record can be", self.path, self.count, length, MAX_RECORD));
+        }
+        self.remaining -= length;
+        self.record.resize(length as usize, 0);
+        self.reader.read_exact(&mut self.record).map_err(|_| format!("{}: record {} is cut short", self.path, self.count))?;
+        pad(&self.record, self.fields)
+            .and_then(|record| rmp_serde::from_slice(&record).map_err(|e| e.to_string()))
+            .map(Some)
+            .map_err(|e| format!("{}: record {}: {}", self.path, self.count, e))
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<MouseAction, String>;
+
+    // Stops after the first error, as nothing after a bad length can be trusted
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_record().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+pub fn write(path: &Path, actions: &[MouseAction]) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path).map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC)?;
+    for record in actions {
+        let encoded = rmp_serde::to_vec(record)?;
+        writer.write_all(&(encoded.len() as u32).to_le_bytes())?;
+        writer.write_all(&encoded)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// How many fields a record of this version has
+fn field_count() -> Result<usize, Box<dyn Error>> {
+    let encoded = rmp_serde::to_vec(&MouseAction::default())?;
+    Ok(array_header(&encoded).ok_or("MouseAction doesn't encode as an array")?.0)
+}
+
+// The length of a MessagePack array and where its elements start
+fn array_header(record: &[u8]) -> Option<(usize, usize)> {
+    match *record {
+        [marker @ 0x90..=0x9f, ..] => Some(((marker & 0x0f) as usize, 1)),
+        [0xdc, high, low, ..] => Some((u16::from_be_bytes([high, low]) as usize, 3)),
+        _ => None,
+    }
+}
+
+// Extend a record written before the last fields existed
+fn pad(record: &[u8], fields: usize) -> Result<Vec<u8>, String> {
+    let (count, start) = array_header(record).ok_or("not an action record")?;
+    if count >= fields {
+        return Ok(record.to_vec());
+    }
+    let mut padded = if fields < 16 {
+        vec![0x90 | fields as u8]
+    } else {
+        let mut header = vec![0xdc];
+        header.extend((fields as u16).to_be_bytes());
+        header
+    };
+    padded.extend_from_slice(&record[start..]);
+    padded.resize(padded.len() + fields - count, NIL);
+    Ok(padded)
+}
@@ -0,0 +1,68 @@
+// What to do with coordinates outside the detected screen.
+
+use serde::Deserialize;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfBounds {
+    // Fail the run (strict / CI use)
+    Error,
+    // Move to the nearest on-screen point and warn
+    Clamp,
+    // Pass coordinates through unchanged, e.g. for secondary monitors
+    #[default]
+    Ignore,
+}
+
+impl OutOfBounds {
+    pub fn parse(name: &str) -> Option<OutOfBounds> {
+        match name {
+            "error" => Some(OutOfBounds::Error),
+            "clamp" => Some(OutOfBounds::Clamp),
+            "ignore" => Some(OutOfBounds::Ignore),
+            _ => None,
+        }
+    }
+}
+
+pub struct Bounds {
+    policy: OutOfBounds,
+    // Unknown when the backend can't report a display size
+    screen: Option<(i32, i32)>,
+}
+
+impl Bounds {
+    pub fn new(policy: OutOfBounds, screen: (i32, i32)) -> Self {
+        let screen = Some(screen).filter(|&(width, height)| width > 0 && height > 0);
+        if screen.is_none() && policy != OutOfBounds::Ignore {
+            warn!("screen size unavailable, coordinates won't be bounds-checked");
+        }
+        Bounds { policy, screen }
+    }
+
+    pub fn policy(&self) -> OutOfBounds {
+        self.policy
+    }
+
+    // Apply the policy to an absolute target position
+    pub fn resolve(&self, x: i32, y: i32) -> Result<(i32, i32), String> {
+        let Some((width, height)) = self.screen else {
+            return Ok((x, y));
+        };
+        if (0..width).contains(&x) && (0..height).contains(&y) {
+            return Ok((x, y));
+        }
+
+        match self.policy {
+            OutOfBounds::Ignore => Ok((x, y)),
+            OutOfBounds::Error => Err(format!("({}, {}) is outside the {}x{} screen", x, y, width, height)),
+            OutOfBounds::Clamp => {
+                let clamped = (x.clamp(0, width - 1), y.clamp(0, height - 1));
+                warn!("({}, {}) is outside the {}x{} screen, clamped to ({}, {})",
+                    x, y, width, height, clamped.0, clamped.1);
+                Ok(clamped)
+            },
+        }
+    }
+}
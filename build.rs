@@ -0,0 +1,5 @@
+// Generate the gRPC service code from proto/automation.proto (needs protoc)
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::compile_protos("proto/automation.proto")?;
+    Ok(())
+}
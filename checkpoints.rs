@@ -0,0 +1,43 @@
+// State file recording which checkpointed sections of a script have finished.
+//
+// A `checkpoint` row starts a named section that runs up to the next
+// checkpoint (or the end of the script). Once a section completes its name is
+// appended to the state file, and later runs with the same file skip it.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use tracing::info;
+
+pub struct StateFile {
+    path: String,
+    done: HashSet<String>,
+}
+
+impl StateFile {
+    // Load completed section names; a missing file means nothing is done yet
+    pub fn open(path: &str) -> Result<StateFile, Box<dyn Error>> {
+        let done = match fs::read_to_string(path) {
+            Ok(text) => text.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(format!("Failed to read state file '{}': {}", path, e).into()),
+        };
+        Ok(StateFile { path: path.to_string(), done })
+    }
+
+    pub fn is_done(&self, name: &str) -> bool {
+        self.done.contains(name)
+    }
+
+    // Record a section as complete right away so a crash can't lose it
+    pub fn mark_done(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if !self.done.insert(name.to_string()) {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", name)?;
+        info!("Checkpoint '{}' completed", name);
+        Ok(())
+    }
+}
@@ -0,0 +1,778 @@
+// The `mouse-automation` command line: flags, subcommands and the prompts
+// around running a script. `main.rs` only calls `run`; everything here goes
+// through the same library functions that embedders use.
+
+use std::env;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::{Args, Parser, Subcommand};
+use enigo::{Enigo, MouseControllable};
+use tracing::{debug, info, warn};
+use crate::backend::BackendKind;
+use crate::binary;
+use crate::bounds::OutOfBounds;
+use crate::config::Config;
+use crate::controls::{self, Controls};
+use crate::foreground::OnGuard;
+use crate::hotkey::Hotkey;
+use crate::keyboard::TypingMode;
+use crate::screen::Region;
+use crate::session::OnLock;
+use crate::vars::Vars;
+use crate::{agent, autoclick, clipboard_watch, combo, control_socket, controller, convert, dwell, folder_watch, fuzz, generate, jiggle, launcher};
+use crate::{library, lint, logging, mqtt, policy, recorder, server, signing, timings, vars, visualize, watchdog, webhook};
+use crate::{backend_kind, build_guard, build_injector, load_script, max_clicks_per_second, parse_duration, prepare_script, resume_key};
+use crate::{execute_script, stream_script, streamable, Command, MouseAction, Options};
+
+// Parse the command line and do what it asks
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let options = parse_options()?;
+    logging::init(logging::level(options.verbose, options.quiet), options.log_file.as_deref())?;
+    
+    // Print current directory for debugging
+    debug!("Current directory: {:?}", env::current_dir()?);
+    
+    let config = Config::load(options.config_path.as_deref())?;
+    match &options.command {
+        Command::Run => run_from_cli(&options, &config),
+        Command::Keygen { secret_path, public_path } => signing::keygen(secret_path, public_path),
+        Command::Sign { script_path, secret_path } => signing::sign(script_path, secret_path),
+        Command::Verify { script_path, public_path } => {
+            signing::verify(script_path, public_path)?;
+            println!("Signature OK: {}", script_path);
+            Ok(())
+        },
+        Command::Lint { script_path } => lint_script(script_path, &options.vars),
+        Command::Convert { input, output } => convert::run(input, output, &options.vars),
+        Command::Control { command } => {
+            let endpoint = options.control_endpoint.clone().unwrap_or_else(control_socket::default_endpoint);
+            println!("{}", control_socket::send(&endpoint, command)?);
+            Ok(())
+        },
+        Command::Serve { address, token_file, allow_origins } => {
+            server::serve(address, server::Access::new(token_file.as_deref(), allow_origins)?, options.clone(), config)
+        },
+        Command::Mqtt { broker } => mqtt::listen(broker.as_deref(), options.clone(), config),
+        Command::Webhook { address, secret_file } => webhook::listen(address, secret_file.as_deref(), options.clone(), config),
+        Command::Hotkeys => launcher::run(options.clone(), config),
+        Command::Clipboard => clipboard_watch::run(options.clone(), config),
+        Command::Watch { dir, script, pattern } => folder_watch::run(dir, script, pattern.as_deref(), options.clone(), config),
+        Command::Agent { address, controller_key } => {
+            let controller_key = controller_key.as_deref().ok_or("agent requires --controller-key <public.key>")?;
+            agent::serve(address, controller_key, options.clone(), config)
+        },
+        Command::Controller { command, agents } => {
+            let key_path = options.key_path.as_deref().ok_or("controller requires --key <secret.key>")?;
+            controller::run(command, options.csv_path.as_deref(), key_path, agents)
+        },
+        Command::Record { output_path, recording } => recorder::record(output_path, recording),
+        Command::Generate { kind, grid } => generate::run(kind, grid),
+        Command::ClickAt { clicking } => autoclick::run(build_injector(&options, &config, backend_kind(&options, &config))?, clicking.clone()),
+        Command::Jiggle { jiggle } => jiggle::run(build_injector(&options, &config, backend_kind(&options, &config))?, jiggle.clone()),
+        Command::Combo { combo } => {
+            let actions = prepare_script(&combo.script_path, &options, &config, &options.vars)?;
+            combo::run(&actions, combo, max_clicks_per_second(&options, &config))
+        },
+        Command::Fuzz { fuzz } => fuzz::run(build_injector(&options, &config, backend_kind(&options, &config))?, build_guard(&options, &config), fuzz.clone()),
+        Command::Dwell { dwell } => dwell::run(build_injector(&options, &config, backend_kind(&options, &config))?, dwell.or(&config.dwell)),
+        Command::Visualize { script_path, output, background } => {
+            let output = output.as_deref().ok_or("visualize requires -o <path.svg>")?;
+            let screen = Some(Enigo::new().main_display_size()).filter(|&(width, height)| width > 0 && height > 0);
+            visualize::render(&load_script(script_path, &options.vars)?, output, background.as_deref(), screen)
+        },
+        Command::Scripts { command, name, description } => {
+            library::manage(command, options.csv_path.as_deref(), name.as_deref(), description.as_deref())
+        },
+    }
+}
+
+// Run a single script from the command line
+fn run_from_cli(options: &Options, config: &Config) -> Result<(), Box<dyn Error>> {
+    let controls = Controls::new();
+    controls.handle_ctrl_c()?;
+    
+    // Let other processes stop or pause us
+    let _control_server = if options.control || options.control_endpoint.is_some() {
+        let endpoint = options.control_endpoint.clone().unwrap_or_else(control_socket::default_endpoint);
+        Some(control_socket::serve(&endpoint, Arc::clone(&controls))?)
+    } else {
+        None
+    };
+    
+    // The resume hotkey is only needed when we may pause for user input
+    if options.pause_on_user_input {
+        controls.listen_for_resume(resume_key(options)?);
+    }
+    
+    let csv_path = script_path(options.csv_path.as_deref())?;
+    info!("Using CSV file: {}", csv_path);
+    
+    let abort_key = options.abort_key.as_deref().or(config.abort_key.as_deref()).unwrap_or(controls::DEFAULT_ABORT_KEY);
+    let pause_key = options.pause_key.as_deref().or(config.pause_key.as_deref()).unwrap_or(controls::DEFAULT_PAUSE_KEY);
+    
+    // A long recording plays as it is decoded instead of being loaded first,
+    // unless something below needs to look at every row
+    let streamed = if binary::is_binary(Path::new(&csv_path)) {
+        streamable(Path::new(&csv_path), options, config, |record| presses(record, abort_key) || presses(record, pause_key))?
+    } else {
+        None
+    };
+    let actions = match streamed {
+        Some(_) => Vec::new(),
+        None => prepare_script(&csv_path, options, config, &options.vars)?,
+    };
+    
+    // Stop or pause a runaway script without racing it for the cursor
+    if !options.dry_run {
+        let abort = playback_hotkey(&actions, abort_key, "abort", "stop playback")?;
+        let pause = playback_hotkey(&actions, pause_key, "pause", "pause and resume playback")?;
+        controls.listen_for_hotkeys(abort, pause);
+    }
+    
+    // Scripts that reach outside the mouse always ask first unless --yes is given
+    let has_dangerous = actions.iter().any(|record| policy::category(record).is_dangerous());
+    if options.confirm || (has_dangerous && !options.assume_yes) {
+        let move_duration = options.move_duration.or(config.move_duration_ms.map(Duration::from_millis)).unwrap_or_default();
+        print_summary(&actions, move_duration, options.speed.unwrap_or(1.0));
+        if !confirm_run()? {
+            info!("Aborted; nothing was executed.");
+            return Ok(());
+        }
+    }
+    
+    // Time to switch to the target window before the first action
+    if let Some(secs) = options.start_delay_secs.or(config.start_delay_secs).filter(|_| !options.dry_run) {
+        for remaining in (1..=secs).rev() {
+            info!("Starting in {}...", remaining);
+            controls.sleep(Duration::from_secs(1));
+            if controls.abort_requested() {
+                info!("Aborted; nothing was executed.");
+                return Ok(());
+            }
+        }
+    }
+    
+    // A hard limit on the whole run, for CI; the countdown above doesn't count
+    if let Some(secs) = options.max_duration_secs.or(config.max_duration_secs).filter(|_| !options.dry_run) {
+        watchdog::spawn_deadline(Arc::clone(&controls), Duration::from_secs(secs.max(1)));
+    }
+    
+    // --loop 0, or a bare --loop, repeats until stopped; a dry run shows one pass
+    let passes = if options.dry_run { 1 } else { options.loops.unwrap_or(1) };
+    let mut result = Ok(());
+    let mut pass = 0;
+    while passes == 0 || pass < passes {
+        pass += 1;
+        if passes != 1 {
+            info!("Pass {}", pass);
+        }
+        result = match streamed {
+            Some(total_steps) => stream_script(Path::new(&csv_path), total_steps, options, config, &controls),
+            None => execute_script(&actions, options, config, &controls),
+        };
+        if result.is_err() || controls.run_aborted() {
+            break;
+        }
+    }
+    controls.mark_finished();
+    library::record_run(Path::new(&csv_path), result.as_ref().map(|_| ()).map_err(|e| e.to_string()));
+    result?;
+    info!("Automation completed successfully!");
+    Ok(())
+}
+
+// Report likely mistakes in a script; fails if any are errors
+fn lint_script(path: &str, vars: &Vars) -> Result<(), Box<dyn Error>> {
+    let actions = load_script(path, vars)?;
+    
+    // The display size is unknown without a display (e.g. on CI)
+    let screen = Some(Enigo::new().main_display_size()).filter(|&(width, height)| width > 0 && height > 0);
+    if screen.is_none() {
+        warn!("screen size unavailable, skipping bounds checks");
+    }
+    
+    let findings = lint::lint(&actions, screen);
+    for finding in &findings {
+        println!("{}: {}", path, finding);
+    }
+    
+    let errors = findings.iter().filter(|finding| finding.severity == lint::Severity::Error).count();
+    println!("{} error(s), {} warning(s)", errors, findings.len() - errors);
+    if errors > 0 {
+        return Err(format!("{} has {} error(s)", path, errors).into());
+    }
+    Ok(())
+}
+
+#[derive(Parser)]
+#[command(name = "mouse-automation", version, about = "Plays back mouse and keyboard scripts",
+    args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+    // `mouse-automation [options] <script>` is short for `run`
+    #[command(flatten)]
+    run: RunArgs,
+    #[command(flatten)]
+    global: GlobalArgs,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[arg(help = "Script to play: a path, or the name of a script in the library")]
+    script: Option<String>,
+    #[arg(long = "loop", value_name = "N", num_args = 0..=1, default_missing_value = "0",
+        help = "Play the script N times, or until stopped when N is 0 or left out")]
+    loops: Option<u32>,
+}
+
+// Playback settings, accepted before or after any subcommand
+#[derive(Args)]
+struct GlobalArgs {
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet",
+        help = "Print more detail: -v for every step's moves and clicks, -vv for everything")]
+    verbose: u8,
+    #[arg(short, long, global = true, action = clap::ArgAction::Count,
+        help = "Print less: -q for only warnings and errors, -qq for only errors")]
+    quiet: u8,
+    #[arg(long, global = true, value_name = "PATH", help = "Also append every message, with timestamps, to this file")]
+    log_file: Option<String>,
+    #[arg(long, global = true, help = "Pause when the cursor is moved by something other than the script")]
+    pause_on_user_input: bool,
+    #[arg(long, global = true, value_name = "KEY", help = "Hotkey that resumes a paused run (default F9)")]
+    resume_key: Option<String>,
+    #[arg(long, global = true, help = "Windows only: block physical input for the whole run")]
+    block_input: bool,
+    #[arg(long, global = true, help = "Move the cursor back to where it was when the run ends")]
+    restore_cursor: bool,
+    #[arg(long, global = true, help = "Accept stop, pause, resume and status on the control socket")]
+    control: bool,
+    #[arg(long, global = true, value_name = "PATH", help = "Control socket to listen on, or to send ctl commands to")]
+    control_endpoint: Option<String>,
+    #[arg(long, global = true, help = "Print a summary and ask before playing")]
+    confirm: bool,
+    #[arg(long = "yes", global = true, help = "Don't ask before playing scripts with dangerous actions")]
+    assume_yes: bool,
+    #[arg(long, global = true, value_name = "PUBLIC_KEY", help = "Refuse scripts not signed by this key")]
+    require_signature: Option<String>,
+    #[arg(long = "config", global = true, value_name = "PATH", help = "Settings file (default mouse-automation.toml)")]
+    config_path: Option<String>,
+    #[arg(long, global = true, value_name = "CATEGORIES", help = "Refuse scripts using these action categories: pointer, keyboard, window, screen, launch (or shell) and clipboard")]
+    deny: Vec<String>,
+    #[arg(long = "max-clicks-per-sec", global = true, value_name = "N", help = "Ceiling on clicks per second (default 20)")]
+    max_clicks_per_second: Option<u32>,
+    #[arg(long, global = true, value_parser = parsed(OnLock::parse, "pause, abort or ignore"),
+        help = "When the session locks: pause, abort or ignore")]
+    on_lock: Option<OnLock>,
+    #[arg(long, global = true, value_parser = parsed(OutOfBounds::parse, "error, clamp or ignore"),
+        help = "Coordinates outside the screen: error, clamp or ignore")]
+    out_of_bounds: Option<OutOfBounds>,
+    #[arg(long = "only-app", global = true, value_name = "NAME", help = "Only inject while this application is in the foreground")]
+    only_apps: Vec<String>,
+    #[arg(long = "never-app", global = true, value_name = "NAME", help = "Never inject while this application is in the foreground")]
+    never_apps: Vec<String>,
+    #[arg(long, global = true, help = "Never inject into a fullscreen window")]
+    no_fullscreen: bool,
+    #[arg(long, global = true, value_parser = parsed(OnGuard::parse, "pause or abort"),
+        help = "When a foreground condition fails: pause or abort")]
+    on_guard: Option<OnGuard>,
+    #[arg(long, global = true, value_name = "MULTIPLE", value_parser = watchdog_arg,
+        help = "Abort a step that runs this many times as long as its delay, timeout and durations allow")]
+    watchdog: Option<f64>,
+    #[arg(long = "max-duration", global = true, value_name = "SECS", help = "Abort the run once it has taken this long, however far it got")]
+    max_duration_secs: Option<u64>,
+    #[arg(long, global = true, value_name = "PATH", help = "Append every injected event to this JSONL file")]
+    audit_log: Option<String>,
+    #[arg(long, global = true, value_name = "PATH", help = "Remember completed checkpoint sections in this file")]
+    state_file: Option<String>,
+    #[arg(long, global = true, value_name = "FILE", help = "Record the screen during playback, e.g. run.mp4")]
+    record_video: Option<String>,
+    #[arg(long, global = true, value_name = "TITLE", requires = "record_video", help = "Windows only: record just this window")]
+    video_window: Option<String>,
+    #[arg(long, global = true, value_name = "FILE", help = "Write an HTML report of the run")]
+    report: Option<String>,
+    #[arg(long, global = true, help = "Print each step's planned and actual time when the run ends")]
+    timings: bool,
+    #[arg(long, global = true, value_name = "FILE", help = "Also write the timings to this file; implies --timings")]
+    timings_file: Option<String>,
+    #[arg(long, global = true, value_name = "FILE", help = "Append a JSON line for every step executed to this file")]
+    log_json: Option<String>,
+    #[arg(long, global = true, help = "Play each checkpoint section as a test case and write JUnit XML results")]
+    test: bool,
+    #[arg(long, global = true, value_name = "FILE", help = "Where --test writes its results (default junit.xml); implies --test")]
+    junit: Option<String>,
+    #[arg(long, global = true, help = "Recapture assert_region_matches baselines instead of comparing")]
+    update_baselines: bool,
+    #[arg(long, global = true, value_parser = parsed(TypingMode::parse, "auto, unicode or keys"),
+        help = "How text is typed: auto, unicode or keys")]
+    typing: Option<TypingMode>,
+    #[arg(long, global = true, value_parser = parsed(BackendKind::parse, "auto, enigo, uinput or simulated"),
+        help = "What delivers the events: enigo (the default), auto, uinput, or simulated to inject nothing")]
+    backend: Option<BackendKind>,
+    #[arg(long, global = true, value_name = "DIR", help = "Where screenshots of failed steps go (default failures)")]
+    failure_dir: Option<String>,
+    #[arg(long, global = true, help = "Don't capture a screenshot when a step fails")]
+    no_failure_screenshots: bool,
+    #[arg(long, global = true, help = "Don't check injection permissions before starting")]
+    skip_preflight: bool,
+    #[arg(long, global = true, value_parser = speed_arg,
+        help = "Scale every delay and movement by this factor: 0.5 plays twice as fast, 2 half as fast")]
+    speed: Option<f64>,
+    #[arg(long, global = true, help = "Check the script and print its steps without injecting anything")]
+    dry_run: bool,
+    #[arg(long, global = true, value_name = "FILE",
+        help = "Draw the dry run's steps over a capture of the screen as an SVG; implies --dry-run")]
+    overlay: Option<String>,
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parsed(parse_duration, "a duration such as 150ms"),
+        help = "Glide the cursor to each position over this long instead of jumping")]
+    move_duration: Option<Duration>,
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parsed(parse_duration, "a duration such as 80ms"),
+        help = "Pause between the clicks of a double or triple click (default 10ms)")]
+    click_interval: Option<Duration>,
+    #[arg(long, global = true, help = "Add jitter, curved paths and varied click timing, like a hand")]
+    humanize: bool,
+    #[arg(long, global = true, value_name = "HOTKEY", help = "Hotkey that stops playback, e.g. ctrl+shift+q, or none (default esc)")]
+    abort_key: Option<String>,
+    #[arg(long, global = true, value_name = "HOTKEY", help = "Hotkey that pauses playback and resumes it, or none (default f8)")]
+    pause_key: Option<String>,
+    #[arg(long = "start-delay", global = true, value_name = "SECS", help = "Count down this long before the first action")]
+    start_delay_secs: Option<u64>,
+    #[arg(long, global = true, value_name = "ROW", help = "Start playback at this row (CSV line number)")]
+    from_row: Option<usize>,
+    #[arg(long, global = true, value_name = "ROW", help = "Stop playback after this row")]
+    to_row: Option<usize>,
+    #[arg(long, global = true, help = "Stop playback when the mouse is pushed into a screen corner")]
+    failsafe: bool,
+    #[arg(long, global = true, help = "Wait for Enter after every step, to find the row that misbehaves")]
+    step: bool,
+    #[arg(long, global = true, help = "Carry on past failed steps, then list them and fail at the end")]
+    keep_going: bool,
+    #[arg(long, global = true, help = "Treat positions as logical pixels and scale them by the display's scaling")]
+    logical_pixels: bool,
+    #[arg(long = "var", visible_alias = "set", global = true, value_name = "NAME=VALUE", value_parser = var_arg,
+        help = "Fill in ${name} placeholders in the script")]
+    vars: Vec<(String, String)>,
+    #[arg(long = "target", global = true, value_name = "tcp://HOST:PORT", help = "Play the script on a remote agent instead")]
+    target: Option<String>,
+    #[arg(long = "key", global = true, value_name = "SECRET_KEY", help = "Controller key for authenticating with agents")]
+    key_path: Option<String>,
+    #[arg(long = "ws", global = true, value_name = "ADDRESS", help = "Also serve the WebSocket API here (serve)")]
+    ws_address: Option<String>,
+    #[arg(long = "grpc", global = true, value_name = "ADDRESS", help = "Also serve the gRPC API here (serve)")]
+    grpc_address: Option<String>,
+    #[arg(long = "metrics", global = true, value_name = "ADDRESS", help = "Serve Prometheus metrics here")]
+    metrics_address: Option<String>,
+    #[arg(long, global = true, value_name = "DIR", help = "Directory of scripts the APIs can run")]
+    scripts_dir: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    #[command(about = "Play a script (the default)")]
+    Run(RunArgs),
+    #[command(alias = "lint", about = "Check a script for mistakes without running it")]
+    Validate { script: String },
+    #[command(about = "Rewrite a script as plain CSV, rendering templates and variables")]
+    Convert { input: String, output: String },
+    #[command(about = "Record mouse and keyboard input into a script")]
+    Record {
+        output: String,
+        #[arg(long, value_name = "KEY", help = "Key that ends the recording (default F10)")]
+        stop_key: Option<String>,
+        #[arg(long, help = "Leave out cursor movement between clicks")]
+        no_moves: bool,
+    },
+    #[command(about = "Create a key pair for signing scripts")]
+    Keygen { secret_key: String, public_key: String },
+    #[command(about = "Sign a script, writing <script>.sig")]
+    Sign { script: String, secret_key: String },
+    #[command(about = "Check the signature of a script")]
+    Verify { script: String, public_key: String },
+    #[command(about = "Send stop, pause, resume or status to a running playback")]
+    Ctl { command: String },
+    #[command(about = "Serve the HTTP API")]
+    Serve {
+        #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+        http: String,
+        #[arg(long, value_name = "FILE", help = "Token every request must carry as Authorization: Bearer")]
+        token_file: Option<String>,
+        #[arg(long = "allow-origin", value_name = "ORIGIN", help = "Web page allowed to use the API, e.g. http://localhost:3000; can be repeated")]
+        allow_origins: Vec<String>,
+    },
+    #[command(about = "Run scripts on MQTT messages")]
+    Mqtt {
+        #[arg(long, value_name = "ADDRESS", help = "Broker to connect to, e.g. localhost:1883")]
+        broker: Option<String>,
+    },
+    #[command(about = "Run scripts on webhook requests")]
+    Webhook {
+        #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8090")]
+        listen: String,
+        #[arg(long, value_name = "FILE", help = "Shared secret requests are signed with")]
+        secret_file: Option<String>,
+    },
+    #[command(about = "Run scripts on global hotkeys")]
+    Hotkeys,
+    #[command(about = "Run scripts when the clipboard matches a rule")]
+    Clipboard,
+    #[command(about = "Run a script for every new file in a directory")]
+    Watch {
+        dir: String,
+        script: String,
+        #[arg(long, value_name = "GLOB", help = "Only files matching this pattern")]
+        pattern: Option<String>,
+    },
+    #[command(about = "Accept scripts from a controller")]
+    Agent {
+        #[arg(long, value_name = "ADDRESS", help = "Address to listen on (default 0.0.0.0:47900)")]
+        listen: Option<String>,
+        #[arg(long, value_name = "PUBLIC_KEY")]
+        controller_key: Option<String>,
+    },
+    #[command(about = "Push, run or control scripts on agents")]
+    Controller {
+        command: String,
+        script: Option<String>,
+        #[arg(long, value_name = "HOST,...", value_delimiter = ',')]
+        agents: Vec<String>,
+        #[arg(long, value_name = "FILE", help = "File listing one agent per line")]
+        agents_file: Vec<String>,
+    },
+    #[command(about = "List, add, remove or show scripts in the library")]
+    Scripts {
+        command: String,
+        script: Option<String>,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    #[command(about = "Generate a script, e.g. a grid of clicks")]
+    Generate {
+        kind: String,
+        #[arg(long, value_name = "X,Y", value_parser = parsed(generate::parse_point, "x,y"))]
+        origin: Option<(i32, i32)>,
+        #[arg(long, value_name = "WxH", value_parser = parsed(generate::parse_size, "<width>x<height>"))]
+        cell: Option<(u32, u32)>,
+        #[arg(long, value_name = "COLUMNSxROWS", value_parser = parsed(generate::parse_size, "<columns>x<rows>"))]
+        grid: Option<(u32, u32)>,
+        #[arg(long, value_name = "MS")]
+        delay: Option<u64>,
+        #[arg(long, value_parser = parsed(generate::Order::parse, "row-major, column-major, snake or spiral"))]
+        order: Option<generate::Order>,
+        #[arg(short = 'o', value_name = "FILE")]
+        output: Option<String>,
+    },
+    #[command(about = "Draw a script's path as an SVG")]
+    Visualize {
+        script: String,
+        #[arg(short = 'o', value_name = "FILE")]
+        output: Option<String>,
+        #[arg(long, value_name = "IMAGE", help = "Screenshot to draw the path over")]
+        background: Option<String>,
+    },
+    #[command(about = "Click repeatedly at the cursor or a point")]
+    ClickAt {
+        #[arg(long, value_parser = duration_arg, help = "Time between clicks (default 100ms)")]
+        interval: Option<Duration>,
+        #[arg(long = "for", value_parser = duration_arg, help = "Stop after this long")]
+        duration: Option<Duration>,
+        #[arg(long, value_name = "X,Y", value_parser = parsed(generate::parse_point, "x,y"))]
+        at: Option<(i32, i32)>,
+        #[arg(long, help = "left, right, middle, back, forward or an X11 button number (1-9)")]
+        button: Option<String>,
+        #[arg(long, value_name = "KEY")]
+        stop_key: Option<String>,
+    },
+    #[command(about = "Nudge the cursor now and then to keep the machine awake")]
+    Jiggle {
+        #[arg(long, value_parser = duration_arg, help = "Time between nudges (default 60s)")]
+        interval: Option<Duration>,
+        #[arg(long = "for", value_parser = duration_arg, help = "Stop after this long")]
+        duration: Option<Duration>,
+        #[arg(long, value_name = "PIXELS")]
+        amplitude: Option<i32>,
+        #[arg(long, value_parser = parsed(jiggle::Pattern::parse, "alternate or zero-net"))]
+        pattern: Option<jiggle::Pattern>,
+    },
+    #[command(about = "Play a short script with minimal latency on a hotkey")]
+    Combo {
+        script: String,
+        #[arg(long, value_name = "KEY")]
+        trigger: Option<String>,
+        #[arg(long, help = "Send relative moves as raw mouse input (Windows)")]
+        raw: bool,
+    },
+    #[command(about = "Send random input to a region of the screen")]
+    Fuzz {
+        #[arg(long, value_name = "X,Y,WxH", value_parser = parsed(fuzz::parse_region, "x,y,<width>x<height>"))]
+        region: Option<Region>,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, value_parser = duration_arg, help = "Time between events (default 200ms)")]
+        interval: Option<Duration>,
+        #[arg(long = "for", value_parser = duration_arg, help = "Stop after this long (default 60s)")]
+        duration: Option<Duration>,
+        #[arg(long, value_name = "FILE", help = "Script the events are written to (default fuzz-<seed>.csv)")]
+        log: Option<String>,
+        #[arg(long, value_name = "KEY")]
+        stop_key: Option<String>,
+    },
+    #[command(about = "Click wherever the cursor comes to rest")]
+    Dwell {
+        #[arg(long, value_name = "MS")]
+        dwell_ms: Option<u64>,
+        #[arg(long, value_name = "PIXELS")]
+        radius: Option<i32>,
+        #[arg(long, value_name = "KEY")]
+        toggle_key: Option<String>,
+        #[arg(long, help = "left, right, middle, back, forward or an X11 button number (1-9)")]
+        button: Option<String>,
+    },
+}
+
+// Parse the command line into options for the library
+fn parse_options() -> Result<Options, Box<dyn Error>> {
+    let cli = Cli::parse();
+    let global = cli.global;
+    let mut options = Options {
+        verbose: global.verbose,
+        quiet: global.quiet,
+        log_file: global.log_file,
+        pause_on_user_input: global.pause_on_user_input,
+        resume_key: global.resume_key,
+        block_input: global.block_input,
+        confirm: global.confirm,
+        assume_yes: global.assume_yes,
+        require_signature: global.require_signature,
+        config_path: global.config_path,
+        deny: global.deny,
+        max_clicks_per_second: global.max_clicks_per_second,
+        on_lock: global.on_lock,
+        restore_cursor: global.restore_cursor,
+        control: global.control,
+        control_endpoint: global.control_endpoint,
+        out_of_bounds: global.out_of_bounds,
+        state_file: global.state_file,
+        skip_preflight: global.skip_preflight,
+        watchdog: global.watchdog,
+        max_duration_secs: global.max_duration_secs,
+        audit_log: global.audit_log,
+        scripts_dir: global.scripts_dir,
+        ws_address: global.ws_address,
+        grpc_address: global.grpc_address,
+        key_path: global.key_path,
+        target: global.target,
+        vars: global.vars.into_iter().collect(),
+        only_apps: global.only_apps,
+        never_apps: global.never_apps,
+        no_fullscreen: global.no_fullscreen,
+        on_guard: global.on_guard,
+        record_video: global.record_video,
+        video_window: global.video_window,
+        failure_dir: global.failure_dir,
+        no_failure_screenshots: global.no_failure_screenshots,
+        report: global.report,
+        timings: global.timings || global.timings_file.is_some(),
+        timings_file: global.timings_file,
+        log_json: global.log_json,
+        test: global.test || global.junit.is_some(),
+        junit: global.junit,
+        metrics_address: global.metrics_address,
+        typing: global.typing,
+        backend: global.backend,
+        update_baselines: global.update_baselines,
+        speed: global.speed,
+        dry_run: global.dry_run || global.overlay.is_some(),
+        overlay: global.overlay,
+        move_duration: global.move_duration,
+        click_interval: global.click_interval,
+        humanize: global.humanize,
+        abort_key: global.abort_key,
+        pause_key: global.pause_key,
+        failsafe: global.failsafe,
+        start_delay_secs: global.start_delay_secs,
+        step: global.step,
+        keep_going: global.keep_going,
+        logical_pixels: global.logical_pixels,
+        from_row: global.from_row,
+        to_row: global.to_row,
+        ..Options::default()
+    };
+    
+    options.command = match cli.command.unwrap_or(CliCommand::Run(cli.run)) {
+        CliCommand::Run(run) => {
+            options.csv_path = run.script;
+            options.loops = run.loops;
+            Command::Run
+        },
+        CliCommand::Validate { script } => Command::Lint { script_path: script },
+        CliCommand::Convert { input, output } => Command::Convert { input, output },
+        CliCommand::Record { output, stop_key, no_moves } => {
+            Command::Record { output_path: output, recording: recorder::RecordOptions { stop_key, no_moves } }
+        },
+        CliCommand::Keygen { secret_key, public_key } => Command::Keygen { secret_path: secret_key, public_path: public_key },
+        CliCommand::Sign { script, secret_key } => Command::Sign { script_path: script, secret_path: secret_key },
+        CliCommand::Verify { script, public_key } => Command::Verify { script_path: script, public_path: public_key },
+        CliCommand::Ctl { command } => Command::Control { command },
+        CliCommand::Serve { http, token_file, allow_origins } => Command::Serve { address: http, token_file, allow_origins },
+        CliCommand::Mqtt { broker } => Command::Mqtt { broker },
+        CliCommand::Webhook { listen, secret_file } => Command::Webhook { address: listen, secret_file },
+        CliCommand::Hotkeys => Command::Hotkeys,
+        CliCommand::Clipboard => Command::Clipboard,
+        CliCommand::Watch { dir, script, pattern } => Command::Watch { dir, script, pattern },
+        CliCommand::Agent { listen, controller_key } => {
+            let address = listen.unwrap_or_else(|| format!("0.0.0.0:{}", agent::DEFAULT_AGENT_PORT));
+            Command::Agent { address, controller_key }
+        },
+        CliCommand::Controller { command, script, agents, agents_file } => {
+            options.csv_path = script;
+            let mut agents: Vec<String> = agents.iter().map(|agent| agent.trim().to_string()).filter(|agent| !agent.is_empty()).collect();
+            for path in &agents_file {
+                agents.extend(controller::read_agents_file(path)?);
+            }
+            Command::Controller { command, agents }
+        },
+        CliCommand::Scripts { command, script, name, description } => {
+            options.csv_path = script;
+            Command::Scripts { command, name, description }
+        },
+        CliCommand::Generate { kind, origin, cell, grid, delay, order, output } => {
+            let grid = generate::GridOptions { origin, cell, size: grid, delay_ms: delay, order: order.unwrap_or_default(), output };
+            Command::Generate { kind, grid }
+        },
+        CliCommand::Visualize { script, output, background } => Command::Visualize { script_path: script, output, background },
+        CliCommand::ClickAt { interval, duration, at, button, stop_key } => {
+            Command::ClickAt { clicking: autoclick::ClickAtOptions { interval, button, at, duration, stop_key } }
+        },
+        CliCommand::Jiggle { interval, duration, amplitude, pattern } => {
+            let jiggle = jiggle::JiggleOptions { interval, amplitude, pattern: pattern.unwrap_or_default(), duration };
+            Command::Jiggle { jiggle }
+        },
+        CliCommand::Combo { script, trigger, raw } => Command::Combo { combo: combo::ComboOptions { script_path: script, trigger, raw } },
+        CliCommand::Fuzz { region, seed, interval, duration, log, stop_key } => {
+            Command::Fuzz { fuzz: fuzz::FuzzOptions { region, seed, duration, interval, log, stop_key } }
+        },
+        CliCommand::Dwell { dwell_ms, radius, toggle_key, button } => {
+            Command::Dwell { dwell: dwell::DwellOptions { dwell_ms, radius, button, toggle_key } }
+        },
+    };
+    
+    // These act on the local desktop, which a remote run doesn't touch
+    let local_only = options.block_input || options.restore_cursor || options.pause_on_user_input || options.record_video.is_some()
+        || options.move_duration.is_some() || options.click_interval.is_some() || options.humanize || options.logical_pixels
+        || options.backend.is_some();
+    if options.target.is_some() && local_only {
+        return Err("--block-input, --restore-cursor, --pause-on-user-input, --record-video, --move-duration, --click-interval, --humanize, --logical-pixels and --backend can't be combined with --target".into());
+    }
+    
+    Ok(options)
+}
+
+// Adapt one of the `parse(&str) -> Option<T>` functions for clap
+fn parsed<T: 'static>(parse: fn(&str) -> Option<T>, expected: &'static str) -> impl Fn(&str) -> Result<T, String> + Clone + Send + Sync + 'static {
+    move |value| parse(value).ok_or_else(|| format!("expected {}", expected))
+}
+
+// A non-zero duration for --interval and --for
+fn duration_arg(value: &str) -> Result<Duration, String> {
+    parse_duration(value).filter(|duration| !duration.is_zero()).ok_or_else(|| "expected a duration such as 250ms or 10m".to_string())
+}
+
+fn speed_arg(value: &str) -> Result<f64, String> {
+    value.parse().ok().filter(|factor: &f64| (controls::MIN_DELAY_SCALE..=controls::MAX_DELAY_SCALE).contains(factor))
+        .ok_or_else(|| format!("expected a factor from {} to {} such as 0.5 or 2", controls::MIN_DELAY_SCALE, controls::MAX_DELAY_SCALE))
+}
+
+fn watchdog_arg(value: &str) -> Result<f64, String> {
+    value.parse().ok().filter(|&multiple: &f64| multiple >= 1.0 && multiple.is_finite())
+        .ok_or_else(|| "expected a multiple of at least 1 such as 3".to_string())
+}
+
+fn var_arg(value: &str) -> Result<(String, String), String> {
+    vars::parse_assignment(value).ok_or_else(|| "expected name=value".to_string())
+}
+
+// A hotkey for controlling playback, or None when turned off with "none"
+fn playback_hotkey(actions: &[MouseAction], name: &str, kind: &str, purpose: &str) -> Result<Option<(Hotkey, String)>, Box<dyn Error>> {
+    if name.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    let hotkey = Hotkey::parse(name).ok_or_else(|| format!("Unknown {} key: {}", kind, name))?;
+    // The listener also sees the keys the script itself presses
+    if actions.iter().any(|record| presses(record, name)) {
+        warn!("the script presses {} itself, which triggers it; choose another key with --{}-key", name, kind);
+    }
+    info!("Press {} to {}", name, purpose);
+    Ok(Some((hotkey, name.to_string())))
+}
+
+// Whether a key row presses the final key of a hotkey
+fn presses(record: &MouseAction, hotkey: &str) -> bool {
+    let last_key = |spec: &str| spec.rsplit('+').next().and_then(|name| controls::parse_key(name.trim()));
+    matches!(record.action.as_str(), "key_press" | "key_down")
+        && record.key.as_deref().and_then(last_key).is_some_and(|key| Some(key) == last_key(hotkey))
+}
+
+// Rough playback time in milliseconds, from delays, moves and built-in pauses
+fn estimated_duration_ms(actions: &[MouseAction], move_duration: Duration, speed: f64) -> u64 {
+    // Totals of the loops still open, each with its pass count, outermost first
+    let mut loops: Vec<(u64, u64)> = Vec::new();
+    let mut total = 0;
+    for record in actions {
+        let duration = match record.action.as_str() {
+            "loop_start" => {
+                loops.push((0, record.repeat_count.unwrap_or(1) as u64));
+                continue;
+            },
+            "loop_end" => match loops.pop() {
+                Some((body, passes)) => body * passes,
+                None => 0,
+            },
+            _ => timings::planned_ms(record, move_duration),
+        };
+        match loops.last_mut() {
+            Some((body, _)) => *body += duration,
+            None => total += duration,
+        }
+    }
+    (total as f64 * speed) as u64
+}
+
+// Print what the script is about to do
+fn print_summary(actions: &[MouseAction], move_duration: Duration, speed: f64) {
+    println!("Script summary:");
+    println!("  Steps: {}", actions.len());
+    println!("  Estimated duration: {:.1}s", estimated_duration_ms(actions, move_duration, speed) as f64 / 1000.0);
+    
+    let dangerous: Vec<_> = actions.iter().enumerate()
+        .filter(|(_, record)| policy::category(record).is_dangerous())
+        .collect();
+    if dangerous.is_empty() {
+        println!("  Dangerous actions: none");
+    } else {
+        println!("  Dangerous actions:");
+        for (index, record) in dangerous {
+            println!("    step {}: {}", index + 1, record.action);
+        }
+    }
+}
+
+// Ask the user to type "yes" before the mouse starts moving
+fn confirm_run() -> Result<bool, Box<dyn Error>> {
+    print!("Type 'yes' to start: ");
+    io::stdout().flush()?;
+    
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+// The requested script: a path, or a name in the script library
+fn script_path(requested: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let requested = requested.ok_or("No script given; usage: mouse-automation run <script.csv>")?;
+    Ok(library::resolve(requested)?.to_string_lossy().into_owned())
+}
@@ -0,0 +1,85 @@
+// Clipboard trigger, started by the `clipboard` subcommand.
+//
+// Polls the clipboard text and, when it changes and matches one of the
+// `[[clipboard]]` rules in the config, runs the rule's script with the match
+// as variables:
+//   ${clip}         the whole match
+//   ${clip_1}, ...  numbered capture groups
+//   ${<name>}       named capture groups
+//
+// Changes made while a script runs are ignored so copy and paste actions in
+// the script can't retrigger it.
+
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+use arboard::Clipboard;
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::runs::Runs;
+use crate::vars::Vars;
+use crate::Options;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClipboardRule {
+    pub pattern: String,
+    pub script: String,
+}
+
+pub fn run(options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    if config.clipboard.is_empty() {
+        return Err("No clipboard rules configured; add [[clipboard]] tables with a pattern and a script to the config".into());
+    }
+    let mut rules = Vec::new();
+    for rule in &config.clipboard {
+        let regex = Regex::new(&rule.pattern)
+            .map_err(|e| format!("Invalid clipboard pattern '{}': {}", rule.pattern, e))?;
+        info!("'{}' -> {}", rule.pattern, rule.script);
+        rules.push((regex, rule.script.clone()));
+    }
+
+    let runs = Runs::new(options, config)?;
+    let mut clipboard = Clipboard::new().map_err(|e| format!("Failed to open the clipboard: {}", e))?;
+    // Whatever is on the clipboard at startup doesn't count as a copy
+    let mut last = clipboard.get_text().unwrap_or_default();
+    info!("Watching the clipboard (scripts in {})", runs.scripts_dir().display());
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        // Non-text contents (images, files) read as errors and are skipped
+        let Ok(text) = clipboard.get_text() else { continue };
+        if text == last {
+            continue;
+        }
+        last = text;
+        if runs.is_busy() {
+            continue;
+        }
+
+        let Some((script, vars)) = rules.iter().find_map(|(regex, script)| Some((script, capture_vars(regex, &last)?))) else {
+            continue;
+        };
+        info!("Clipboard matched '{}': starting {}", vars["clip"], script);
+        if let Err(error) = runs.start_with_vars(script, &vars) {
+            warn!("Could not start {}: {}", script, error);
+        }
+    }
+}
+
+fn capture_vars(regex: &Regex, text: &str) -> Option<Vars> {
+    let captures = regex.captures(text)?;
+    let mut vars = Vars::new();
+    vars.insert("clip".to_string(), captures.get(0)?.as_str().to_string());
+    for (index, name) in regex.capture_names().enumerate().skip(1) {
+        let value = captures.get(index).map_or("", |group| group.as_str()).to_string();
+        if let Some(name) = name {
+            vars.insert(name.to_string(), value.clone());
+        }
+        vars.insert(format!("clip_{}", index), value);
+    }
+    Some(vars)
+}
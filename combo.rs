@@ -0,0 +1,297 @@
+// Low-latency combo mode, the `combo` subcommand.
+//
+// Plays a short script every time a hotkey is pressed, for gaming-style
+// click combos where millisecond consistency matters more than the safety
+// checks and logging of normal playback. The script is resolved up front
+// into a plan of input operations at fixed offsets from the trigger, so the
+// hot path only sleeps and injects: no parsing, no printing, no audit log.
+// The last couple of milliseconds before each operation are spun rather than
+// slept, and `--raw` sends relative moves as raw mouse deltas on Windows,
+// which games reading raw input see (absolute moves there are ignored).
+//
+// Supported actions: move, move_relative, click, double_click, triple_click,
+// click_and_hold, right_click, drag, release, drag_to, the drawing actions,
+// scroll, wait, key_press, key_down and key_up.
+
+use std::error::Error;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+use enigo::{Enigo, Key, KeyboardControllable, MouseButton, MouseControllable};
+use rdev::{listen, EventType};
+use tracing::{info, warn};
+use crate::controls::{self, Controls};
+use crate::rate_limit::ClickLimiter;
+use crate::stroke;
+use crate::{keyboard, lint, parse_button, scroll_direction, MouseAction};
+
+// Sleeping is only trusted up to this close to a deadline; the rest is spun
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+// Between the clicks of a double or triple click unless the row sets interval_ms, as in playback
+const CLICK_INTERVAL: Duration = Duration::from_millis(10);
+// How often a drag_to or drawing row moves, as in playback
+const DRAG_STEP: Duration = Duration::from_millis(8);
+
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    MoveTo(i32, i32),
+    MoveRelative(i32, i32),
+    Click(MouseButton),
+    ButtonDown(MouseButton),
+    ButtonUp(MouseButton),
+    Scroll(i32, i32),
+    KeyDown(Key),
+    KeyUp(Key),
+    KeyClick(Key),
+}
+
+// An operation and when it happens, measured from the trigger
+struct Planned {
+    at: Duration,
+    operation: Operation,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ComboOptions {
+    pub script_path: String,
+    pub trigger: Option<String>,
+    pub raw: bool,
+}
+
+pub fn run(actions: &[MouseAction], options: &ComboOptions, max_clicks_per_second: u32) -> Result<(), Box<dyn Error>> {
+    let plan = build_plan(actions)?;
+    let key_name = options.trigger.as_deref().ok_or("combo needs --trigger <key>")?;
+    let trigger = controls::parse_key(key_name).ok_or_else(|| format!("Unknown trigger key: {}", key_name))?;
+    let length = plan.last().map_or(Duration::ZERO, |planned| planned.at);
+
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let result = listen(move |event| {
+            if event.event_type == EventType::KeyPress(trigger) {
+                let _ = sender.send(Instant::now());
+            }
+        });
+        if let Err(error) = result {
+            warn!("trigger listener failed: {:?}", error);
+        }
+    });
+    let controls = Controls::new();
+    controls.handle_ctrl_c()?;
+    raise_timer_resolution();
+
+    let mut enigo = Enigo::new();
+    let mut click_limiter = ClickLimiter::new(max_clicks_per_second);
+    // Allocated once; how late each operation of a run was, reported after the run
+    let mut lateness = Vec::with_capacity(plan.len());
+    let mut held_keys: Vec<Key> = Vec::new();
+    info!("Combo of {} operation(s) over {} ms armed; press {} to fire it, Ctrl+C to quit",
+        plan.len(), length.as_millis(), key_name);
+
+    while !controls.abort_requested() {
+        let Ok(pressed) = receiver.recv_timeout(Duration::from_millis(100)) else { continue };
+        // Presses queued while the combo was running would fire it again straight away
+        while receiver.try_recv().is_ok() {}
+
+        lateness.clear();
+        let started = Instant::now();
+        for planned in &plan {
+            let deadline = started + planned.at;
+            wait_until(deadline);
+            lateness.push(Instant::now().saturating_duration_since(deadline));
+            perform(&mut enigo, &mut click_limiter, planned.operation, options.raw, &mut held_keys);
+        }
+        // A combo never leaves keys held between triggers
+        for key in held_keys.drain(..).rev() {
+            enigo.key_up(key);
+        }
+
+        let worst = lateness.iter().max().copied().unwrap_or_default();
+        info!("Fired {} ms after the hotkey, took {:.2} ms (planned {} ms), worst lateness {} us",
+            started.duration_since(pressed).as_millis(), started.elapsed().as_secs_f64() * 1000.0,
+            length.as_millis(), worst.as_micros());
+    }
+    Ok(())
+}
+
+fn build_plan(actions: &[MouseAction]) -> Result<Vec<Planned>, Box<dyn Error>> {
+    let mut plan = Vec::new();
+    let mut at = Duration::ZERO;
+    // Modifiers a drag row holds until the release row, as in playback
+    let mut drag_modifiers: Vec<Key> = Vec::new();
+    for (index, record) in actions.iter().enumerate() {
+        let step = || format!("Step {} (row {})", index + 1, lint::row_number(index));
+        at += Duration::from_millis(record.delay_ms.unwrap_or(0));
+        let position = record.x_position.zip(record.y_position);
+        let button = match record.button.as_deref() {
+            Some(name) => parse_button(name).ok_or_else(|| format!("{}: unknown button '{}'", step(), name))?,
+            None => MouseButton::Left,
+        };
+        let modifiers = match record.modifiers.as_deref() {
+            Some(spec) if keyboard::takes_modifiers(&record.action) => keyboard::parse_modifiers(spec).map_err(|e| format!("{}: {}", step(), e))?,
+            _ => Vec::new(),
+        };
+
+        let mut push = |at: Duration, operation: Operation| plan.push(Planned { at, operation });
+        for _ in 0..record.repeat_count.unwrap_or(1) {
+            // These move to their coordinates first
+            if let ("move" | "click" | "double_click" | "triple_click" | "click_and_hold" | "right_click" | "drag" | "release", Some((x, y))) = (record.action.as_str(), position) {
+                push(at, Operation::MoveTo(x, y));
+            }
+            for &key in modifiers.iter().filter(|key| !drag_modifiers.contains(key)) {
+                push(at, Operation::KeyDown(key));
+            }
+            match record.action.as_str() {
+                "move" | "wait" => {},
+                "move_relative" => {
+                    if let Some((dx, dy)) = position {
+                        push(at, Operation::MoveRelative(dx, dy));
+                    }
+                },
+                "click" => push(at, Operation::Click(button)),
+                "click_and_hold" => {
+                    push(at, Operation::ButtonDown(button));
+                    at += Duration::from_millis(record.duration_ms.unwrap_or(0));
+                    push(at, Operation::ButtonUp(button));
+                },
+                "right_click" => push(at, Operation::Click(MouseButton::Right)),
+                "double_click" | "triple_click" => {
+                    let interval = record.interval_ms.map_or(CLICK_INTERVAL, Duration::from_millis);
+                    push(at, Operation::Click(button));
+                    for _ in 1..if record.action == "triple_click" { 3 } else { 2 } {
+                        at += interval;
+                        push(at, Operation::Click(button));
+                    }
+                },
+                "drag_to" | "draw_circle" | "draw_rectangle" | "draw_polyline" => {
+                    let points = stroke::outline(record, |x, y| Ok((x, y))).map_err(|e| format!("{}: {}", step(), e))?;
+                    push(at, Operation::MoveTo(points[0].0, points[0].1));
+                    push(at, Operation::ButtonDown(button));
+                    // In steps, since a button that jumps while held isn't taken for a drag
+                    let duration = Duration::from_millis(stroke::duration_ms(record));
+                    let steps = ((duration.as_millis() / DRAG_STEP.as_millis()) as u32).max(1);
+                    for part in 1..=steps {
+                        at += duration / steps;
+                        let (x, y) = stroke::along(&points, f64::from(part) / f64::from(steps));
+                        push(at, Operation::MoveTo(x, y));
+                    }
+                    push(at, Operation::ButtonUp(button));
+                },
+                "drag" => push(at, Operation::ButtonDown(MouseButton::Left)),
+                "release" => push(at, Operation::ButtonUp(MouseButton::Left)),
+                "scroll" => {
+                    let (dx, dy) = scroll_direction(record);
+                    push(at, Operation::Scroll(dx, dy));
+                },
+                "key_press" => {
+                    let keys = keyboard::parse_chord(record.key.as_deref().unwrap_or("")).map_err(|e| format!("{}: {}", step(), e))?;
+                    let Some((&key, modifiers)) = keys.split_last() else { continue };
+                    for &modifier in modifiers {
+                        push(at, Operation::KeyDown(modifier));
+                    }
+                    push(at, Operation::KeyClick(key));
+                    for &modifier in modifiers.iter().rev() {
+                        push(at, Operation::KeyUp(modifier));
+                    }
+                },
+                "key_down" | "key_up" => {
+                    let name = record.key.as_deref().unwrap_or("");
+                    let key = keyboard::parse_key(name).ok_or_else(|| format!("{}: unknown key '{}'", step(), name))?;
+                    push(at, if record.action == "key_down" { Operation::KeyDown(key) } else { Operation::KeyUp(key) });
+                },
+                action => return Err(format!("{}: '{}' isn't supported in combo mode", step(), action).into()),
+            }
+
+            let mut pressed = if record.action == "release" { std::mem::take(&mut drag_modifiers) } else { Vec::new() };
+            for &key in &modifiers {
+                if !pressed.contains(&key) && !drag_modifiers.contains(&key) {
+                    pressed.push(key);
+                }
+            }
+            if record.action == "drag" {
+                drag_modifiers.append(&mut pressed);
+            } else {
+                for &key in pressed.iter().rev() {
+                    push(at, Operation::KeyUp(key));
+                }
+            }
+        }
+    }
+    if plan.is_empty() {
+        return Err("The combo script has no input to send".into());
+    }
+    Ok(plan)
+}
+
+// Sleep most of the way, then spin for precision
+fn wait_until(deadline: Instant) {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining > SPIN_MARGIN {
+        thread::sleep(remaining - SPIN_MARGIN);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+fn perform(enigo: &mut Enigo, click_limiter: &mut ClickLimiter, operation: Operation, raw: bool, held_keys: &mut Vec<Key>) {
+    match operation {
+        Operation::MoveTo(x, y) => enigo.mouse_move_to(x, y),
+        Operation::MoveRelative(dx, dy) if raw => raw_move_relative(enigo, dx, dy),
+        Operation::MoveRelative(dx, dy) => enigo.mouse_move_relative(dx, dy),
+        Operation::Click(button) => {
+            click_limiter.wait();
+            enigo.mouse_click(button);
+        },
+        Operation::ButtonDown(button) => {
+            click_limiter.wait();
+            enigo.mouse_down(button);
+        },
+        Operation::ButtonUp(button) => enigo.mouse_up(button),
+        Operation::Scroll(0, dy) => enigo.mouse_scroll_y(dy),
+        Operation::Scroll(dx, _) => enigo.mouse_scroll_x(dx),
+        Operation::KeyDown(key) => {
+            enigo.key_down(key);
+            held_keys.push(key);
+        },
+        Operation::KeyUp(key) => {
+            enigo.key_up(key);
+            held_keys.retain(|held| *held != key);
+        },
+        Operation::KeyClick(key) => enigo.key_click(key),
+    }
+}
+
+// A relative MOUSEEVENTF_MOVE, which reaches raw input unlike a cursor warp
+#[cfg(windows)]
+fn raw_move_relative(_enigo: &mut Enigo, dx: i32, dy: i32) {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEEVENTF_MOVE, MOUSEINPUT};
+
+    let input = INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT { dx, dy, mouseData: 0, dwFlags: MOUSEEVENTF_MOVE, time: 0, dwExtraInfo: 0 },
+        },
+    };
+    // SAFETY: one fully initialized INPUT of the size we pass
+    unsafe {
+        SendInput(1, &input, std::mem::size_of::<INPUT>() as i32);
+    }
+}
+
+// XTest and Quartz relative moves are already deltas
+#[cfg(not(windows))]
+fn raw_move_relative(enigo: &mut Enigo, dx: i32, dy: i32) {
+    enigo.mouse_move_relative(dx, dy);
+}
+
+// Windows sleeps in 15.6 ms ticks unless asked for 1 ms; the request lasts until the process exits
+#[cfg(windows)]
+fn raise_timer_resolution() {
+    // SAFETY: takes a plain value
+    unsafe {
+        windows_sys::Win32::Media::timeBeginPeriod(1);
+    }
+}
+
+#[cfg(not(windows))]
+fn raise_timer_resolution() {}
@@ -0,0 +1,103 @@
+// Optional TOML configuration file.
+//
+// Looked up at `--config <path>` or `mouse-automation.toml` in the current
+// directory. Command line flags add to (never loosen) what is configured here.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use tracing::info;
+use crate::backend::BackendKind;
+use crate::bounds::OutOfBounds;
+use crate::clipboard_watch::ClipboardRule;
+use crate::dwell::DwellOptions;
+use crate::foreground::GuardConfig;
+use crate::keyboard::TypingMode;
+use crate::mqtt::MqttConfig;
+use crate::policy::Category;
+use crate::session::OnLock;
+use crate::webhook::WebhookConfig;
+
+pub const DEFAULT_CONFIG_PATH: &str = "mouse-automation.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Public key every script must be signed with
+    pub require_signature: Option<String>,
+    pub max_clicks_per_second: Option<u32>,
+    pub on_lock: Option<OnLock>,
+    pub out_of_bounds: Option<OutOfBounds>,
+    // Abort a step that takes this many times as long as it should, like --watchdog
+    pub watchdog: Option<f64>,
+    // Abort a run that takes longer than this, like --max-duration
+    pub max_duration_secs: Option<u64>,
+    // Append every injected event to this JSONL file
+    pub audit_log: Option<String>,
+    // Where failure screenshots are saved
+    pub failure_dir: Option<String>,
+    // Don't capture a screenshot when a step fails, like --no-failure-screenshots
+    pub no_failure_screenshots: bool,
+    pub policy: PolicyConfig,
+    pub guard: GuardConfig,
+    pub mqtt: MqttConfig,
+    pub webhook: WebhookConfig,
+    // Global hotkey -> script name, for the `hotkeys` launcher
+    pub hotkeys: BTreeMap<String, String>,
+    // Rules for the `clipboard` trigger
+    pub clipboard: Vec<ClipboardRule>,
+    // Named text templates for the `snippet` action
+    pub snippets: BTreeMap<String, String>,
+    // Pause between characters typed by snippets, unless the row sets interval_ms
+    pub snippet_interval_ms: Option<u64>,
+    pub typing: Option<TypingMode>,
+    // What delivers the events, like --backend
+    pub backend: Option<BackendKind>,
+    // How long moves take, in place of --move-duration
+    pub move_duration_ms: Option<u64>,
+    // Between the clicks of a double or triple click, like --click-interval
+    pub click_interval_ms: Option<u64>,
+    // Jitter, curved paths and varied click timing, like --humanize
+    pub humanize: bool,
+    // Hotkey that stops playback, like --abort-key
+    pub abort_key: Option<String>,
+    // Hotkey that pauses and resumes playback, like --pause-key
+    pub pause_key: Option<String>,
+    // Stop when the cursor is pushed into a screen corner, like --failsafe
+    pub failsafe: bool,
+    // Carry on past failed steps and list them at the end, like --keep-going
+    pub keep_going: bool,
+    // Countdown before the first action, like --start-delay
+    pub start_delay_secs: Option<u64>,
+    // Positions are logical pixels, like --logical-pixels
+    pub logical_pixels: bool,
+    // Put the cursor back where it was when the run ends, like --restore-cursor
+    pub restore_cursor: bool,
+    pub dwell: DwellOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    pub deny: Vec<Category>,
+}
+
+impl Config {
+    // Load the given file, or the default file if present
+    pub fn load(path: Option<&str>) -> Result<Config, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => DEFAULT_CONFIG_PATH,
+            None => return Ok(Config::default()),
+        };
+
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config '{}': {}", path, e))?;
+        let config = toml::from_str(&text)
+            .map_err(|e| format!("Invalid config '{}': {}", path, e))?;
+        info!("Loaded config: {}", path);
+        Ok(config)
+    }
+}
@@ -0,0 +1,118 @@
+// Local control endpoint so other processes can stop, pause or query a run.
+//
+// Unix platforms use a Unix domain socket; Windows listens on a loopback TCP
+// port instead. The protocol is one command per line (`stop`, `pause`,
+// `resume`, `status`) answered with one line.
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::thread;
+use tracing::info;
+use crate::controls::Controls;
+
+#[cfg(unix)]
+pub fn default_endpoint() -> String {
+    let dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| std::env::temp_dir().display().to_string());
+    format!("{}/mouse-automation.sock", dir.trim_end_matches('/'))
+}
+
+#[cfg(not(unix))]
+pub fn default_endpoint() -> String {
+    "127.0.0.1:47800".to_string()
+}
+
+// Listens on the endpoint until dropped
+pub struct ControlServer {
+    #[cfg(unix)]
+    path: String,
+}
+
+#[cfg(unix)]
+pub fn serve(endpoint: &str, controls: Arc<Controls>) -> Result<ControlServer, Box<dyn Error>> {
+    use std::os::unix::net::UnixListener;
+
+    // A socket file left behind by a crashed run would make bind fail
+    let _ = std::fs::remove_file(endpoint);
+    let listener = UnixListener::bind(endpoint)
+        .map_err(|e| format!("Failed to open control socket '{}': {}", endpoint, e))?;
+    info!("Control socket listening at {}", endpoint);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Ok(reader) = stream.try_clone() {
+                handle(reader, stream, &controls);
+            }
+        }
+    });
+
+    Ok(ControlServer { path: endpoint.to_string() })
+}
+
+#[cfg(not(unix))]
+pub fn serve(endpoint: &str, controls: Arc<Controls>) -> Result<ControlServer, Box<dyn Error>> {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind(endpoint)
+        .map_err(|e| format!("Failed to open control endpoint '{}': {}", endpoint, e))?;
+    info!("Control endpoint listening at {}", endpoint);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Ok(reader) = stream.try_clone() {
+                handle(reader, stream, &controls);
+            }
+        }
+    });
+
+    Ok(ControlServer {})
+}
+
+#[cfg(unix)]
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Answer each command on a connection
+fn handle(reader: impl Read, mut writer: impl Write, controls: &Controls) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { return };
+        let reply = match line.trim() {
+            "stop" => {
+                controls.request_abort();
+                "ok stopping".to_string()
+            },
+            "pause" => {
+                controls.set_paused(true);
+                "ok paused".to_string()
+            },
+            "resume" => {
+                controls.request_resume();
+                "ok resumed".to_string()
+            },
+            "status" => controls.status(),
+            "" => continue,
+            other => format!("error unknown command: {}", other),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+// Send one command to a running instance and return its reply
+pub fn send(endpoint: &str, command: &str) -> Result<String, Box<dyn Error>> {
+    #[cfg(unix)]
+    let stream = std::os::unix::net::UnixStream::connect(endpoint);
+    #[cfg(not(unix))]
+    let stream = std::net::TcpStream::connect(endpoint);
+
+    let mut stream = stream.map_err(|e| format!("No running instance at '{}': {}", endpoint, e))?;
+    writeln!(stream, "{}", command)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim().to_string())
+}
@@ -0,0 +1,163 @@
+// Controller side of agent fleets, the `controller` subcommand.
+//
+// Sends the same command to every listed agent in parallel and prints one
+// line per agent. See agent.rs for the protocol.
+
+use std::error::Error;
+use std::fs;
+use std::io::BufReader;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use ed25519_dalek::{Signer, SigningKey};
+use tracing::info;
+use crate::agent::{self, AgentReply, AgentRequest, Challenge, ChallengeResponse, CHALLENGE_CONTEXT, DEFAULT_AGENT_PORT};
+use crate::{signing, MouseAction};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// An authenticated connection to one agent
+pub struct AgentConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl AgentConnection {
+    pub fn connect(agent: &str, signing_key: &SigningKey) -> Result<AgentConnection, Box<dyn Error>> {
+        let address = with_default_port(agent);
+        let socket_address = address.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format!("could not resolve {}", address))?;
+        let writer = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)?;
+        let mut connection = AgentConnection { reader: BufReader::new(writer.try_clone()?), writer };
+
+        let challenge: Challenge = agent::read_message(&mut connection.reader)?.ok_or("agent closed the connection")?;
+        let challenge = hex::decode(&challenge.challenge)?;
+        let signature = signing_key.sign(&[CHALLENGE_CONTEXT, &challenge].concat());
+        agent::write_message(&mut connection.writer, &ChallengeResponse { signature: hex::encode(signature.to_bytes()) })?;
+        connection.reply()?;
+        Ok(connection)
+    }
+
+    // Send one request and wait for its reply
+    pub fn request(&mut self, request: &AgentRequest) -> Result<String, Box<dyn Error>> {
+        agent::write_message(&mut self.writer, request)?;
+        self.reply()
+    }
+
+    fn reply(&mut self) -> Result<String, Box<dyn Error>> {
+        let reply: AgentReply = agent::read_message(&mut self.reader)?.ok_or("agent closed the connection")?;
+        if reply.ok {
+            Ok(reply.message)
+        } else {
+            Err(reply.message.into())
+        }
+    }
+}
+
+// Plays a script on an agent one action at a time, for `run --target tcp://host:port`
+pub struct RemoteTarget {
+    address: String,
+    connection: AgentConnection,
+}
+
+impl RemoteTarget {
+    pub fn connect(target: &str, key_path: Option<&str>) -> Result<RemoteTarget, Box<dyn Error>> {
+        let address = target.strip_prefix("tcp://")
+            .ok_or_else(|| format!("Unsupported target '{}', expected tcp://host:port", target))?;
+        let key_path = key_path.ok_or("--target requires --key <secret.key>")?;
+        let connection = AgentConnection::connect(address, &signing::load_signing_key(key_path)?)
+            .map_err(|e| format!("Failed to connect to agent {}: {}", address, e))?;
+        info!("Playing back on agent {}", address);
+        Ok(RemoteTarget { address: address.to_string(), connection })
+    }
+
+    // Send one row and wait until the agent has executed it
+    pub fn step(&mut self, step: usize, record: &MouseAction) -> Result<(), Box<dyn Error>> {
+        let request = AgentRequest {
+            command: "step".to_string(),
+            step: Some(step),
+            action: Some(record.clone()),
+            ..AgentRequest::default()
+        };
+        self.connection.request(&request)
+            .map(|_| ())
+            .map_err(|e| format!("{}: {}", self.address, e).into())
+    }
+}
+
+pub fn run(command: &str, script: Option<&str>, key_path: &str, agents: &[String]) -> Result<(), Box<dyn Error>> {
+    if agents.is_empty() {
+        return Err("No agents given; use --agents host[:port],... or --agents-file".into());
+    }
+    let request = build_request(command, script)?;
+    let signing_key = Arc::new(signing::load_signing_key(key_path)?);
+
+    let handles: Vec<_> = agents.iter().map(|agent| {
+        let (agent, request, signing_key) = (agent.clone(), request.clone(), Arc::clone(&signing_key));
+        // Box<dyn Error> isn't Send, so errors cross the thread boundary as text
+        thread::spawn(move || send(&agent, &signing_key, &request).map_err(|e| e.to_string()))
+    }).collect();
+
+    let mut failures = 0;
+    for (agent, handle) in agents.iter().zip(handles) {
+        match handle.join() {
+            Ok(Ok(message)) => println!("{}: {}", agent, message),
+            Ok(Err(error)) => {
+                failures += 1;
+                println!("{}: error: {}", agent, error);
+            },
+            Err(_) => {
+                failures += 1;
+                println!("{}: error: controller thread panicked", agent);
+            },
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("{} of {} agent(s) failed", failures, agents.len()).into());
+    }
+    Ok(())
+}
+
+fn send(agent: &str, signing_key: &SigningKey, request: &AgentRequest) -> Result<String, Box<dyn Error>> {
+    AgentConnection::connect(agent, signing_key)?.request(request)
+}
+
+// Agents from a file, one host[:port] per line; blank lines and # comments are skipped
+pub fn read_agents_file(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read agents file '{}': {}", path, e))?;
+    Ok(text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+fn build_request(command: &str, script: Option<&str>) -> Result<AgentRequest, Box<dyn Error>> {
+    let request = match command {
+        "push" => {
+            let path = script.ok_or("push needs a script file")?;
+            let name = Path::new(path).file_name().ok_or("push needs a script file")?.to_string_lossy().into_owned();
+            let content = fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+            AgentRequest { command: command.to_string(), script: Some(name), content: Some(content), ..AgentRequest::default() }
+        },
+        "run" => {
+            let name = script.ok_or("run needs a script name")?;
+            AgentRequest { command: command.to_string(), script: Some(name.to_string()), ..AgentRequest::default() }
+        },
+        "status" | "pause" | "resume" | "stop" => AgentRequest { command: command.to_string(), ..AgentRequest::default() },
+        other => return Err(format!("Unknown controller command '{}'", other).into()),
+    };
+    Ok(request)
+}
+
+fn with_default_port(agent: &str) -> String {
+    if agent.contains(':') {
+        agent.to_string()
+    } else {
+        format!("{}:{}", agent, DEFAULT_AGENT_PORT)
+    }
+}
@@ -0,0 +1,447 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use rdev::{listen, EventType, Key};
+use tracing::{info, warn};
+use crate::events::{Event, EventBus};
+use crate::hotkey::{Hotkey, Tracker};
+use crate::lint;
+
+pub const DEFAULT_ABORT_KEY: &str = "esc";
+pub const DEFAULT_PAUSE_KEY: &str = "f8";
+
+// Range of --speed and the speed commands; a row's delay_ms of u64::MAX still fits in a Duration scaled by the maximum
+pub const MIN_DELAY_SCALE: f64 = 0.01;
+pub const MAX_DELAY_SCALE: f64 = 100.0;
+
+// Pixels the cursor may drift from where we left it before we treat it as user input
+const USER_INPUT_TOLERANCE: i32 = 2;
+
+// Shared state between the playback loop and the threads that steer it
+#[derive(Default)]
+pub struct Controls {
+    resume_requested: AtomicBool,
+    abort_requested: AtomicBool,
+    paused: AtomicBool,
+    finished: AtomicBool,
+    step: AtomicUsize,
+    total_steps: AtomicUsize,
+    // Milliseconds since EPOCH when the current step started, or last resumed
+    // from a pause it didn't ask for, and how long it should take, for the watchdog
+    last_progress_ms: AtomicU64,
+    step_budget_ms: AtomicU64,
+    // Why the watchdog gave up on the current step, until the run loop fails it
+    step_cancelled: AtomicBool,
+    cancel_reason: Mutex<Option<String>>,
+    // Multiplier applied to delays, stored as f64 bits (0 means unset, i.e. 1.0)
+    delay_scale: AtomicU64,
+    // Sleeps return at once and only add to the nanoseconds slept, for tests
+    virtual_time: AtomicBool,
+    slept_ns: AtomicU64,
+    // Virtual time at which the run is aborted (0 means never)
+    abort_at_ns: AtomicU64,
+    events: Option<Arc<EventBus>>,
+}
+
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+fn now_ms() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+impl Controls {
+    pub fn new() -> Arc<Controls> {
+        Arc::new(Controls::default())
+    }
+
+    // Controls that broadcast progress to the given bus
+    pub fn with_events(events: Arc<EventBus>) -> Arc<Controls> {
+        Arc::new(Controls { events: Some(events), ..Controls::default() })
+    }
+
+    pub fn publish(&self, event: Event) {
+        if let Some(events) = &self.events {
+            events.publish(&event);
+        }
+    }
+
+    pub fn delay_scale(&self) -> f64 {
+        match self.delay_scale.load(Ordering::SeqCst) {
+            0 => 1.0,
+            bits => f64::from_bits(bits),
+        }
+    }
+
+    // 0.5 halves every delay (double speed), 2.0 doubles them. Kept within
+    // MIN_DELAY_SCALE and MAX_DELAY_SCALE, so scaled durations can't overflow
+    pub fn set_delay_scale(&self, factor: f64) {
+        let factor = if factor.is_nan() { 1.0 } else { factor.clamp(MIN_DELAY_SCALE, MAX_DELAY_SCALE) };
+        self.delay_scale.store(factor.to_bits(), Ordering::SeqCst);
+        self.publish(Event::Speed { factor });
+    }
+
+    // Abort the run between actions when Ctrl+C is pressed
+    pub fn handle_ctrl_c(self: &Arc<Self>) -> Result<(), ctrlc::Error> {
+        let controls = Arc::clone(self);
+        ctrlc::set_handler(move || {
+            // A second Ctrl+C exits without waiting for cleanup
+            if controls.abort_requested() {
+                std::process::exit(130);
+            }
+            info!("Ctrl+C received, stopping after the current action...");
+            controls.request_abort();
+        })
+    }
+
+    // Start listening for the resume hotkey on a background thread
+    pub fn listen_for_resume(self: &Arc<Self>, resume_key: Key) {
+        let listener = Arc::clone(self);
+
+        thread::spawn(move || {
+            let result = listen(move |event| {
+                if event.event_type == EventType::KeyPress(resume_key) {
+                    listener.resume_requested.store(true, Ordering::SeqCst);
+                }
+            });
+            if let Err(error) = result {
+                warn!("hotkey listener failed: {:?}", error);
+            }
+        });
+    }
+
+    // Stop or pause playback on hotkeys, from a background thread
+    pub fn listen_for_hotkeys(self: &Arc<Self>, abort: Option<(Hotkey, String)>, pause: Option<(Hotkey, String)>) {
+        if abort.is_none() && pause.is_none() {
+            return;
+        }
+        let listener = Arc::clone(self);
+
+        thread::spawn(move || {
+            let mut tracker = Tracker::default();
+            let result = listen(move |event| {
+                let Some(pressed) = tracker.pressed(&event.event_type) else { return };
+                if let Some((_, name)) = abort.as_ref().filter(|(hotkey, _)| *hotkey == pressed) {
+                    if !listener.abort_requested() {
+                        info!("{} pressed, stopping playback...", name);
+                        listener.request_abort();
+                    }
+                } else if let Some((_, name)) = pause.as_ref().filter(|(hotkey, _)| *hotkey == pressed) {
+                    let paused = !listener.is_paused();
+                    listener.set_paused(paused);
+                    if paused {
+                        info!("{} pressed, pausing after the current action; press it again to resume", name);
+                    }
+                }
+            });
+            if let Err(error) = result {
+                warn!("playback hotkey listener failed: {:?}", error);
+            }
+        });
+    }
+
+    // Returns true once per hotkey press
+    pub fn take_resume(&self) -> bool {
+        self.resume_requested.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn request_abort(&self) {
+        self.abort_requested.store(true, Ordering::SeqCst);
+    }
+
+    // Whether to stop what is being played: the run was aborted, or the
+    // watchdog gave up on the current step
+    pub fn abort_requested(&self) -> bool {
+        self.run_aborted() || self.step_cancelled.load(Ordering::SeqCst)
+    }
+
+    // Whether the whole run is to stop, rather than just the current step
+    pub fn run_aborted(&self) -> bool {
+        self.abort_requested.load(Ordering::SeqCst)
+    }
+
+    // Fail the current step; waits and sleeps inside it return as they do on an abort
+    pub fn cancel_step(&self, reason: String) {
+        *self.cancel_reason.lock().unwrap_or_else(PoisonError::into_inner) = Some(reason);
+        self.step_cancelled.store(true, Ordering::SeqCst);
+    }
+
+    // Why the current step was cancelled, if it was, letting the next one play
+    pub fn take_cancelled_step(&self) -> Option<String> {
+        if !self.step_cancelled.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+        self.cancel_reason.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+
+    // Resume from a control pause or a user-input pause alike
+    pub fn request_resume(&self) {
+        self.set_paused(false);
+        self.resume_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn mark_finished(&self) {
+        self.finished.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    // Block between actions while paused from outside
+    pub fn wait_while_paused(&self, step: usize) {
+        if !self.is_paused() {
+            return;
+        }
+        info!("Paused before step {} (row {})", step, lint::row_number(step.saturating_sub(1)));
+        self.publish(Event::Paused { step });
+        while self.is_paused() && !self.abort_requested() {
+            self.heartbeat();
+            thread::sleep(Duration::from_millis(50));
+        }
+        info!("Resuming playback at step {}", step);
+        self.publish(Event::Resumed { step });
+    }
+
+    pub fn set_progress(&self, step: usize, total_steps: usize) {
+        self.step.store(step, Ordering::SeqCst);
+        self.total_steps.store(total_steps, Ordering::SeqCst);
+        self.heartbeat();
+    }
+
+    pub fn step(&self) -> usize {
+        self.step.load(Ordering::SeqCst)
+    }
+
+    pub fn total_steps(&self) -> usize {
+        self.total_steps.load(Ordering::SeqCst)
+    }
+
+    // Start the clock on a step (or another attempt at it) that should take at most `budget`
+    pub fn start_step(&self, budget: Duration) {
+        self.step_budget_ms.store(u64::try_from(budget.as_millis()).unwrap_or(u64::MAX), Ordering::SeqCst);
+        // A late cancellation of the step before is no reason to fail this one
+        self.step_cancelled.store(false, Ordering::SeqCst);
+        self.heartbeat();
+    }
+
+    pub fn step_budget(&self) -> Duration {
+        Duration::from_millis(self.step_budget_ms.load(Ordering::SeqCst))
+    }
+
+    // Restart the step's clock while held up by a pause the step didn't ask for,
+    // such as a lock screen, so the watchdog doesn't count it
+    pub fn heartbeat(&self) {
+        self.last_progress_ms.store(now_ms(), Ordering::SeqCst);
+    }
+
+    // Time the current step has been running since it started or was last held up
+    pub fn since_progress(&self) -> Duration {
+        Duration::from_millis(now_ms().saturating_sub(self.last_progress_ms.load(Ordering::SeqCst)))
+    }
+
+    // One-line description of the run for status queries
+    pub fn status(&self) -> String {
+        format!("{} step {}/{}", self.state_name(), self.step(), self.total_steps())
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        if self.is_finished() {
+            "finished"
+        } else if self.run_aborted() {
+            "stopping"
+        } else if self.is_paused() {
+            "paused"
+        } else {
+            "running"
+        }
+    }
+
+    // Count sleeps instead of sleeping, so a script plays without waiting for its delays
+    pub fn use_virtual_time(&self) {
+        self.virtual_time.store(true, Ordering::SeqCst);
+    }
+
+    // Under virtual time, abort once the delays slept reach `at`, as if the
+    // run were stopped from another thread partway through that delay
+    pub fn abort_after(&self, at: Duration) {
+        let nanos = u64::try_from(at.as_nanos()).unwrap_or(u64::MAX).max(1);
+        self.abort_at_ns.store(nanos, Ordering::SeqCst);
+    }
+
+    // Time the run would have spent sleeping so far under virtual time
+    pub fn virtual_elapsed(&self) -> Duration {
+        Duration::from_nanos(self.slept_ns.load(Ordering::SeqCst))
+    }
+
+    // Sleep in short slices so an abort request is noticed promptly
+    pub fn sleep(&self, duration: Duration) {
+        if self.virtual_time.load(Ordering::SeqCst) {
+            let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+            let abort_at = match self.abort_at_ns.load(Ordering::SeqCst) {
+                0 => u64::MAX,
+                at => at,
+            };
+            // A sleep that reaches the abort ends there
+            let slept = self.slept_ns.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |slept| Some(slept.saturating_add(nanos).min(abort_at.max(slept))))
+                .unwrap_or_default();
+            if slept.saturating_add(nanos) >= abort_at {
+                self.request_abort();
+            }
+            return;
+        }
+        let deadline = Instant::now() + duration;
+        while !self.abort_requested() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            thread::sleep(remaining.min(Duration::from_millis(50)));
+        }
+    }
+}
+
+// Detects cursor movement that didn't come from us and pauses until the resume hotkey
+pub struct UserInputWatch {
+    controls: Arc<Controls>,
+    resume_key: Key,
+    expected: Option<(i32, i32)>,
+}
+
+impl UserInputWatch {
+    pub fn new(controls: Arc<Controls>, resume_key: Key) -> Self {
+        UserInputWatch { controls, resume_key, expected: None }
+    }
+
+    // Call right before injecting an action with the current cursor location
+    pub fn check(&mut self, location: (i32, i32), step: usize) {
+        let Some((expected_x, expected_y)) = self.expected else {
+            return;
+        };
+
+        let (x, y) = location;
+        if (x - expected_x).abs() <= USER_INPUT_TOLERANCE && (y - expected_y).abs() <= USER_INPUT_TOLERANCE {
+            return;
+        }
+
+        info!("\x07Paused: the mouse was moved by someone else before step {}.", step);
+        info!("Press {:?} to resume playback.", self.resume_key);
+
+        // Ignore presses that happened before we paused
+        self.controls.take_resume();
+        while !self.controls.take_resume() {
+            if self.controls.abort_requested() {
+                return;
+            }
+            self.controls.heartbeat();
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        info!("Resuming playback at step {}", step);
+    }
+
+    // Call right after injecting an action to remember where we left the cursor
+    pub fn record(&mut self, location: (i32, i32)) {
+        self.expected = Some(location);
+    }
+}
+
+// Parse a hotkey name such as "F9", "Pause", "F13" or "k"
+pub fn parse_key(name: &str) -> Option<Key> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        "esc" | "escape" => Key::Escape,
+        "pause" => Key::Pause,
+        "scrolllock" | "scroll_lock" => Key::ScrollLock,
+        "insert" => Key::Insert,
+        "home" => Key::Home,
+        "end" => Key::End,
+        name => return extended_function_key(name).or_else(|| character_key(name)),
+    };
+    Some(key)
+}
+
+// F13-F24 have no rdev variant and arrive as platform key codes
+fn extended_function_key(name: &str) -> Option<Key> {
+    let number: u32 = name.strip_prefix('f')?.parse().ok()?;
+    if !(13..=24).contains(&number) {
+        return None;
+    }
+    // VK_F13..VK_F24
+    #[cfg(target_os = "windows")]
+    let code = 0x7C + (number - 13);
+    // X11 keycodes
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let code = 191 + (number - 13);
+    // kVK_F13..kVK_F20; macOS has no F21-F24
+    #[cfg(target_os = "macos")]
+    let code = *[105, 107, 113, 106, 64, 79, 80, 90].get((number - 13) as usize)?;
+    Some(Key::Unknown(code))
+}
+
+// Single letters and digits, e.g. the "k" in a "ctrl+alt+k" hotkey
+fn character_key(name: &str) -> Option<Key> {
+    let mut chars = name.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let key = match c {
+        'a' => Key::KeyA,
+        'b' => Key::KeyB,
+        'c' => Key::KeyC,
+        'd' => Key::KeyD,
+        'e' => Key::KeyE,
+        'f' => Key::KeyF,
+        'g' => Key::KeyG,
+        'h' => Key::KeyH,
+        'i' => Key::KeyI,
+        'j' => Key::KeyJ,
+        'k' => Key::KeyK,
+        'l' => Key::KeyL,
+        'm' => Key::KeyM,
+        'n' => Key::KeyN,
+        'o' => Key::KeyO,
+        'p' => Key::KeyP,
+        'q' => Key::KeyQ,
+        'r' => Key::KeyR,
+        's' => Key::KeyS,
+        't' => Key::KeyT,
+        'u' => Key::KeyU,
+        'v' => Key::KeyV,
+        'w' => Key::KeyW,
+        'x' => Key::KeyX,
+        'y' => Key::KeyY,
+        'z' => Key::KeyZ,
+        '0' => Key::Num0,
+        '1' => Key::Num1,
+        '2' => Key::Num2,
+        '3' => Key::Num3,
+        '4' => Key::Num4,
+        '5' => Key::Num5,
+        '6' => Key::Num6,
+        '7' => Key::Num7,
+        '8' => Key::Num8,
+        '9' => Key::Num9,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,38 @@
+// Script conversion, the `convert` subcommand.
+//
+// Reads a script the way playback does, with templates rendered and `${name}`
+// placeholders filled in, and writes it back out in the format the output's
+// extension names: plain CSV with every column, a flat YAML list of steps
+// (`.yaml`, `.yml`) or a compact binary script (`.mab`). Useful for checking
+// what a template produces, for flattening YAML blocks into rows, or for
+// shrinking a long recording.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use csv::Writer;
+use tracing::info;
+use crate::vars::Vars;
+use crate::{binary, load_script, yaml, MouseAction};
+
+pub fn run(input: &str, output: &str, vars: &Vars) -> Result<(), Box<dyn Error>> {
+    let actions = load_script(input, vars)?;
+    if binary::is_binary(Path::new(output)) {
+        binary::write(Path::new(output), &actions)?;
+    } else if yaml::is_yaml(Path::new(output)) {
+        fs::write(output, yaml::to_string(&actions)?).map_err(|e| format!("Failed to write '{}': {}", output, e))?;
+    } else {
+        write_csv(output, &actions)?;
+    }
+    info!("Wrote {} row(s) to {}", actions.len(), output);
+    Ok(())
+}
+
+fn write_csv(path: &str, actions: &[MouseAction]) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_path(path).map_err(|e| format!("Failed to create '{}': {}", path, e))?;
+    for record in actions {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
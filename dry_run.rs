@@ -0,0 +1,121 @@
+// Dry runs (`--dry-run`): what a script would do, without injecting anything.
+//
+// The script is checked the way `validate` checks it, then every row (or those
+// between `--from-row` and `--to-row`) is printed in file order with the position it would act on. Relative moves and
+// clicks without coordinates are worked out from where the cursor would be,
+// and `--out-of-bounds` is applied as playback would apply it, so a row that
+// would fail the run is reported. Nothing goes through the input system, so a
+// long script can be checked on the machine you are working on. Loops and
+// jumps are listed rather than followed, since conditions like `if_pixel`
+// depend on the screen at the time. With `--overlay` the rows are also drawn
+// as numbered markers and paths over a capture of the live screen, to review
+// against what is on it; rows positioned in a window are left out, as the
+// window may be elsewhere by the time they play.
+
+use std::error::Error;
+use std::ops::Range;
+use crate::bounds::{Bounds, OutOfBounds};
+use crate::monitors::Layout;
+use crate::stroke;
+use crate::{lint, report, screen, visualize, MouseAction};
+
+pub fn run(actions: &[MouseAction], rows: Range<usize>, out_of_bounds: OutOfBounds, overlay: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let size = screen::size();
+    let findings = lint::lint(actions, size);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    let errors = findings.iter().filter(|finding| finding.severity == lint::Severity::Error).count();
+    if errors > 0 {
+        return Err(format!("Dry run: the script has {} error(s)", errors).into());
+    }
+
+    let bounds = Bounds::new(out_of_bounds, size.unwrap_or((0, 0)));
+    // Unknown until the first absolute position
+    let mut cursor: Option<(i32, i32)> = None;
+    let mut monitors = Layout::default();
+    let mut failures = 0;
+    for (index, record) in actions.iter().enumerate().take(rows.end).skip(rows.start) {
+        let step = format!("Step {} (row {})", index + 1, lint::row_number(index));
+        match resolve(record, &bounds, &mut monitors, &mut cursor) {
+            Ok(target) => println!("{}: {} {}{}", step, record.action, report::details(record), target),
+            Err(error) => {
+                failures += 1;
+                println!("{}: {} {} would fail: {}", step, record.action, report::details(record), error);
+            },
+        }
+    }
+
+    // Drawn even if a step would fail, to see where it would have gone
+    if let Some(output) = overlay {
+        visualize::overlay(actions, rows.clone(), output, |record, x, y| match (record.window.as_deref(), record.monitor) {
+            (Some(title), _) => Err(format!("positioned in window '{}'", title)),
+            (None, Some(monitor)) => monitors.resolve(monitor, x, y, bounds.policy()),
+            (None, None) => bounds.resolve(x, y),
+        })?;
+    }
+
+    if failures > 0 {
+        return Err(format!("Dry run: {} step(s) would fail", failures).into());
+    }
+    println!("Dry run: {} step(s), nothing was injected", rows.len());
+    Ok(())
+}
+
+// Where the row would act, as text to add to its line, moving the cursor along
+fn resolve(record: &MouseAction, bounds: &Bounds, monitors: &mut Layout, cursor: &mut Option<(i32, i32)>) -> Result<String, String> {
+    let coordinates = record.x_position.zip(record.y_position);
+    match record.action.as_str() {
+        "move" | "click" | "double_click" | "triple_click" | "click_and_hold" | "right_click" | "drag" | "release" => {
+            if let Some((x, y)) = coordinates {
+                // Windows move, so where they'd be during the run isn't known yet
+                if record.window.is_some() {
+                    *cursor = None;
+                    return Ok(String::new());
+                }
+                let target = match record.monitor {
+                    Some(monitor) => monitors.resolve(monitor, x, y, bounds.policy())?,
+                    None => bounds.resolve(x, y)?,
+                };
+                *cursor = Some(target);
+                if target != (x, y) {
+                    return Ok(format!(" -> at ({}, {})", target.0, target.1));
+                }
+                return Ok(String::new());
+            }
+            Ok(match cursor {
+                Some((x, y)) => format!(" -> at ({}, {})", x, y),
+                None => " -> wherever the cursor is".to_string(),
+            })
+        },
+        "drag_to" | "draw_circle" | "draw_rectangle" | "draw_polyline" => {
+            if record.window.is_some() {
+                *cursor = None;
+                return Ok(String::new());
+            }
+            let points = stroke::outline(record, |x, y| match record.monitor {
+                Some(monitor) => monitors.resolve(monitor, x, y, bounds.policy()),
+                None => bounds.resolve(x, y),
+            })?;
+            let (from, to) = (points[0], points[points.len() - 1]);
+            *cursor = Some(to);
+            Ok(format!(" -> from ({}, {}) to ({}, {})", from.0, from.1, to.0, to.1))
+        },
+        // Saved from wherever the real cursor is during the run
+        "restore_position" => {
+            *cursor = None;
+            Ok(String::new())
+        },
+        "move_relative" => {
+            let (Some((dx, dy)), Some(mut position)) = (coordinates, *cursor) else {
+                return Ok(" -> from wherever the cursor is".to_string());
+            };
+            for _ in 0..record.repeat_count.unwrap_or(1) {
+                position = bounds.resolve(position.0 + dx, position.1 + dy)?;
+            }
+            *cursor = Some(position);
+            Ok(format!(" -> to ({}, {})", position.0, position.1))
+        },
+        _ => Ok(String::new()),
+    }
+}
@@ -0,0 +1,121 @@
+// Dwell clicking, the `dwell` subcommand.
+//
+// An accessibility aid for people who can point but not click: watches the
+// physical cursor and clicks wherever it comes to rest for the dwell time.
+// The cursor has to move away before the next click, so resting on a button
+// clicks it once. Resting in the top-right corner of the screen turns dwell
+// clicking off and on again, as does the toggle hotkey. Nothing is clicked
+// while the session is locked.
+//
+//   [dwell]
+//   dwell_ms = 1000
+//   radius = 6
+//   button = "left"
+//   toggle_key = "F9"
+
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use enigo::MouseButton;
+use rdev::{listen, EventType};
+use serde::Deserialize;
+use tracing::{info, warn};
+use crate::controls::{self, Controls};
+use crate::injector::Injector;
+use crate::{parse_button, session};
+
+const DEFAULT_DWELL_MS: u64 = 800;
+const DEFAULT_RADIUS: i32 = 5;
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+// Side of the square in the top-right corner that toggles dwell clicking
+const TOGGLE_CORNER: i32 = 24;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DwellOptions {
+    // How long the cursor has to rest before it clicks
+    pub dwell_ms: Option<u64>,
+    // Pixels the cursor may wander while resting, for unsteady hands
+    pub radius: Option<i32>,
+    pub button: Option<String>,
+    pub toggle_key: Option<String>,
+}
+
+impl DwellOptions {
+    // Flags given on the command line win over the [dwell] config table
+    pub fn or(&self, config: &DwellOptions) -> DwellOptions {
+        DwellOptions {
+            dwell_ms: self.dwell_ms.or(config.dwell_ms),
+            radius: self.radius.or(config.radius),
+            button: self.button.clone().or_else(|| config.button.clone()),
+            toggle_key: self.toggle_key.clone().or_else(|| config.toggle_key.clone()),
+        }
+    }
+}
+
+pub fn run(mut injector: Injector, options: DwellOptions) -> Result<(), Box<dyn Error>> {
+    let dwell = Duration::from_millis(options.dwell_ms.unwrap_or(DEFAULT_DWELL_MS));
+    let radius = options.radius.unwrap_or(DEFAULT_RADIUS);
+    let button = match options.button.as_deref() {
+        Some(name) => parse_button(name).ok_or_else(|| format!("Unknown button '{}'; use left, right, middle, back, forward or 1-9", name))?,
+        None => MouseButton::Left,
+    };
+    let key_name = options.toggle_key.as_deref().unwrap_or("F9");
+    let toggle_key = controls::parse_key(key_name).ok_or_else(|| format!("Unknown toggle key: {}", key_name))?;
+
+    let enabled = Arc::new(AtomicBool::new(true));
+    let listener = Arc::clone(&enabled);
+    thread::spawn(move || {
+        let result = listen(move |event| {
+            if event.event_type == EventType::KeyPress(toggle_key) {
+                toggle(&listener);
+            }
+        });
+        if let Err(error) = result {
+            warn!("toggle hotkey listener failed: {:?}", error);
+        }
+    });
+    let controls = Controls::new();
+    controls.handle_ctrl_c()?;
+
+    info!("Dwell clicking: {:?} click when the cursor rests for {} ms", button, dwell.as_millis());
+    info!("Rest in the top-right corner or press {} to turn it off and on; press Ctrl+C to quit", key_name);
+
+    let mut anchor = injector.location();
+    let mut resting_since = Instant::now();
+    // Set once the cursor has moved since the last click, so a resting cursor clicks once
+    let mut armed = false;
+    let mut clicks = 0;
+    while !controls.abort_requested() {
+        thread::sleep(POLL_INTERVAL);
+        let (x, y) = injector.location();
+        if (x - anchor.0).abs() > radius || (y - anchor.1).abs() > radius {
+            anchor = (x, y);
+            resting_since = Instant::now();
+            armed = true;
+            continue;
+        }
+        if !armed || resting_since.elapsed() < dwell {
+            continue;
+        }
+        armed = false;
+
+        let (width, _) = injector.display_size();
+        if anchor.0 >= width - TOGGLE_CORNER && anchor.1 < TOGGLE_CORNER {
+            toggle(&enabled);
+        } else if enabled.load(Ordering::SeqCst) && !session::is_locked() {
+            injector.click(button);
+            clicks += 1;
+        }
+    }
+    info!("Stopped dwell clicking after {} click(s)", clicks);
+    Ok(())
+}
+
+fn toggle(enabled: &AtomicBool) {
+    let now_enabled = !enabled.fetch_xor(true, Ordering::SeqCst);
+    // The bell tells users who can't see the terminal
+    info!("\x07Dwell clicking {}", if now_enabled { "on" } else { "off" });
+}
@@ -0,0 +1,48 @@
+// Progress events broadcast to live listeners such as WebSocket clients.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Started { script: String, total_steps: usize },
+    Step { step: usize, total_steps: usize, action: String },
+    StepFinished { step: usize, duration_ms: u64, ok: bool },
+    Retry { step: usize, attempt: u32, error: String },
+    Paused { step: usize },
+    Resumed { step: usize },
+    Speed { factor: f64 },
+    Finished { ok: bool, error: Option<String> },
+}
+
+type Observer = Box<dyn Fn(&Event) + Send>;
+
+// Fan-out of serialized events to every subscriber, and of the events themselves to observers
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<String>>>,
+    observers: Mutex<Vec<Observer>>,
+}
+
+impl EventBus {
+    pub fn observe(&self, observer: impl Fn(&Event) + Send + 'static) {
+        self.observers.lock().unwrap().push(Box::new(observer));
+    }
+
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, event: &Event) {
+        for observer in self.observers.lock().unwrap().iter() {
+            observer(event);
+        }
+        let Ok(line) = serde_json::to_string(event) else { return };
+        // Drop subscribers whose receiving end has gone away
+        self.subscribers.lock().unwrap().retain(|subscriber| subscriber.send(line.clone()).is_ok());
+    }
+}
@@ -0,0 +1,40 @@
+// The failsafe corner: slamming the mouse into a corner of the screen stops
+// playback, as in PyAutoGUI.
+//
+// The real cursor position is polled on a background thread, so this works
+// where no hotkey listener is available (e.g. without the accessibility or
+// input permission). The script moving the cursor into a corner itself trips
+// it too, so scripts that need the corners are run without `--failsafe`.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use enigo::{Enigo, MouseControllable};
+use tracing::{info, warn};
+use crate::controls::Controls;
+
+// How close to a corner counts, in pixels
+const MARGIN: i32 = 2;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn spawn(controls: Arc<Controls>) {
+    thread::spawn(move || {
+        let enigo = Enigo::new();
+        let (width, height) = enigo.main_display_size();
+        if width <= 0 || height <= 0 {
+            warn!("screen size unavailable, the failsafe corners are off");
+            return;
+        }
+        while !controls.is_finished() && !controls.run_aborted() {
+            let (x, y) = enigo.mouse_location();
+            let left_or_right = x < MARGIN || x >= width - MARGIN;
+            let top_or_bottom = y < MARGIN || y >= height - MARGIN;
+            if left_or_right && top_or_bottom {
+                info!("Failsafe: the cursor is in a corner at ({}, {}); stopping playback", x, y);
+                controls.request_abort();
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,258 @@
+// Control flow rows, which steer playback instead of injecting anything.
+//
+// A `loop_start` row plays the rows up to its matching `loop_end`
+// `repeat_count` times: once if unset, not at all if 0. Loops nest.
+//
+// A `goto` row jumps to the `label` row with the same name. With a
+// `repeat_count` it jumps at most that many times and then falls through to
+// the next row, which is how a retry loop gives up; without one it jumps every
+// time it is reached. Jumping out of a loop abandons it.
+//
+// A condition such as `if_pixel` that doesn't hold skips the row after it,
+// or jumps to the label in its `name` column if it has one.
+//
+// Loops and labels are matched before the run starts, so an unmatched loop
+// row or a jump to a missing label fails the script before anything is
+// injected.
+
+use std::collections::HashMap;
+use tracing::info;
+use crate::{lint, MouseAction};
+
+pub const ACTIONS: &[&str] = &["loop_start", "loop_end", "label", "goto"];
+
+pub fn is_flow(action: &str) -> bool {
+    ACTIONS.contains(&action)
+}
+
+pub struct Flow {
+    // The matching loop_end of each loop_start, and the other way round
+    ends: HashMap<usize, usize>,
+    starts: HashMap<usize, usize>,
+    // Loops being played, innermost last: the loop_start row and the passes left
+    active: Vec<(usize, u32)>,
+    labels: HashMap<String, usize>,
+    // How often each goto row has jumped
+    jumps: HashMap<usize, u32>,
+}
+
+impl Flow {
+    pub fn new(actions: &[MouseAction]) -> Result<Flow, String> {
+        let mut ends = HashMap::new();
+        let mut starts = HashMap::new();
+        let mut open = Vec::new();
+        let mut labels = HashMap::new();
+        for (index, record) in actions.iter().enumerate() {
+            let step = || format!("Step {} (row {})", index + 1, lint::row_number(index));
+            match record.action.as_str() {
+                "label" => {
+                    let name = record.name.as_deref().filter(|name| !name.is_empty())
+                        .ok_or_else(|| format!("{}: label needs a name", step()))?;
+                    if let Some(first) = labels.insert(name.to_string(), index) {
+                        return Err(format!("{}: label '{}' is already defined at step {}", step(), name, first + 1));
+                    }
+                },
+                "loop_start" => open.push(index),
+                "loop_end" => {
+                    let start = open.pop().ok_or_else(|| format!("{}: loop_end without a loop_start", step()))?;
+                    ends.insert(start, index);
+                    starts.insert(index, start);
+                },
+                _ => {},
+            }
+        }
+        if let Some(&start) = open.last() {
+            return Err(format!("Step {} (row {}): loop_start is never closed by a loop_end", start + 1, lint::row_number(start)));
+        }
+        for (index, record) in actions.iter().enumerate().filter(|(_, record)| jumps_to_label(record)) {
+            let name = record.name.as_deref().unwrap_or("");
+            if !labels.contains_key(name) {
+                return Err(format!("Step {} (row {}): {} to unknown label '{}'", index + 1, lint::row_number(index), record.action, name));
+            }
+        }
+        Ok(Flow { ends, starts, active: Vec::new(), labels, jumps: HashMap::new() })
+    }
+
+    // Index of the row to play after the flow row at `index`
+    pub fn next(&mut self, index: usize, record: &MouseAction) -> usize {
+        match record.action.as_str() {
+            "loop_start" => {
+                let passes = record.repeat_count.unwrap_or(1);
+                if passes == 0 {
+                    return self.ends[&index] + 1;
+                }
+                self.active.push((index, passes));
+                index + 1
+            },
+            "loop_end" => {
+                let start = self.starts[&index];
+                match self.active.last_mut() {
+                    Some((active, passes)) if *active == start && *passes > 1 => {
+                        *passes -= 1;
+                        info!("Repeating loop from step {} ({} pass(es) left)", start + 1, passes);
+                        start + 1
+                    },
+                    Some((active, _)) if *active == start => {
+                        self.active.pop();
+                        index + 1
+                    },
+                    // Reached by a goto into the loop rather than through its loop_start
+                    _ => index + 1,
+                }
+            },
+            "goto" => {
+                let name = record.name.as_deref().unwrap_or("");
+                let jumps = self.jumps.entry(index).or_insert(0);
+                if record.repeat_count.is_some_and(|limit| *jumps >= limit) {
+                    info!("goto '{}' has jumped {} time(s); continuing", name, jumps);
+                    return index + 1;
+                }
+                *jumps += 1;
+                self.jump_to(name)
+            },
+            _ => index + 1,
+        }
+    }
+
+    // Index of the row to play after the condition at `index` didn't hold
+    pub fn otherwise(&mut self, index: usize, record: &MouseAction) -> usize {
+        match record.name.as_deref() {
+            Some(name) if !name.is_empty() => self.jump_to(name),
+            _ => {
+                info!("Condition not met; skipping step {}", index + 2);
+                index + 2
+            },
+        }
+    }
+
+    // Index of the row after a label, leaving the loops the jump lands outside of
+    fn jump_to(&mut self, name: &str) -> usize {
+        let target = self.labels[name];
+        let ends = &self.ends;
+        self.active.retain(|(start, _)| *start < target && target < ends[start]);
+        info!("Jumping to label '{}' (step {})", name, target + 1);
+        target + 1
+    }
+}
+
+// A goto, or a condition with a label to jump to
+pub fn jumps_to_label(record: &MouseAction) -> bool {
+    record.action == "goto" || record.action == "if_pixel" && record.name.as_deref().is_some_and(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(action: &str, name: Option<&str>, repeat_count: Option<u32>) -> MouseAction {
+        MouseAction { action: action.to_string(), name: name.map(str::to_string), repeat_count, ..MouseAction::default() }
+    }
+
+    fn click() -> MouseAction {
+        row("click", None, None)
+    }
+
+    // The rows that would be played from `from` on, the way Playback::run steps through them
+    fn played(actions: &[MouseAction], from: usize) -> Vec<usize> {
+        let mut flow = Flow::new(actions).unwrap();
+        let mut rows = Vec::new();
+        let mut next = from;
+        while next < actions.len() {
+            assert!(rows.len() < 100, "playback didn't end: {:?}", rows);
+            let index = next;
+            if is_flow(&actions[index].action) {
+                next = flow.next(index, &actions[index]);
+            } else {
+                rows.push(index);
+                next += 1;
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn nested_loops_repeat_their_bodies() {
+        let actions = [
+            row("loop_start", None, Some(2)),
+            click(),
+            row("loop_start", None, Some(3)),
+            click(),
+            row("loop_end", None, None),
+            row("loop_end", None, None),
+            click(),
+        ];
+        assert_eq!(played(&actions, 0), [1, 3, 3, 3, 1, 3, 3, 3, 6]);
+    }
+
+    #[test]
+    fn zero_pass_loops_are_skipped_and_unset_ones_play_once() {
+        let actions = [
+            row("loop_start", None, Some(0)),
+            click(),
+            row("loop_end", None, None),
+            row("loop_start", None, None),
+            click(),
+            row("loop_end", None, None),
+        ];
+        assert_eq!(played(&actions, 0), [4]);
+    }
+
+    #[test]
+    fn goto_falls_through_once_its_jumps_run_out() {
+        let actions = [
+            click(),
+            row("label", Some("retry"), None),
+            click(),
+            row("goto", Some("retry"), Some(2)),
+            click(),
+        ];
+        assert_eq!(played(&actions, 0), [0, 2, 2, 2, 4]);
+    }
+
+    #[test]
+    fn jumping_out_of_a_loop_abandons_it() {
+        let actions = [
+            row("loop_start", None, Some(5)),
+            click(),
+            row("goto", Some("out"), None),
+            row("loop_end", None, None),
+            row("label", Some("out"), None),
+            click(),
+        ];
+        assert_eq!(played(&actions, 0), [1, 5]);
+    }
+
+    #[test]
+    fn unknown_labels_fail_before_playback() {
+        let error = Flow::new(&[row("goto", Some("nowhere"), None)]).err().unwrap();
+        assert!(error.contains("goto to unknown label 'nowhere'"), "{}", error);
+        let error = Flow::new(&[row("if_pixel", Some("missing"), None)]).err().unwrap();
+        assert!(error.contains("if_pixel to unknown label 'missing'"), "{}", error);
+    }
+
+    #[test]
+    fn unmatched_loop_rows_fail_before_playback() {
+        assert!(Flow::new(&[row("loop_start", None, Some(2)), click()]).is_err());
+        assert!(Flow::new(&[click(), row("loop_end", None, None)]).is_err());
+        assert!(Flow::new(&[row("label", Some("a"), None), row("label", Some("a"), None)]).is_err());
+    }
+
+    #[test]
+    fn starting_inside_a_loop_plays_the_rest_of_it_once() {
+        let actions = [
+            row("loop_start", None, Some(3)),
+            click(),
+            click(),
+            row("loop_end", None, None),
+            click(),
+        ];
+        assert_eq!(played(&actions, 2), [2, 4]);
+    }
+
+    #[test]
+    fn a_failed_condition_skips_a_row_or_jumps() {
+        let mut flow = Flow::new(&[row("label", Some("end"), None), row("if_pixel", Some("end"), None), click(), click()]).unwrap();
+        assert_eq!(flow.otherwise(1, &row("if_pixel", Some("end"), None)), 1);
+        assert_eq!(flow.otherwise(2, &row("if_pixel", None, None)), 4);
+    }
+}
@@ -0,0 +1,122 @@
+// Folder-watch trigger, started by the `watch` subcommand.
+//
+// Polls a directory and runs a script for every new file whose name matches
+// the pattern, once the file has stopped growing. The file is passed to the
+// script as variables:
+//   ${file}       full path
+//   ${file_name}  name with extension
+//   ${file_stem}  name without extension
+//
+// Files already present at startup are ignored. Files arriving during a run
+// are queued and handled one after another.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::runs::Runs;
+use crate::vars::Vars;
+use crate::Options;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn run(dir: &str, script: &str, pattern: Option<&str>, options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(dir);
+    let pattern = pattern.unwrap_or("*");
+    let runs = Runs::new(options, config)?;
+
+    let mut seen: HashSet<PathBuf> = list_files(dir)?.into_iter().collect();
+    // New files and their size at the last poll, until they stop growing
+    let mut growing: HashMap<PathBuf, u64> = HashMap::new();
+    let mut queue: VecDeque<PathBuf> = VecDeque::new();
+    info!("Watching {} for '{}' -> {} (scripts in {})", dir.display(), pattern, script, runs.scripts_dir().display());
+
+    loop {
+        for path in list_files(dir)? {
+            let matches = path.file_name().is_some_and(|name| glob_match(pattern, &name.to_string_lossy()));
+            if matches && seen.insert(path.clone()) {
+                growing.insert(path, u64::MAX);
+            }
+        }
+
+        growing.retain(|path, last_size| {
+            let Ok(size) = fs::metadata(path).map(|metadata| metadata.len()) else {
+                // Vanished before it finished arriving
+                return false;
+            };
+            if size == *last_size {
+                queue.push_back(path.clone());
+                return false;
+            }
+            *last_size = size;
+            true
+        });
+
+        if !runs.is_busy() {
+            if let Some(path) = queue.pop_front() {
+                info!("New file {}: starting {}", path.display(), script);
+                if let Err(error) = runs.start_with_vars(script, &file_vars(&path)) {
+                    warn!("Could not start {} for {}: {}", script, path.display(), error);
+                }
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn file_vars(path: &Path) -> Vars {
+    let mut vars = Vars::new();
+    let name = |part: Option<&std::ffi::OsStr>| part.map(|part| part.to_string_lossy().into_owned()).unwrap_or_default();
+    vars.insert("file".to_string(), path.to_string_lossy().into_owned());
+    vars.insert("file_name".to_string(), name(path.file_name()));
+    vars.insert("file_stem".to_string(), name(path.file_stem()));
+    vars
+}
+
+// Shell-style matching with `*` (any run of characters) and `?` (one character)
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            },
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,207 @@
+// Guard conditions on the foreground application.
+//
+// Keeps a script from clicking into the wrong window: injection can be
+// limited to some applications, refused for others, or refused while any
+// window is fullscreen (presentations, video calls, games). Applications are
+// matched case-insensitively against the process name (Windows), the window
+// class (X11) or the application name (macOS).
+
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use tracing::info;
+use crate::controls::Controls;
+
+// How often the foreground application is checked during playback
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+// What to do when a guard condition fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnGuard {
+    // Wait until the condition holds again, then continue
+    #[default]
+    Pause,
+    // Stop the run with an error
+    Abort,
+}
+
+impl OnGuard {
+    pub fn parse(name: &str) -> Option<OnGuard> {
+        match name {
+            "pause" => Some(OnGuard::Pause),
+            "abort" => Some(OnGuard::Abort),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct GuardConfig {
+    // Only inject while one of these is in the foreground
+    pub only_apps: Vec<String>,
+    // Never inject while one of these is in the foreground
+    pub never_apps: Vec<String>,
+    pub no_fullscreen: bool,
+    pub on_guard: Option<OnGuard>,
+}
+
+pub struct Foreground {
+    pub app: String,
+    pub fullscreen: bool,
+}
+
+pub struct ForegroundGuard {
+    only_apps: Vec<String>,
+    never_apps: Vec<String>,
+    no_fullscreen: bool,
+    on_guard: OnGuard,
+    last_check: Option<Instant>,
+}
+
+impl ForegroundGuard {
+    // None when no condition is configured
+    pub fn new(only_apps: Vec<String>, never_apps: Vec<String>, no_fullscreen: bool, on_guard: OnGuard) -> Option<Self> {
+        if only_apps.is_empty() && never_apps.is_empty() && !no_fullscreen {
+            return None;
+        }
+        let lowercase = |apps: Vec<String>| apps.into_iter().map(|app| app.to_lowercase()).collect();
+        Some(ForegroundGuard {
+            only_apps: lowercase(only_apps),
+            never_apps: lowercase(never_apps),
+            no_fullscreen,
+            on_guard,
+            last_check: None,
+        })
+    }
+
+    // Call before injecting an action; blocks or fails while a condition is violated
+    pub fn check(&mut self, step: usize, controls: &Controls) -> Result<(), Box<dyn Error>> {
+        if self.last_check.is_some_and(|at| at.elapsed() < CHECK_INTERVAL) {
+            return Ok(());
+        }
+        self.last_check = Some(Instant::now());
+
+        let Some(reason) = self.violation() else {
+            return Ok(());
+        };
+        if self.on_guard == OnGuard::Abort {
+            return Err(format!("Refusing to inject before step {}: {}", step, reason).into());
+        }
+
+        info!("Paused before step {}: {}.", step, reason);
+        while self.violation().is_some() {
+            if controls.abort_requested() {
+                return Ok(());
+            }
+            controls.heartbeat();
+            thread::sleep(Duration::from_secs(1));
+        }
+        info!("Foreground application allowed again. Resuming playback at step {}", step);
+        Ok(())
+    }
+
+    fn violation(&self) -> Option<String> {
+        let Some(foreground) = foreground() else {
+            // Without knowing what's in front, only an allow-list can be unsafe
+            return (!self.only_apps.is_empty()).then(|| "the foreground application can't be determined".to_string());
+        };
+
+        let app = foreground.app.to_lowercase();
+        if self.no_fullscreen && foreground.fullscreen {
+            return Some(format!("'{}' is fullscreen", foreground.app));
+        }
+        if self.never_apps.iter().any(|never| app.contains(never.as_str())) {
+            return Some(format!("'{}' is in the foreground", foreground.app));
+        }
+        if !self.only_apps.is_empty() && !self.only_apps.iter().any(|only| app.contains(only.as_str())) {
+            return Some(format!("'{}' is in the foreground instead of an allowed application", foreground.app));
+        }
+        None
+    }
+}
+
+// Ask the X server for the active window's class and state
+#[cfg(target_os = "linux")]
+pub fn foreground() -> Option<Foreground> {
+    use std::process::Command;
+
+    let root = Command::new("xprop").args(["-root", "_NET_ACTIVE_WINDOW"]).output().ok()?;
+    let root = String::from_utf8_lossy(&root.stdout);
+    let window = root.split_whitespace().last().filter(|id| id.starts_with("0x"))?.to_string();
+
+    let properties = Command::new("xprop").args(["-id", &window, "WM_CLASS", "_NET_WM_STATE"]).output().ok()?;
+    let properties = String::from_utf8_lossy(&properties.stdout);
+    // WM_CLASS(STRING) = "instance", "Class"
+    let app = properties.lines()
+        .find(|line| line.starts_with("WM_CLASS"))?
+        .split('"')
+        .nth(3)?
+        .to_string();
+    Some(Foreground { app, fullscreen: properties.contains("_NET_WM_STATE_FULLSCREEN") })
+}
+
+// System Events knows the frontmost process; fullscreen isn't exposed there
+#[cfg(target_os = "macos")]
+pub fn foreground() -> Option<Foreground> {
+    use std::process::Command;
+
+    let output = Command::new("osascript")
+        .args(["-e", "tell application \"System Events\" to get name of first application process whose frontmost is true"])
+        .output()
+        .ok()?;
+    let app = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!app.is_empty()).then_some(Foreground { app, fullscreen: false })
+}
+
+// Process image of the foreground window, and whether the window covers its monitor
+#[cfg(windows)]
+pub fn foreground() -> Option<Foreground> {
+    use windows_sys::Win32::Foundation::{CloseHandle, RECT};
+    use windows_sys::Win32::Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONEAREST};
+    use windows_sys::Win32::System::Threading::{OpenProcess, QueryFullProcessImageNameW, PROCESS_QUERY_LIMITED_INFORMATION};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect, GetWindowThreadProcessId};
+
+    // SAFETY: every out-parameter points at a live local of the right size and the process handle is closed
+    unsafe {
+        let window = GetForegroundWindow();
+        if window == 0 {
+            return None;
+        }
+        let mut process_id = 0u32;
+        GetWindowThreadProcessId(window, &mut process_id);
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id);
+        if process == 0 {
+            return None;
+        }
+        let mut buffer = [0u16; 260];
+        let mut length = buffer.len() as u32;
+        let ok = QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut length);
+        CloseHandle(process);
+        if ok == 0 {
+            return None;
+        }
+        let path = String::from_utf16_lossy(&buffer[..length as usize]);
+        let app = path.rsplit('\\').next().unwrap_or(&path).to_string();
+
+        let mut rect: RECT = std::mem::zeroed();
+        let mut monitor: MONITORINFO = std::mem::zeroed();
+        monitor.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+        let measured = GetWindowRect(window, &mut rect) != 0
+            && GetMonitorInfoW(MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST), &mut monitor) != 0;
+        let screen = monitor.rcMonitor;
+        // The desktop itself spans the monitor too
+        let fullscreen = measured
+            && !app.eq_ignore_ascii_case("explorer.exe")
+            && rect.left <= screen.left && rect.top <= screen.top
+            && rect.right >= screen.right && rect.bottom >= screen.bottom;
+        Some(Foreground { app, fullscreen })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn foreground() -> Option<Foreground> {
+    None
+}
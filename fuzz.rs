@@ -0,0 +1,167 @@
+// Monkey testing, the `fuzz` subcommand.
+//
+// Sends random clicks, double and right clicks, drags and scrolls to points
+// inside a region of the screen for a set time. The sequence comes from a
+// seeded generator, and every event is also written to a script as it is
+// sent, so a crash in the application under test can be reproduced either by
+// fuzzing again with the same seed and region or by playing the log back.
+// Injection goes through the usual rate limit, audit log and foreground
+// guard, so `--only-app` keeps the monkey inside the application under test.
+
+use std::error::Error;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use csv::Writer;
+use enigo::MouseButton;
+use rdev::{listen, EventType};
+use tracing::{info, warn};
+use crate::controls::{self, Controls};
+use crate::foreground::ForegroundGuard;
+use crate::injector::Injector;
+use crate::random::Random;
+use crate::screen::Region;
+use crate::{generate, MouseAction};
+
+const DEFAULT_DURATION: Duration = Duration::from_secs(60);
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(200);
+const DEFAULT_STOP_KEY: &str = "F10";
+// How long a drag holds the button before moving, so applications register it
+const DRAG_HOLD: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Default)]
+pub struct FuzzOptions {
+    pub region: Option<Region>,
+    pub seed: Option<u64>,
+    pub duration: Option<Duration>,
+    pub interval: Option<Duration>,
+    // Script the events are written to
+    pub log: Option<String>,
+    pub stop_key: Option<String>,
+}
+
+pub fn run(mut injector: Injector, mut guard: Option<ForegroundGuard>, options: FuzzOptions) -> Result<(), Box<dyn Error>> {
+    let region = match options.region {
+        Some(region) => region,
+        None => {
+            let (width, height) = injector.display_size();
+            Region { x: 0, y: 0, width: width.max(1) as u32, height: height.max(1) as u32 }
+        },
+    };
+    let seed = match options.seed {
+        Some(seed) => seed,
+        None => Random::seed()?,
+    };
+    let duration = options.duration.unwrap_or(DEFAULT_DURATION);
+    let interval = options.interval.unwrap_or(DEFAULT_INTERVAL);
+    let log_path = options.log.clone().unwrap_or_else(|| format!("fuzz-{}.csv", seed));
+    let mut log = Writer::from_path(&log_path).map_err(|e| format!("Failed to create '{}': {}", log_path, e))?;
+    let key_name = options.stop_key.as_deref().unwrap_or(DEFAULT_STOP_KEY);
+    let stop_key = controls::parse_key(key_name).ok_or_else(|| format!("Unknown stop key: {}", key_name))?;
+
+    let controls = Controls::new();
+    controls.handle_ctrl_c()?;
+    let listener = Arc::clone(&controls);
+    thread::spawn(move || {
+        let result = listen(move |event| {
+            if event.event_type == EventType::KeyPress(stop_key) {
+                listener.request_abort();
+            }
+        });
+        if let Err(error) = result {
+            warn!("stop key listener failed: {:?}", error);
+        }
+    });
+
+    info!("Fuzzing {}x{} at ({}, {}) for {} s with seed {}; press {} or Ctrl+C to stop",
+        region.width, region.height, region.x, region.y, duration.as_secs(), seed, key_name);
+    info!("Logging events to {}", log_path);
+
+    let mut random = Random::new(seed);
+    let started = Instant::now();
+    let mut events = 0;
+    while !controls.abort_requested() && started.elapsed() < duration {
+        controls.sleep(interval);
+        if controls.abort_requested() {
+            break;
+        }
+        events += 1;
+        if let Some(guard) = guard.as_mut() {
+            guard.check(events, &controls)?;
+        }
+
+        let (x, y) = random.point(region);
+        let mut rows = Vec::new();
+        match random.below(100) {
+            0..=49 => {
+                info!("#{} click ({}, {})", events, x, y);
+                injector.move_to(x, y);
+                injector.click(MouseButton::Left);
+                rows.push(row("click", Some((x, y))));
+            },
+            50..=59 => {
+                info!("#{} double click ({}, {})", events, x, y);
+                injector.move_to(x, y);
+                injector.click(MouseButton::Left);
+                thread::sleep(Duration::from_millis(10));
+                injector.click(MouseButton::Left);
+                rows.push(row("double_click", Some((x, y))));
+            },
+            60..=69 => {
+                info!("#{} right click ({}, {})", events, x, y);
+                injector.move_to(x, y);
+                injector.click(MouseButton::Right);
+                rows.push(row("right_click", Some((x, y))));
+            },
+            70..=84 => {
+                let (to_x, to_y) = random.point(region);
+                info!("#{} drag ({}, {}) -> ({}, {})", events, x, y, to_x, to_y);
+                injector.move_to(x, y);
+                injector.button_down(MouseButton::Left);
+                thread::sleep(DRAG_HOLD);
+                injector.move_to(to_x, to_y);
+                injector.button_up(MouseButton::Left);
+                rows.push(row("drag", Some((x, y))));
+                rows.push(MouseAction { delay_ms: Some(DRAG_HOLD.as_millis() as u64), ..row("release", Some((to_x, to_y))) });
+            },
+            _ => {
+                let down = random.below(2) == 0;
+                info!("#{} scroll {} ({}, {})", events, if down { "down" } else { "up" }, x, y);
+                injector.move_to(x, y);
+                injector.scroll_y(if down { -1 } else { 1 });
+                rows.push(row("move", Some((x, y))));
+                rows.push(MouseAction { modifiers: Some(if down { "down" } else { "up" }.to_string()), ..row("scroll", None) });
+            },
+        }
+
+        // The first row of each event waits the interval, as the fuzzer did
+        rows[0].delay_ms = Some(interval.as_millis() as u64);
+        for record in &rows {
+            log.serialize(record)?;
+        }
+        log.flush()?;
+    }
+    info!("Sent {} event(s) in {:.1} s with seed {}; replay them with: mouse-automation {}",
+        events, started.elapsed().as_secs_f64(), seed, log_path);
+    Ok(())
+}
+
+fn row(action: &str, position: Option<(i32, i32)>) -> MouseAction {
+    MouseAction {
+        action: action.to_string(),
+        x_position: position.map(|(x, _)| x),
+        y_position: position.map(|(_, y)| y),
+        delay_ms: Some(0),
+        repeat_count: Some(1),
+        ..Default::default()
+    }
+}
+
+// "100,80,1280x720": the top-left corner, then the size
+pub fn parse_region(text: &str) -> Option<Region> {
+    let mut parts = text.splitn(3, ',');
+    let x = parts.next()?.trim().parse().ok()?;
+    let y = parts.next()?.trim().parse().ok()?;
+    let (width, height) = generate::parse_size(parts.next()?)?;
+    (width > 0 && height > 0).then_some(Region { x, y, width, height })
+}
@@ -0,0 +1,133 @@
+// Script generators, the `generate` subcommand.
+//
+//   generate grid --origin 100,200 --cell 40x40 --grid 10x5 [--delay 50] [--order spiral] -o grid.csv
+//
+// Clicks the center of every cell of a grid whose top-left corner is the
+// origin. The grid size is columns x rows. Cells are visited row by row
+// (row-major, the default), column by column (column-major), row by row
+// alternating direction (snake), or from the outside in, clockwise from the
+// top-left corner (spiral).
+
+use std::error::Error;
+use csv::Writer;
+use tracing::info;
+use crate::MouseAction;
+
+const USAGE: &str = "Usage: mouse-automation generate grid --origin <x,y> --cell <width>x<height> --grid <columns>x<rows> [--delay <ms>] [--order row-major|column-major|snake|spiral] -o <script.csv>";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    RowMajor,
+    ColumnMajor,
+    Snake,
+    Spiral,
+}
+
+impl Order {
+    pub fn parse(name: &str) -> Option<Order> {
+        match name {
+            "row-major" => Some(Order::RowMajor),
+            "column-major" => Some(Order::ColumnMajor),
+            "snake" => Some(Order::Snake),
+            "spiral" => Some(Order::Spiral),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GridOptions {
+    pub origin: Option<(i32, i32)>,
+    pub cell: Option<(u32, u32)>,
+    pub size: Option<(u32, u32)>,
+    pub delay_ms: Option<u64>,
+    pub order: Order,
+    pub output: Option<String>,
+}
+
+pub fn run(kind: &str, options: &GridOptions) -> Result<(), Box<dyn Error>> {
+    if kind != "grid" {
+        return Err(format!("Unknown generator '{}'. {}", kind, USAGE).into());
+    }
+    let (Some((x, y)), Some((cell_width, cell_height)), Some((columns, rows)), Some(path)) =
+        (options.origin, options.cell, options.size, options.output.as_deref()) else {
+        return Err(USAGE.into());
+    };
+    if columns == 0 || rows == 0 {
+        return Err("The grid needs at least one column and one row".into());
+    }
+
+    let clicks: Vec<MouseAction> = cells(columns, rows, options.order).into_iter().map(|(column, row)| MouseAction {
+        action: "click".to_string(),
+        x_position: Some(x + (column * cell_width + cell_width / 2) as i32),
+        y_position: Some(y + (row * cell_height + cell_height / 2) as i32),
+        delay_ms: Some(options.delay_ms.unwrap_or(0)),
+        button: Some("left".to_string()),
+        repeat_count: Some(1),
+        ..Default::default()
+    }).collect();
+
+    let mut writer = Writer::from_path(path).map_err(|e| format!("Failed to create '{}': {}", path, e))?;
+    for row in &clicks {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    info!("Wrote {} click(s) to {}", clicks.len(), path);
+    Ok(())
+}
+
+// (column, row) of every cell in visiting order
+fn cells(columns: u32, rows: u32, order: Order) -> Vec<(u32, u32)> {
+    match order {
+        Order::RowMajor => (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).collect(),
+        Order::ColumnMajor => (0..columns).flat_map(|column| (0..rows).map(move |row| (column, row))).collect(),
+        Order::Snake => (0..rows).flat_map(|row| (0..columns).map(move |column| {
+            let column = if row % 2 == 0 { column } else { columns - 1 - column };
+            (column, row)
+        })).collect(),
+        Order::Spiral => spiral(columns, rows),
+    }
+}
+
+// Walk the outer ring clockwise, then the next ring in, until every cell is visited
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let mut cells = Vec::with_capacity((columns * rows) as usize);
+    let (mut left, mut top) = (0i64, 0i64);
+    let (mut right, mut bottom) = (columns as i64 - 1, rows as i64 - 1);
+    while left <= right && top <= bottom {
+        for column in left..=right {
+            cells.push((column, top));
+        }
+        for row in top + 1..=bottom {
+            cells.push((right, row));
+        }
+        if top < bottom {
+            for column in (left..right).rev() {
+                cells.push((column, bottom));
+            }
+        }
+        if left < right {
+            for row in (top + 1..bottom).rev() {
+                cells.push((left, row));
+            }
+        }
+        left += 1;
+        top += 1;
+        right -= 1;
+        bottom -= 1;
+    }
+    cells.into_iter().map(|(column, row)| (column as u32, row as u32)).collect()
+}
+
+// "100,200"
+pub fn parse_point(text: &str) -> Option<(i32, i32)> {
+    let (x, y) = text.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+// "40x30"
+pub fn parse_size(text: &str) -> Option<(u32, u32)> {
+    let (width, height) = text.split_once(['x', 'X'])?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
@@ -0,0 +1,165 @@
+// gRPC control service (proto/automation.proto), started by `serve --grpc`.
+//
+// Offers the same operations as the HTTP API with typed clients. The service
+// runs on its own thread with a small tokio runtime; scripts still play back
+// on plain threads through the shared Runs. Every call is checked like an HTTP
+// request: with `--token-file` it must carry `authorization: Bearer <token>`
+// metadata, and one with an `origin` not allowed with `--allow-origin` is
+// refused.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status};
+use tracing::{info, warn};
+use crate::runs::{RunError, Runs};
+use crate::server::Access;
+
+mod proto {
+    tonic::include_proto!("mouse_automation");
+}
+
+use proto::automation_server::{Automation, AutomationServer};
+use proto::{
+    CancelReply, CancelRequest, GetStatusRequest, RecordReply, RecordRequest, RunScriptReply, RunScriptRequest,
+    StatusReply,
+};
+
+struct Service {
+    runs: Arc<Runs>,
+}
+
+#[tonic::async_trait]
+impl Automation for Service {
+    async fn run_script(&self, request: Request<RunScriptRequest>) -> Result<Response<RunScriptReply>, Status> {
+        let request = request.into_inner();
+        if let Some(content) = &request.content {
+            self.runs.save_script(&request.name, content)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        }
+        self.runs.start(&request.name).map_err(to_status)?;
+        Ok(Response::new(RunScriptReply {}))
+    }
+
+    async fn record(&self, request: Request<RecordRequest>) -> Result<Response<RecordReply>, Status> {
+        let request = request.into_inner();
+        let runs = Arc::clone(&self.runs);
+        // Recording blocks for the whole duration, so keep it off the runtime's workers
+        let content = tokio::task::spawn_blocking(move || {
+            runs.record(&request.name, Duration::from_secs(u64::from(request.duration_secs)))
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(to_status)?;
+        Ok(Response::new(RecordReply { content }))
+    }
+
+    async fn get_status(&self, _request: Request<GetStatusRequest>) -> Result<Response<StatusReply>, Status> {
+        let status = self.runs.status();
+        Ok(Response::new(StatusReply {
+            state: status.state,
+            script: status.script,
+            step: status.step as u64,
+            total_steps: status.total_steps as u64,
+            error: status.error,
+        }))
+    }
+
+    async fn cancel(&self, _request: Request<CancelRequest>) -> Result<Response<CancelReply>, Status> {
+        self.runs.with_active(|controls| controls.request_abort()).map_err(to_status)?;
+        Ok(Response::new(CancelReply {}))
+    }
+}
+
+pub fn serve(address: &str, runs: Arc<Runs>, access: Arc<Access>) -> Result<(), Box<dyn Error>> {
+    let address: SocketAddr = address.parse()
+        .map_err(|e| format!("Invalid gRPC address '{}': {}", address, e))?;
+    if !access.has_token() && !address.ip().is_loopback() {
+        warn!("{} accepts gRPC calls from other machines and there is no --token-file; anyone who can reach it can run scripts and record input", address);
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()?;
+    info!("gRPC service listening on {}", address);
+
+    thread::spawn(move || {
+        let server = Server::builder()
+            .add_service(AutomationServer::with_interceptor(Service { runs }, move |request| authorize(&access, request)))
+            .serve(address);
+        if let Err(error) = runtime.block_on(server) {
+            warn!("gRPC service stopped: {}", error);
+        }
+    });
+    Ok(())
+}
+
+// Refuse a call from another page, or without the token
+fn authorize(access: &Access, request: Request<()>) -> Result<Request<()>, Status> {
+    let metadata = |name: &str| request.metadata().get(name).and_then(|value| value.to_str().ok());
+    if !access.origin_allowed(metadata("origin")) {
+        warn!("Refused a gRPC call from another web page");
+        return Err(Status::permission_denied("calls from other web pages are refused; allow the page's origin with --allow-origin"));
+    }
+    if !access.token_accepted(metadata("authorization").and_then(|value| value.strip_prefix("Bearer "))) {
+        warn!("Refused a gRPC call: bad or missing token");
+        return Err(Status::unauthenticated("bad or missing token"));
+    }
+    Ok(request)
+}
+
+fn to_status(error: RunError) -> Status {
+    let code = match error {
+        RunError::Busy | RunError::Idle => Code::FailedPrecondition,
+        RunError::NotFound => Code::NotFound,
+        RunError::BadRequest(_) | RunError::Invalid(_) => Code::InvalidArgument,
+        RunError::Forbidden(_) => Code::PermissionDenied,
+        RunError::Failed(_) => Code::Internal,
+    };
+    Status::new(code, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn access(token: Option<&str>) -> Access {
+        let Some(token) = token else {
+            return Access::new(None, &[]).unwrap();
+        };
+        let path = env::temp_dir().join(format!("mouse-automation-grpc-token-{}", process::id()));
+        fs::write(&path, token).unwrap();
+        let access = Access::new(path.to_str(), &[]);
+        fs::remove_file(&path).unwrap();
+        access.unwrap()
+    }
+
+    // The metadata a RunScript or Record call reaches the interceptor with
+    fn call(access: &Access, metadata: &[(&'static str, &str)]) -> Result<(), Code> {
+        let mut request = Request::new(());
+        for &(name, value) in metadata {
+            request.metadata_mut().insert(name, value.parse().unwrap());
+        }
+        authorize(access, request).map(drop).map_err(|status| status.code())
+    }
+
+    #[test]
+    fn calls_without_the_token_are_refused() {
+        let access = access(Some("0123456789abcdef"));
+        assert_eq!(call(&access, &[]), Err(Code::Unauthenticated));
+        assert_eq!(call(&access, &[("authorization", "Bearer fedcba9876543210")]), Err(Code::Unauthenticated));
+        assert_eq!(call(&access, &[("authorization", "0123456789abcdef")]), Err(Code::Unauthenticated));
+        assert_eq!(call(&access, &[("authorization", "Bearer 0123456789abcdef")]), Ok(()));
+    }
+
+    #[test]
+    fn calls_from_other_pages_are_refused() {
+        let access = access(None);
+        assert_eq!(call(&access, &[]), Ok(()));
+        assert_eq!(call(&access, &[("origin", "https://example.com")]), Err(Code::PermissionDenied));
+    }
+}
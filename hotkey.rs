@@ -0,0 +1,73 @@
+// Global hotkeys such as "F13" or "ctrl+alt+r", for the hotkey launcher and
+// the abort key.
+//
+// Modifiers are ctrl, alt, shift and meta (cmd/win), and the key is named as
+// for `--resume-key`. Key events come from rdev, so the modifiers held at the
+// time are tracked from the press and release events seen so far.
+
+use rdev::{EventType, Key};
+use crate::controls::parse_key;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Modifiers {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    meta: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hotkey {
+    modifiers: Modifiers,
+    key: Key,
+}
+
+impl Hotkey {
+    pub fn parse(spec: &str) -> Option<Hotkey> {
+        let mut parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+        let key = parse_key(parts.pop()?)?;
+        let mut modifiers = Modifiers::default();
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" | "option" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "meta" | "cmd" | "win" | "super" => modifiers.meta = true,
+                _ => return None,
+            }
+        }
+        Some(Hotkey { modifiers, key })
+    }
+}
+
+// Follows the modifiers through a stream of key events
+#[derive(Default)]
+pub struct Tracker {
+    held: Modifiers,
+}
+
+impl Tracker {
+    // The hotkey an event presses, if it presses a key that isn't a modifier
+    pub fn pressed(&mut self, event: &EventType) -> Option<Hotkey> {
+        match *event {
+            EventType::KeyPress(key) if !self.track_modifier(key, true) => Some(Hotkey { modifiers: self.held, key }),
+            EventType::KeyRelease(key) => {
+                self.track_modifier(key, false);
+                None
+            },
+            _ => None,
+        }
+    }
+
+    // Update the held modifiers; returns whether the key was a modifier
+    fn track_modifier(&mut self, key: Key, pressed: bool) -> bool {
+        match key {
+            Key::ControlLeft | Key::ControlRight => self.held.ctrl = pressed,
+            Key::Alt | Key::AltGr => self.held.alt = pressed,
+            Key::ShiftLeft | Key::ShiftRight => self.held.shift = pressed,
+            Key::MetaLeft | Key::MetaRight => self.held.meta = pressed,
+            _ => return false,
+        }
+        true
+    }
+}
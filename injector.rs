@@ -0,0 +1,238 @@
+// The single path through which events reach the OS, so safety limits and
+// auditing apply to every injected move, click, scroll and keystroke. The
+// events themselves are delivered by an input backend (see backend.rs).
+
+use enigo::{Key, MouseButton};
+use tracing::{debug, info};
+use crate::audit::AuditLog;
+use crate::backend::InputBackend;
+use crate::keyboard::{self, TypingMode};
+use crate::rate_limit::ClickLimiter;
+
+pub struct Injector {
+    backend: Box<dyn InputBackend>,
+    click_limiter: ClickLimiter,
+    audit: Option<AuditLog>,
+    // CSV row currently being executed, for the audit log
+    row: usize,
+    // Keys pressed by `key_down` and not yet released
+    held_keys: Vec<Key>,
+    // Buttons held down by a drag, or while a click is held
+    held_buttons: Vec<MouseButton>,
+    // Events since the last take_journal, when a run report wants them
+    journal: Option<Vec<String>>,
+    typing: TypingMode,
+    // The keyboard layout is logged before the first text is typed
+    layout_logged: bool,
+    // Whether the cursor has been moved to a position, which a backend that
+    // can't read it back then knows
+    placed: bool,
+}
+
+impl Injector {
+    pub fn new(backend: Box<dyn InputBackend>, click_limiter: ClickLimiter, audit: Option<AuditLog>) -> Self {
+        Injector {
+            backend,
+            click_limiter,
+            audit,
+            row: 0,
+            held_keys: Vec::new(),
+            held_buttons: Vec::new(),
+            journal: None,
+            typing: TypingMode::default(),
+            layout_logged: false,
+            placed: false,
+        }
+    }
+
+    pub fn set_row(&mut self, row: usize) {
+        self.row = row;
+    }
+
+    pub fn set_typing(&mut self, typing: TypingMode) {
+        self.typing = typing;
+    }
+
+    pub fn enable_journal(&mut self) {
+        self.journal.get_or_insert_with(Vec::new);
+    }
+
+    pub fn take_journal(&mut self) -> Vec<String> {
+        self.journal.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn location(&self) -> (i32, i32) {
+        self.backend.location()
+    }
+
+    pub fn display_size(&self) -> (i32, i32) {
+        self.backend.display_size()
+    }
+
+    // False under uinput, where location() is only where the cursor was put
+    pub fn reads_cursor(&self) -> bool {
+        self.backend.reads_cursor()
+    }
+
+    // Whether location() can be trusted yet
+    pub fn knows_cursor(&self) -> bool {
+        self.placed || self.backend.reads_cursor()
+    }
+
+    pub fn move_to(&mut self, x: i32, y: i32) {
+        self.backend.move_to(x, y);
+        self.placed = true;
+        self.audit("move", Some((x, y)), None, None, None);
+    }
+
+    pub fn move_relative(&mut self, dx: i32, dy: i32) {
+        self.backend.move_relative(dx, dy);
+        self.audit("move_relative", Some((dx, dy)), None, None, None);
+    }
+
+    pub fn click(&mut self, button: MouseButton) {
+        self.click_limiter.wait();
+        self.backend.click(button);
+        self.audit("click", None, Some(format!("{:?}", button)), None, None);
+    }
+
+    pub fn button_down(&mut self, button: MouseButton) {
+        self.click_limiter.wait();
+        self.backend.button_down(button);
+        if !self.held_buttons.contains(&button) {
+            self.held_buttons.push(button);
+        }
+        self.audit("button_down", None, Some(format!("{:?}", button)), None, None);
+    }
+
+    pub fn button_up(&mut self, button: MouseButton) {
+        self.backend.button_up(button);
+        self.held_buttons.retain(|held| *held != button);
+        self.audit("button_up", None, Some(format!("{:?}", button)), None, None);
+    }
+
+    pub fn scroll_x(&mut self, amount: i32) {
+        self.backend.scroll_x(amount);
+        self.audit("scroll_x", None, None, None, Some(amount));
+    }
+
+    pub fn scroll_y(&mut self, amount: i32) {
+        self.backend.scroll_y(amount);
+        self.audit("scroll_y", None, None, None, Some(amount));
+    }
+
+    pub fn key_down(&mut self, key: Key) {
+        self.backend.key_down(key);
+        if !self.held_keys.contains(&key) {
+            self.held_keys.push(key);
+        }
+        self.audit("key_down", None, None, Some(format!("{:?}", key)), None);
+    }
+
+    pub fn key_up(&mut self, key: Key) {
+        self.backend.key_up(key);
+        self.held_keys.retain(|held| *held != key);
+        self.audit("key_up", None, None, Some(format!("{:?}", key)), None);
+    }
+
+    // Hold the modifiers, tap the last key, release in reverse order
+    pub fn chord(&mut self, keys: &[Key]) {
+        let Some((&key, modifiers)) = keys.split_last() else { return };
+        for &modifier in modifiers {
+            self.key_down(modifier);
+        }
+        self.backend.key_click(key);
+        self.audit("key_click", None, None, Some(format!("{:?}", key)), None);
+        for &modifier in modifiers.iter().rev() {
+            self.key_up(modifier);
+        }
+    }
+
+    // Type text so it comes out right on the active keyboard layout
+    pub fn type_text(&mut self, text: &str) -> Result<(), String> {
+        if !self.layout_logged {
+            self.layout_logged = true;
+            let layout = keyboard::active_layout().unwrap_or_else(|| "unknown".to_string());
+            debug!("Typing with keyboard layout {} ({:?} mode)", layout, self.typing);
+        }
+        if self.typing.uses_keys() {
+            self.type_keys(text)?;
+        } else {
+            self.backend.key_sequence(text);
+        }
+        // Only the length is logged; typed text may be a password
+        self.audit("type", None, None, None, Some(text.chars().count() as i32));
+        Ok(())
+    }
+
+    // Layout keystrokes for each character; in auto mode runs the layout can't type go as Unicode
+    fn type_keys(&mut self, text: &str) -> Result<(), String> {
+        let mut unicode = String::new();
+        for c in text.chars() {
+            let Some(keys) = keyboard::layout_keystroke(c) else {
+                if self.typing == TypingMode::Keys {
+                    return Err(format!("'{}' can't be typed with the active keyboard layout; use --typing unicode", c));
+                }
+                unicode.push(c);
+                continue;
+            };
+            if !unicode.is_empty() {
+                self.backend.key_sequence(&unicode);
+                unicode.clear();
+            }
+            // Not through key_down/key_click, which would put the typed text in the audit log
+            let Some((&key, modifiers)) = keys.split_last() else { continue };
+            for &modifier in modifiers {
+                self.backend.key_down(modifier);
+            }
+            self.backend.key_click(key);
+            for &modifier in modifiers.iter().rev() {
+                self.backend.key_up(modifier);
+            }
+        }
+        if !unicode.is_empty() {
+            self.backend.key_sequence(&unicode);
+        }
+        Ok(())
+    }
+
+    // Let go of keys and buttons a script left held, so they don't stick after
+    // the run, including one that was aborted mid-drag
+    pub fn release_keys(&mut self) {
+        while let Some(key) = self.held_keys.pop() {
+            info!("Releasing held key {:?}", key);
+            self.backend.key_up(key);
+            self.audit("key_up", None, None, Some(format!("{:?}", key)), None);
+        }
+        while let Some(button) = self.held_buttons.pop() {
+            info!("Releasing held {:?} button", button);
+            self.backend.button_up(button);
+            self.audit("button_up", None, Some(format!("{:?}", button)), None, None);
+        }
+    }
+
+    fn audit(&mut self, event: &str, position: Option<(i32, i32)>, button: Option<String>, key: Option<String>, amount: Option<i32>) {
+        if let Some(journal) = self.journal.as_mut() {
+            let mut line = event.to_string();
+            if let Some((x, y)) = position {
+                line.push_str(&format!(" ({}, {})", x, y));
+            }
+            for detail in [&button, &key].into_iter().flatten() {
+                line.push_str(&format!(" {}", detail));
+            }
+            if let Some(amount) = amount {
+                line.push_str(&format!(" {}", amount));
+            }
+            journal.push(line);
+        }
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record(self.row, event, position, button, key, amount);
+        }
+    }
+}
+
+impl Drop for Injector {
+    fn drop(&mut self) {
+        self.release_keys();
+    }
+}
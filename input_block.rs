@@ -0,0 +1,45 @@
+// OS-level blocking of physical keyboard and mouse input (Windows only).
+//
+// BlockInput requires an elevated process. While input is blocked only the
+// thread that blocked it can inject events, and Ctrl+Alt+Del always unblocks.
+
+use std::error::Error;
+
+// Blocks physical input until dropped
+pub struct InputBlock {
+    _private: (),
+}
+
+impl InputBlock {
+    #[cfg(windows)]
+    pub fn acquire() -> Result<InputBlock, Box<dyn Error>> {
+        use tracing::info;
+        use windows_sys::Win32::UI::Input::KeyboardAndMouse::BlockInput;
+
+        // SAFETY: BlockInput takes no pointers and only toggles system state
+        if unsafe { BlockInput(1) } == 0 {
+            return Err("BlockInput failed; --block-input requires running as administrator".into());
+        }
+        info!("Physical keyboard and mouse input is blocked (Ctrl+Alt+Del releases it)");
+        Ok(InputBlock { _private: () })
+    }
+
+    #[cfg(not(windows))]
+    pub fn acquire() -> Result<InputBlock, Box<dyn Error>> {
+        Err("--block-input is only supported on Windows".into())
+    }
+}
+
+impl Drop for InputBlock {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        {
+            use tracing::info;
+            use windows_sys::Win32::UI::Input::KeyboardAndMouse::BlockInput;
+
+            // SAFETY: see acquire
+            unsafe { BlockInput(0) };
+            info!("Physical input unblocked");
+        }
+    }
+}
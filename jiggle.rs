@@ -0,0 +1,96 @@
+// Keep-awake mouse jiggler, the `jiggle` subcommand.
+//
+// Nudges the cursor by a few pixels every interval so the session doesn't
+// idle-lock or go "away" during long monitoring sessions. The `alternate`
+// pattern moves the cursor out on one tick and back on the next; `zero-net`
+// traces a tiny square within each tick, so the cursor always ends where it
+// started. Ticks are skipped while the user is moving the mouse themselves.
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+use tracing::info;
+use crate::controls::Controls;
+use crate::injector::Injector;
+use crate::session;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_AMPLITUDE: i32 = 1;
+// Pause between the moves of a zero-net square
+const STEP_PAUSE: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pattern {
+    #[default]
+    Alternate,
+    ZeroNet,
+}
+
+impl Pattern {
+    pub fn parse(name: &str) -> Option<Pattern> {
+        match name {
+            "alternate" => Some(Pattern::Alternate),
+            "zero-net" => Some(Pattern::ZeroNet),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JiggleOptions {
+    pub interval: Option<Duration>,
+    // Pixels per nudge
+    pub amplitude: Option<i32>,
+    pub pattern: Pattern,
+    pub duration: Option<Duration>,
+}
+
+pub fn run(mut injector: Injector, options: JiggleOptions) -> Result<(), Box<dyn Error>> {
+    let interval = options.interval.unwrap_or(DEFAULT_INTERVAL);
+    let amplitude = options.amplitude.unwrap_or(DEFAULT_AMPLITUDE);
+    if amplitude < 1 {
+        return Err("--amplitude must be at least 1 pixel".into());
+    }
+    let controls = Controls::new();
+    controls.handle_ctrl_c()?;
+    info!("Jiggling the mouse by {} px every {} s ({:?}); press Ctrl+C to stop", amplitude, interval.as_secs(), options.pattern);
+
+    let started = Instant::now();
+    let mut last = injector.location();
+    // Direction of the next alternate nudge
+    let mut outward = true;
+    let mut jiggles: u64 = 0;
+    loop {
+        controls.sleep(interval);
+        if controls.abort_requested() || options.duration.is_some_and(|duration| started.elapsed() >= duration) {
+            break;
+        }
+        let location = injector.location();
+        if location != last || session::is_locked() {
+            // Someone is using the mouse (or can't be kept awake by it), so leave it alone
+            last = location;
+            continue;
+        }
+
+        match options.pattern {
+            Pattern::Alternate => {
+                injector.move_relative(if outward { amplitude } else { -amplitude }, 0);
+                outward = !outward;
+            },
+            Pattern::ZeroNet => {
+                for (dx, dy) in [(amplitude, 0), (0, amplitude), (-amplitude, 0), (0, -amplitude)] {
+                    injector.move_relative(dx, dy);
+                    controls.sleep(STEP_PAUSE);
+                }
+            },
+        }
+        jiggles += 1;
+        last = injector.location();
+    }
+
+    // An unpaired alternate nudge is undone so the cursor ends where it started
+    if !outward {
+        injector.move_relative(-amplitude, 0);
+    }
+    info!("Stopped after {} jiggle(s)", jiggles);
+    Ok(())
+}
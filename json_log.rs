@@ -0,0 +1,103 @@
+// Machine-readable record of every run, for `--log-json`.
+//
+// Appends one JSON object per line: a `started` line for each run, a `step`
+// line for every step executed and a `finished` line with the outcome, so
+// external tools can audit and post-process runs. A step line has the time it
+// started, its index and CSV row, the action, the desktop positions its
+// coordinates were resolved to (after monitors, windows, display scaling and
+// --out-of-bounds), how long it took, how many attempts it needed, and
+// whether it succeeded, with the error if not. With `--target` the agent
+// resolves positions, so they are left out.
+//
+//     {"event":"step","time":1760433612.25,"step":3,"row":4,"action":"click",
+//      "positions":[[640,400]],"duration_ms":112,"attempts":1,"ok":true}
+
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tracing::{info, warn};
+use crate::{lint, MouseAction};
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Entry<'a> {
+    Started {
+        time: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        script: Option<&'a str>,
+        total_steps: usize,
+    },
+    Step {
+        time: f64,
+        step: usize,
+        row: usize,
+        action: &'a str,
+        #[serde(skip_serializing_if = "<[_]>::is_empty")]
+        positions: &'a [(i32, i32)],
+        duration_ms: u64,
+        attempts: u32,
+        ok: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Finished {
+        time: f64,
+        ok: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+pub struct JsonLog {
+    file: File,
+}
+
+impl JsonLog {
+    pub fn open(path: &str) -> Result<JsonLog, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("Failed to open JSON log '{}': {}", path, e))?;
+        info!("Logging steps as JSON to {}", path);
+        Ok(JsonLog { file })
+    }
+
+    pub fn started(&mut self, script: Option<&str>, total_steps: usize) {
+        self.write(&Entry::Started { time: seconds(SystemTime::now()), script, total_steps });
+    }
+
+    // A step that took `duration` up to now, with the positions it resolved
+    pub fn step(&mut self, index: usize, record: &MouseAction, positions: &[(i32, i32)], duration: Duration, attempts: u32, error: Option<String>) {
+        let started = SystemTime::now().checked_sub(duration).unwrap_or(UNIX_EPOCH);
+        self.write(&Entry::Step {
+            time: seconds(started),
+            step: index + 1,
+            row: lint::row_number(index),
+            action: &record.action,
+            positions,
+            duration_ms: duration.as_millis() as u64,
+            attempts,
+            ok: error.is_none(),
+            error,
+        });
+    }
+
+    pub fn finished(&mut self, error: Option<String>) {
+        self.write(&Entry::Finished { time: seconds(SystemTime::now()), ok: error.is_none(), error });
+    }
+
+    // One write per line so a crash never leaves a partial entry behind
+    fn write(&mut self, entry: &Entry) {
+        let written = serde_json::to_string(entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| self.file.write_all(format!("{}\n", line).as_bytes()).map_err(|e| e.to_string()));
+        if let Err(error) = written {
+            warn!("failed to write JSON log entry: {}", error);
+        }
+    }
+}
+
+// Seconds since the Unix epoch, microsecond precision
+fn seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_micros() as f64 / 1_000_000.0)
+}
@@ -0,0 +1,110 @@
+// Test-runner mode, for `--test` and `--junit`.
+//
+// Each checkpoint section of the script is a test case, named after its
+// checkpoint, and the rows before the first checkpoint are one named after
+// the script. A failed step fails its test case and the rest of that section
+// is skipped, so playback carries on with the next test; with --keep-going
+// the section plays on and every failure is listed. The run fails if any test
+// did, and a JUnit XML file is written for CI to pick up, with a test case for
+// every section, the failed steps' errors, and the sections that never ran
+// (not reached, or already completed in --state-file) as skipped.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+use crate::{lint, report, MouseAction};
+
+// Where the results go without --junit
+pub const DEFAULT_JUNIT: &str = "junit.xml";
+
+struct Case {
+    name: String,
+    // Steps played and how long they took
+    steps: u64,
+    time: Duration,
+    failures: Vec<String>,
+}
+
+pub struct TestRun {
+    suite: String,
+    started: SystemTime,
+    cases: Vec<Case>,
+    // Index of the case each row belongs to
+    case_of: Vec<usize>,
+}
+
+impl TestRun {
+    pub fn new(suite: &str, actions: &[MouseAction]) -> TestRun {
+        let stem = Path::new(suite).file_stem().and_then(|stem| stem.to_str()).unwrap_or(suite);
+        let mut cases = vec![Case { name: stem.to_string(), steps: 0, time: Duration::ZERO, failures: Vec::new() }];
+        let mut case_of = Vec::with_capacity(actions.len());
+        for record in actions {
+            if record.action == "checkpoint" {
+                let name = record.name.clone().filter(|name| !name.is_empty()).unwrap_or_else(|| format!("row {}", lint::row_number(case_of.len())));
+                cases.push(Case { name, steps: 0, time: Duration::ZERO, failures: Vec::new() });
+            }
+            case_of.push(cases.len() - 1);
+        }
+        TestRun { suite: suite.to_string(), started: SystemTime::now(), cases, case_of }
+    }
+
+    // Name of the test case a row belongs to
+    pub fn name(&self, index: usize) -> &str {
+        &self.cases[self.case_of[index]].name
+    }
+
+    pub fn record(&mut self, index: usize, duration: Duration, error: Option<String>) {
+        let case = &mut self.cases[self.case_of[index]];
+        case.steps += 1;
+        case.time += duration;
+        case.failures.extend(error.map(|error| format!("row {}: {}", lint::row_number(index), error)));
+    }
+
+    pub fn failed(&self) -> usize {
+        self.cases.iter().filter(|case| !case.failures.is_empty()).count()
+    }
+
+    // Print the results and write them as JUnit XML
+    pub fn finish(&self, path: &str, outcome: Result<(), String>) -> Result<(), Box<dyn Error>> {
+        // The leading case only counts if the script has rows before its first checkpoint
+        let cases: Vec<&Case> = self.cases.iter().enumerate()
+            .filter(|&(number, _)| number > 0 || self.case_of.first() == Some(&0))
+            .map(|(_, case)| case)
+            .collect();
+        let skipped = cases.iter().filter(|case| case.steps == 0).count();
+        let failed = self.failed();
+        for case in cases.iter().filter(|case| !case.failures.is_empty()) {
+            error!("Test '{}' failed:\n  {}", case.name, case.failures.join("\n  "));
+        }
+        info!("Tests: {} passed, {} failed, {} skipped", cases.len() - failed - skipped, failed, skipped);
+
+        let time: Duration = cases.iter().map(|case| case.time).sum();
+        let mut xml = String::new();
+        writeln!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(xml, "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">", cases.len(), failed, skipped, time.as_secs_f64())?;
+        writeln!(xml, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\" timestamp=\"{}\">",
+            report::escape(&self.suite), cases.len(), failed, skipped, time.as_secs_f64(), report::utc_timestamp(self.started).replace(' ', "T"))?;
+        for case in &cases {
+            write!(xml, "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"", report::escape(&case.name), report::escape(&self.suite), case.time.as_secs_f64())?;
+            if case.steps == 0 {
+                writeln!(xml, ">\n      <skipped message=\"not run\"/>\n    </testcase>")?;
+            } else if let Some(first) = case.failures.first() {
+                writeln!(xml, ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>", report::escape(first), report::escape(&case.failures.join("\n")))?;
+            } else {
+                writeln!(xml, "/>")?;
+            }
+        }
+        // Why the run stopped, if it did
+        if let Err(error) = outcome {
+            writeln!(xml, "    <system-err>{}</system-err>", report::escape(&error))?;
+        }
+        writeln!(xml, "  </testsuite>\n</testsuites>")?;
+
+        fs::write(path, xml).map_err(|e| format!("Failed to write JUnit results '{}': {}", path, e))?;
+        info!("Wrote test results to {}", path);
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+// Key names and chords for the keyboard actions.
+//
+// Keys are named case-insensitively: enter, tab, escape, backspace, delete,
+// up, down, left, right, home, end, pageup, pagedown, space, capslock,
+// f1-f12, the modifiers ctrl, alt, shift and meta (cmd/win/super), or any
+// single character, which is typed with the current layout. A chord joins
+// keys with `+`, modifiers first: "ctrl+shift+t", "alt+f4", "ctrl+plus".
+//
+// Text is typed as Unicode where the platform's text injection already follows
+// the active layout (X11, macOS). On Windows, `auto` typing presses the keys
+// the foreground window's layout uses for each character (AltGr+0 for '@' on
+// AZERTY, Y for 'z' on QWERTZ) and falls back to Unicode injection only for
+// characters the layout can't produce, since some applications (games,
+// remote desktops, terminals) ignore injected Unicode.
+
+use serde::Deserialize;
+use enigo::Key;
+
+// How type_text turns characters into input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypingMode {
+    // Layout keystrokes where needed and possible, Unicode otherwise
+    #[default]
+    Auto,
+    // Always inject Unicode text
+    Unicode,
+    // Always press layout keys; characters the layout lacks are an error
+    Keys,
+}
+
+impl TypingMode {
+    pub fn parse(name: &str) -> Option<TypingMode> {
+        match name {
+            "auto" => Some(TypingMode::Auto),
+            "unicode" => Some(TypingMode::Unicode),
+            "keys" => Some(TypingMode::Keys),
+            _ => None,
+        }
+    }
+
+    // Whether to type key by key rather than hand the platform the whole text
+    pub fn uses_keys(self) -> bool {
+        match self {
+            TypingMode::Auto => cfg!(windows),
+            TypingMode::Unicode => false,
+            TypingMode::Keys => true,
+        }
+    }
+}
+
+pub fn parse_key(name: &str) -> Option<Key> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Key::Layout(c));
+    }
+
+    let key = match name.to_ascii_lowercase().as_str() {
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "esc" | "escape" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "space" => Key::Space,
+        "plus" => Key::Layout('+'),
+        "capslock" => Key::CapsLock,
+        "ctrl" | "control" => Key::Control,
+        "alt" | "option" => Key::Alt,
+        "shift" => Key::Shift,
+        "meta" | "cmd" | "win" | "super" => Key::Meta,
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Pointer actions that hold the keys in their modifiers column while they press;
+// a scroll row's modifiers column is its direction instead
+const MODIFIER_ACTIONS: &[&str] = &[
+    "click", "double_click", "triple_click", "click_and_hold", "right_click", "drag", "release", "drag_to",
+    "draw_circle", "draw_rectangle", "draw_polyline",
+];
+
+pub fn takes_modifiers(action: &str) -> bool {
+    MODIFIER_ACTIONS.contains(&action)
+}
+
+// The modifiers column of a click or drag, such as "ctrl+shift", in press order
+pub fn parse_modifiers(spec: &str) -> Result<Vec<Key>, String> {
+    spec.split('+')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match parse_key(name) {
+            Some(key @ (Key::Control | Key::Alt | Key::Shift | Key::Meta)) => Ok(key),
+            _ => Err(format!("'{}' isn't a modifier; use ctrl, alt, shift or meta (cmd)", name)),
+        })
+        .collect()
+}
+
+// The keys of a chord in press order; the last one is the key being pressed
+pub fn parse_chord(spec: &str) -> Result<Vec<Key>, String> {
+    if spec.trim().is_empty() {
+        return Err("no key given".to_string());
+    }
+    spec.split('+')
+        .map(str::trim)
+        .map(|name| parse_key(name).ok_or_else(|| format!("unknown key '{}'", name)))
+        .collect()
+}
+
+// Keys producing `c` on the foreground window's layout, modifiers first
+#[cfg(windows)]
+pub fn layout_keystroke(c: char) -> Option<Vec<Key>> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetKeyboardLayout, VkKeyScanExW};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    let mut units = [0u16; 2];
+    let [unit] = c.encode_utf16(&mut units) else {
+        // Outside the Basic Multilingual Plane; no layout has a key for it
+        return None;
+    };
+    // SAFETY: a null process id pointer is allowed, and the other calls take plain values
+    let scan = unsafe {
+        let thread = GetWindowThreadProcessId(GetForegroundWindow(), std::ptr::null_mut());
+        VkKeyScanExW(*unit, GetKeyboardLayout(thread))
+    };
+    if scan == -1 {
+        return None;
+    }
+    let (virtual_key, state) = ((scan & 0xff) as u16, (scan >> 8) & 0xff);
+    // Bits above shift, ctrl and alt are IME and kana states we can't reproduce
+    if state & !0b111 != 0 {
+        return None;
+    }
+    let mut keys = Vec::new();
+    for (bit, modifier) in [(1, Key::Shift), (2, Key::Control), (4, Key::Alt)] {
+        if state & bit != 0 {
+            keys.push(modifier);
+        }
+    }
+    keys.push(Key::Raw(virtual_key));
+    Some(keys)
+}
+
+// Elsewhere the layout lookup happens when the character key is pressed
+#[cfg(not(windows))]
+pub fn layout_keystroke(c: char) -> Option<Vec<Key>> {
+    Some(vec![Key::Layout(c)])
+}
+
+// The foreground window's layout, for the log
+#[cfg(windows)]
+pub fn active_layout() -> Option<String> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::GetKeyboardLayout;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    // SAFETY: as in layout_keystroke
+    let layout = unsafe { GetKeyboardLayout(GetWindowThreadProcessId(GetForegroundWindow(), std::ptr::null_mut())) };
+    // The low word is the language identifier, e.g. 040C for French
+    (layout != 0).then(|| format!("language 0x{:04X}", layout as usize & 0xffff))
+}
+
+#[cfg(target_os = "linux")]
+pub fn active_layout() -> Option<String> {
+    let output = std::process::Command::new("setxkbmap").arg("-query").output().ok()?;
+    let query = String::from_utf8_lossy(&output.stdout);
+    let layout = query.lines().find_map(|line| line.strip_prefix("layout:"))?.trim().to_string();
+    let variant = query.lines().find_map(|line| line.strip_prefix("variant:")).map(str::trim);
+    Some(match variant {
+        Some(variant) if !variant.is_empty() => format!("{} ({})", layout, variant),
+        _ => layout,
+    })
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn active_layout() -> Option<String> {
+    None
+}
@@ -0,0 +1,58 @@
+// Resident hotkey launcher, started by the `hotkeys` subcommand.
+//
+// Registers the global hotkeys in the `[hotkeys]` config table and starts the
+// mapped script from the scripts directory when one is pressed:
+//
+//   [hotkeys]
+//   "F13" = "open-ticket.csv"
+//   "ctrl+alt+r" = "restock.csv"
+
+use std::error::Error;
+use std::sync::mpsc::channel;
+use std::thread;
+use rdev::listen;
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::hotkey::{Hotkey, Tracker};
+use crate::runs::Runs;
+use crate::Options;
+
+pub fn run(options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    if config.hotkeys.is_empty() {
+        return Err("No hotkeys configured; map hotkeys to scripts in the [hotkeys] table of the config".into());
+    }
+    let mut bindings = Vec::new();
+    for (spec, script) in &config.hotkeys {
+        let hotkey = Hotkey::parse(spec).ok_or_else(|| format!("Invalid hotkey '{}' in [hotkeys]", spec))?;
+        bindings.push((spec.clone(), hotkey, script.clone()));
+    }
+    let hotkeys: Vec<Hotkey> = bindings.iter().map(|(_, hotkey, _)| *hotkey).collect();
+
+    let runs = Runs::new(options, config)?;
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let mut tracker = Tracker::default();
+        let result = listen(move |event| {
+            let pressed = tracker.pressed(&event.event_type);
+            if let Some(index) = hotkeys.iter().position(|hotkey| Some(*hotkey) == pressed) {
+                let _ = sender.send(index);
+            }
+        });
+        if let Err(error) = result {
+            warn!("hotkey listener failed: {:?}", error);
+        }
+    });
+
+    for (spec, _, script) in &bindings {
+        info!("{} -> {}", spec, script);
+    }
+    info!("Waiting for hotkeys (scripts in {}); press Ctrl+C to quit", runs.scripts_dir().display());
+    for index in receiver {
+        let (spec, _, script) = &bindings[index];
+        match runs.start(script) {
+            Ok(()) => info!("{}: started {}", spec, script),
+            Err(error) => warn!("{}: {}", spec, error),
+        }
+    }
+    Err("Hotkey listener stopped".into())
+}
@@ -0,0 +1,1425 @@
+// Mouse automation as a library.
+//
+// Scripts are lists of `MouseAction` rows, usually loaded from CSV. A
+// `ScriptRunner` plays them back with the same safety checks as the command
+// line (policy, bounds, lock screen and foreground guards, rate limit, audit
+// log), or executes single actions one at a time. The `mouse-automation`
+// binary is a thin wrapper around `cli::run`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use csv::{Reader, StringRecord};
+use enigo::{Key, MouseButton};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod agent;
+mod audit;
+mod backend;
+mod baseline;
+mod autoclick;
+mod binary;
+mod bounds;
+mod checkpoints;
+pub mod cli;
+mod clipboard_watch;
+mod combo;
+mod config;
+mod control_socket;
+mod controller;
+mod controls;
+mod convert;
+mod dry_run;
+mod dwell;
+mod events;
+mod failsafe;
+mod folder_watch;
+mod flow;
+mod foreground;
+mod fuzz;
+mod generate;
+mod grpc;
+mod hotkey;
+mod injector;
+mod jiggle;
+mod json_log;
+mod junit;
+mod input_block;
+mod keyboard;
+mod launcher;
+mod library;
+mod lint;
+mod locate;
+mod logging;
+mod metrics;
+mod monitors;
+mod motion;
+mod mqtt;
+mod policy;
+mod preflight;
+mod random;
+mod rate_limit;
+mod recorder;
+mod report;
+mod runner;
+mod runs;
+mod screen;
+mod server;
+mod session;
+mod vars;
+mod signing;
+mod stroke;
+mod template;
+mod timings;
+mod uinput;
+mod video;
+mod visualize;
+mod watchdog;
+mod webhook;
+mod websocket;
+mod window;
+mod yaml;
+
+use controls::UserInputWatch;
+use events::Event;
+use flow::Flow;
+use foreground::{ForegroundGuard, OnGuard};
+use audit::AuditLog;
+use backend::{BackendKind, InputBackend};
+use bounds::{Bounds, OutOfBounds};
+use checkpoints::StateFile;
+use controller::RemoteTarget;
+use injector::Injector;
+use json_log::JsonLog;
+use junit::TestRun;
+use keyboard::TypingMode;
+use input_block::InputBlock;
+use monitors::Layout;
+use motion::Motion;
+use policy::Policy;
+use rate_limit::ClickLimiter;
+use report::Report;
+use timings::Timings;
+use screen::Region;
+use session::{LockWatch, OnLock};
+use vars::Vars;
+use video::VideoRecording;
+
+pub use backend::{SimulatedBackend, SimulatedEvent};
+pub use config::Config;
+pub use controls::Controls;
+pub use runner::{Script, ScriptRunner};
+
+// One row of a script
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MouseAction {
+    pub action: String,
+    pub x_position: Option<i32>,
+    pub y_position: Option<i32>,
+    pub delay_ms: Option<u64>,
+    pub button: Option<String>,
+    pub modifiers: Option<String>,
+    pub repeat_count: Option<u32>,
+    pub name: Option<String>,
+    // Text for type_text, key or chord for the key actions
+    pub text: Option<String>,
+    pub key: Option<String>,
+    // Pause between typed characters
+    pub interval_ms: Option<u64>,
+    // Region size, baseline image and per-channel tolerance for assert_region_matches
+    // (the tolerance applies to if_pixel, assert_pixel and wait_for_image too,
+    // and is in pixels for assert_cursor_position)
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub image: Option<String>,
+    pub tolerance: Option<u8>,
+    // Expected color for if_pixel and assert_pixel, as #rrggbb
+    pub color: Option<String>,
+    // How long wait_for_image waits for its image, or a window row for its window
+    pub timeout_ms: Option<u64>,
+    // How long moving to the row's position takes, instead of --move-duration,
+    // how long a scroll is spread over, a click_and_hold holds or a drag_to drags
+    pub duration_ms: Option<u64>,
+    // Monitor the coordinates are on, numbered from 1
+    pub monitor: Option<u32>,
+    // Title of the window the coordinates are inside
+    pub window: Option<String>,
+    // Where a drag_to drops, on the same monitor or window as its start
+    pub to_x: Option<i32>,
+    pub to_y: Option<i32>,
+    // Waypoints or Bezier control points a drag_to passes on the way
+    pub path: Option<String>,
+    // How many more times a failed row is tried, waiting retry_delay_ms before
+    // the first retry and twice as long before each one after it
+    pub retries: Option<u32>,
+    pub retry_delay_ms: Option<u64>,
+}
+
+// What the binary was asked to do
+#[derive(Debug, Clone, Default)]
+enum Command {
+    #[default]
+    Run,
+    Keygen { secret_path: String, public_path: String },
+    Sign { script_path: String, secret_path: String },
+    Verify { script_path: String, public_path: String },
+    Control { command: String },
+    Lint { script_path: String },
+    Convert { input: String, output: String },
+    Serve { address: String, token_file: Option<String>, allow_origins: Vec<String> },
+    Mqtt { broker: Option<String> },
+    Webhook { address: String, secret_file: Option<String> },
+    Hotkeys,
+    Clipboard,
+    Watch { dir: String, script: String, pattern: Option<String> },
+    Agent { address: String, controller_key: Option<String> },
+    Controller { command: String, agents: Vec<String> },
+    Record { output_path: String, recording: recorder::RecordOptions },
+    Scripts { command: String, name: Option<String>, description: Option<String> },
+    Generate { kind: String, grid: generate::GridOptions },
+    Visualize { script_path: String, output: Option<String>, background: Option<String> },
+    Dwell { dwell: dwell::DwellOptions },
+    ClickAt { clicking: autoclick::ClickAtOptions },
+    Jiggle { jiggle: jiggle::JiggleOptions },
+    Combo { combo: combo::ComboOptions },
+    Fuzz { fuzz: fuzz::FuzzOptions },
+}
+
+// Command line options
+#[derive(Debug, Clone, Default)]
+struct Options {
+    command: Command,
+    // Number of -v and -q flags, and --log-file
+    verbose: u8,
+    quiet: u8,
+    log_file: Option<String>,
+    csv_path: Option<String>,
+    pause_on_user_input: bool,
+    resume_key: Option<String>,
+    block_input: bool,
+    confirm: bool,
+    assume_yes: bool,
+    require_signature: Option<String>,
+    config_path: Option<String>,
+    deny: Vec<String>,
+    max_clicks_per_second: Option<u32>,
+    on_lock: Option<OnLock>,
+    restore_cursor: bool,
+    control: bool,
+    control_endpoint: Option<String>,
+    out_of_bounds: Option<OutOfBounds>,
+    state_file: Option<String>,
+    skip_preflight: bool,
+    // Abort a step that takes this many times as long as it should
+    watchdog: Option<f64>,
+    // Wall-clock budget for the whole run, every pass included
+    max_duration_secs: Option<u64>,
+    audit_log: Option<String>,
+    scripts_dir: Option<String>,
+    ws_address: Option<String>,
+    grpc_address: Option<String>,
+    key_path: Option<String>,
+    target: Option<String>,
+    vars: Vars,
+    only_apps: Vec<String>,
+    never_apps: Vec<String>,
+    no_fullscreen: bool,
+    on_guard: Option<OnGuard>,
+    record_video: Option<String>,
+    video_window: Option<String>,
+    failure_dir: Option<String>,
+    no_failure_screenshots: bool,
+    report: Option<String>,
+    // Planned against actual step times at the end, and where to write them
+    timings: bool,
+    timings_file: Option<String>,
+    // Structured record of every step, one JSON object per line
+    log_json: Option<String>,
+    // Sections are test cases, with JUnit XML results written here
+    test: bool,
+    junit: Option<String>,
+    metrics_address: Option<String>,
+    typing: Option<TypingMode>,
+    update_baselines: bool,
+    // Factor every delay is multiplied by
+    speed: Option<f64>,
+    dry_run: bool,
+    // Where --dry-run draws its steps over the live screen
+    overlay: Option<String>,
+    // Passes over the script for `run`; 0 repeats until stopped
+    loops: Option<u32>,
+    // How long moves take unless a row sets duration_ms
+    move_duration: Option<Duration>,
+    // Between the clicks of a double or triple click, unless a row sets interval_ms
+    click_interval: Option<Duration>,
+    humanize: bool,
+    // Hotkey that stops playback, or "none"
+    abort_key: Option<String>,
+    // Hotkey that pauses and resumes playback, or "none"
+    pause_key: Option<String>,
+    // Stop when the cursor is pushed into a screen corner
+    failsafe: bool,
+    // Countdown before the first action
+    start_delay_secs: Option<u64>,
+    // Rows to play, as CSV line numbers
+    from_row: Option<usize>,
+    to_row: Option<usize>,
+    // Wait for Enter after every step
+    step: bool,
+    // Carry on past failed steps and fail at the end instead
+    keep_going: bool,
+    // Coordinates are logical pixels, scaled by the display's scale factor
+    logical_pixels: bool,
+    // What delivers the events, and one ScriptRunner::simulate plays into instead
+    backend: Option<BackendKind>,
+    simulated: Option<SimulatedBackend>,
+}
+
+// Verify, parse and policy-check a script before anything runs
+fn prepare_script(path: &str, options: &Options, config: &Config, vars: &Vars) -> Result<Vec<MouseAction>, Box<dyn Error>> {
+    // Refuse scripts that weren't signed by an approved key
+    if let Some(public_path) = options.require_signature.as_ref().or(config.require_signature.as_ref()) {
+        signing::verify(path, public_path)?;
+        info!("Script signature verified");
+    }
+    
+    // Open and parse the CSV file
+    let mut actions = load_script(path, vars)?;
+    expand_snippets(&mut actions, config, vars)?;
+    check_policy(&actions, options, config)?;
+    Ok(actions)
+}
+
+// Refuse denied action categories before anything is executed
+fn check_policy(actions: &[MouseAction], options: &Options, config: &Config) -> Result<(), Box<dyn Error>> {
+    build_policy(options, config)?.check(actions)
+}
+
+// The categories denied by the config and --deny
+fn build_policy(options: &Options, config: &Config) -> Result<Policy, Box<dyn Error>> {
+    let mut policy = Policy::default();
+    for category in &config.policy.deny {
+        policy.deny(*category);
+    }
+    for list in &options.deny {
+        policy.deny_list(list)?;
+    }
+    Ok(policy)
+}
+
+// Fill in the text of snippet rows from the [snippets] config table
+fn expand_snippets(actions: &mut [MouseAction], config: &Config, vars: &Vars) -> Result<(), Box<dyn Error>> {
+    for (index, record) in actions.iter_mut().enumerate().filter(|(_, record)| record.action == "snippet") {
+        let name = record.name.as_deref().ok_or_else(|| format!("Step {}: snippet needs a name", index + 1))?;
+        let template = config.snippets.get(name)
+            .ok_or_else(|| format!("Step {}: no snippet named '{}' in the [snippets] config", index + 1, name))?;
+        let text = vars::substitute(template, vars).map_err(|e| format!("Step {}: snippet '{}': {}", index + 1, name, e))?;
+        record.text = Some(text);
+        record.interval_ms = record.interval_ms.or(config.snippet_interval_ms);
+    }
+    Ok(())
+}
+
+// Play back a prepared script with all configured safety features
+fn execute_script(actions: &[MouseAction], options: &Options, config: &Config, controls: &Arc<Controls>) -> Result<(), Box<dyn Error>> {
+    let result = play_script(actions, options, config, controls);
+    // Also sent when the run fails before the first step, e.g. in preflight
+    controls.publish(Event::Finished { ok: result.is_ok(), error: result.as_ref().err().map(|e| e.to_string()) });
+    result
+}
+
+fn play_script(actions: &[MouseAction], options: &Options, config: &Config, controls: &Arc<Controls>) -> Result<(), Box<dyn Error>> {
+    let rows = row_range(options, actions.len())?;
+    
+    // Show what would run without touching the mouse
+    if options.dry_run {
+        return dry_run::run(actions, rows, options.out_of_bounds.or(config.out_of_bounds).unwrap_or_default(), options.overlay.as_deref());
+    }
+    
+    let (playback, result) = play(options, config, controls, actions.len(), |playback| {
+        playback.rows = rows;
+        if options.test {
+            playback.tests = Some(TestRun::new(options.csv_path.as_deref().unwrap_or("script"), actions));
+        }
+        playback.run(actions)
+    })?;
+    
+    if let Some(timings) = &playback.timings {
+        if let Err(error) = timings.finish(actions, options.timings_file.as_deref()) {
+            warn!("{}", error);
+        }
+    }
+    if let Some(tests) = &playback.tests {
+        let outcome = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+        if let Err(error) = tests.finish(options.junit.as_deref().unwrap_or(junit::DEFAULT_JUNIT), outcome) {
+            warn!("{}", error);
+        }
+    }
+    if let (Some(path), Some(report)) = (&options.report, &playback.report) {
+        let outcome = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+        if let Err(error) = report.write(path, options.csv_path.as_deref(), actions, outcome) {
+            warn!("{}", error);
+        }
+    }
+    result
+}
+
+// Play a binary script as it is decoded, for recordings too long to load first
+fn stream_script(path: &Path, total_steps: usize, options: &Options, config: &Config, controls: &Arc<Controls>) -> Result<(), Box<dyn Error>> {
+    let result = binary::read(path).and_then(|records| {
+        play(options, config, controls, total_steps, |playback| playback.run_decoded(records, total_steps))?.1
+    });
+    controls.publish(Event::Finished { ok: result.is_ok(), error: result.as_ref().err().map(|e| e.to_string()) });
+    result
+}
+
+// Whether a binary script can play as it is decoded, and how many rows it has:
+// its rows only ever run in order, none is denied or dangerous or
+// `also_loaded`, and no option needs every row at once
+fn streamable(path: &Path, options: &Options, config: &Config, also_loaded: impl Fn(&MouseAction) -> bool) -> Result<Option<usize>, Box<dyn Error>> {
+    let whole_script = options.dry_run || options.test || options.timings || options.report.is_some() || options.confirm
+        || options.from_row.is_some() || options.to_row.is_some();
+    if whole_script {
+        return Ok(None);
+    }
+    if let Some(public_path) = options.require_signature.as_ref().or(config.require_signature.as_ref()) {
+        signing::verify(&path.to_string_lossy(), public_path)?;
+        info!("Script signature verified");
+    }
+    let policy = build_policy(options, config)?;
+    let mut rows = 0;
+    for record in binary::read(path)? {
+        let record = record?;
+        let in_order = !flow::is_flow(&record.action) && !matches!(record.action.as_str(), "checkpoint" | "if_pixel" | "snippet");
+        if !in_order || !policy.allows(&record) || policy::category(&record).is_dangerous() || also_loaded(&record) {
+            return Ok(None);
+        }
+        rows += 1;
+    }
+    Ok(Some(rows))
+}
+
+// How a run went, apart from failing to set it up
+type Outcome = Result<(), Box<dyn Error>>;
+
+// Set up a playback with the run-wide safety features around `body`, and put
+// the cursor back after it; the playback is returned with how the run went
+fn play(options: &Options, config: &Config, controls: &Arc<Controls>, total_steps: usize, body: impl FnOnce(&mut Playback) -> Result<(), Box<dyn Error>>)
+    -> Result<(Playback, Outcome), Box<dyn Error>> {
+    if let Some(speed) = options.speed {
+        controls.set_delay_scale(speed);
+    }
+    
+    // Make sure injected events will actually arrive
+    let backend = backend_kind(options, config);
+    if !options.skip_preflight && options.target.is_none() {
+        backend.preflight(options.block_input)?;
+    }
+    
+    let mut playback = build_playback(options, config, controls, backend)?;
+    
+    // Stream the actions to an agent instead of injecting them here
+    if let Some(target) = &options.target {
+        playback.remote = Some(RemoteTarget::connect(target, options.key_path.as_deref())?);
+    }
+    
+    info!("Successfully opened CSV file. Starting automation...");
+    
+    // Held for the whole run; dropping it (including on error) unblocks input
+    let _input_block = if options.block_input {
+        if options.pause_on_user_input {
+            warn!("--pause-on-user-input has no effect while input is blocked");
+        }
+        Some(InputBlock::acquire()?)
+    } else {
+        None
+    };
+    
+    // Remember where the user left the cursor; the config file's setting doesn't apply to an agent's
+    let restore_cursor = options.restore_cursor || config.restore_cursor && options.target.is_none();
+    let original_position = restore_cursor.then(|| playback.injector.location());
+    
+    // Abort a step that runs far longer than it should; waiting for Enter under --step isn't stuck
+    if let Some(multiple) = options.watchdog.or(config.watchdog).filter(|_| !playback.step) {
+        controls.start_step(Duration::MAX);
+        watchdog::spawn(Arc::clone(controls), multiple);
+    }
+    
+    // Throwing the mouse into a corner stops the run
+    if options.failsafe || config.failsafe {
+        failsafe::spawn(Arc::clone(controls));
+    }
+    
+    // Evidence of what the run did; finalized when dropped at the end of the run
+    let _video = options.record_video.as_deref()
+        .map(|path| VideoRecording::start(path, options.video_window.as_deref(), playback.injector.display_size()))
+        .transpose()?;
+    
+    if let Some(log) = playback.json_log.as_mut() {
+        log.started(options.csv_path.as_deref(), total_steps);
+    }
+    let result = body(&mut playback);
+    if let Some(log) = playback.json_log.as_mut() {
+        log.finished(result.as_ref().err().map(|e| e.to_string()));
+    }
+    
+    // Put the cursor back whether the run finished or was aborted
+    if let Some((x, y)) = original_position {
+        info!("Restoring cursor to ({}, {})", x, y);
+        playback.injector.move_to(x, y);
+    }
+    Ok((playback, result))
+}
+
+// Indices of the rows between --from-row and --to-row, which are CSV line numbers
+fn row_range(options: &Options, len: usize) -> Result<Range<usize>, Box<dyn Error>> {
+    if options.from_row.is_none() && options.to_row.is_none() {
+        return Ok(0..len);
+    }
+    let (first_row, last_row) = (lint::row_number(0), lint::row_number(len.saturating_sub(1)));
+    let from = options.from_row.unwrap_or(first_row);
+    let to = options.to_row.unwrap_or(last_row);
+    if len == 0 || !(first_row..=last_row).contains(&from) || !(from..=last_row).contains(&to) {
+        return Err(format!("Rows {}-{} are not within the script's rows {}-{}", from, to, first_row, last_row).into());
+    }
+    info!("Playing rows {}-{}", from, to);
+    Ok(from - first_row..to - first_row + 1)
+}
+
+// Set up injection and the per-step safety checks for this machine
+fn max_clicks_per_second(options: &Options, config: &Config) -> u32 {
+    options.max_clicks_per_second
+        .or(config.max_clicks_per_second)
+        .unwrap_or(rate_limit::DEFAULT_MAX_CLICKS_PER_SECOND)
+}
+
+// The backend to play through, with `auto` resolved for this session
+fn backend_kind(options: &Options, config: &Config) -> BackendKind {
+    options.backend.or(config.backend).unwrap_or_default().resolve()
+}
+
+// Mouse controller with the configured click rate limit, audit log and typing mode
+fn build_injector(options: &Options, config: &Config, backend: BackendKind) -> Result<Injector, Box<dyn Error>> {
+    let click_limiter = ClickLimiter::new(max_clicks_per_second(options, config));
+    let audit = options.audit_log.as_ref().or(config.audit_log.as_ref())
+        .map(|path| AuditLog::open(path))
+        .transpose()?;
+    let backend: Box<dyn InputBackend> = match &options.simulated {
+        Some(simulated) => Box::new(simulated.clone()),
+        None => backend.open()?,
+    };
+    let mut injector = Injector::new(backend, click_limiter, audit);
+    injector.set_typing(options.typing.or(config.typing).unwrap_or_default());
+    Ok(injector)
+}
+
+// Foreground conditions from the config and command line together
+fn build_guard(options: &Options, config: &Config) -> Option<ForegroundGuard> {
+    ForegroundGuard::new(
+        [&config.guard.only_apps[..], &options.only_apps[..]].concat(),
+        [&config.guard.never_apps[..], &options.never_apps[..]].concat(),
+        options.no_fullscreen || config.guard.no_fullscreen,
+        options.on_guard.or(config.guard.on_guard).unwrap_or_default(),
+    )
+}
+
+fn build_playback(options: &Options, config: &Config, controls: &Arc<Controls>, backend: BackendKind) -> Result<Playback, Box<dyn Error>> {
+    let injector = build_injector(options, config, backend)?;
+    let screen = injector.display_size();
+    // Nothing reaches the desktop, so its lock screen, foreground window and screenshots don't matter
+    let simulated = options.simulated.is_some() || backend == BackendKind::Simulated;
+    let on_lock = if simulated { OnLock::Ignore } else { options.on_lock.or(config.on_lock).unwrap_or_default() };
+    let mut playback = Playback {
+        injector,
+        controls: Arc::clone(controls),
+        lock_watch: LockWatch::new(on_lock),
+        foreground_guard: build_guard(options, config).filter(|_| !simulated),
+        user_input_watch: None,
+        bounds: Bounds::new(options.out_of_bounds.or(config.out_of_bounds).unwrap_or_default(), screen),
+        state: options.state_file.as_deref().map(StateFile::open).transpose()?,
+        failure_dir: (!simulated && !options.no_failure_screenshots && !config.no_failure_screenshots).then(|| {
+            PathBuf::from(options.failure_dir.as_ref().or(config.failure_dir.as_ref()).map_or(screen::DEFAULT_FAILURE_DIR, String::as_str))
+        }),
+        remote: None,
+        report: None,
+        timings: None,
+        update_baselines: options.update_baselines,
+        motion: Motion::new(
+            options.move_duration.or(config.move_duration_ms.map(Duration::from_millis)).unwrap_or_default(),
+            options.click_interval.or(config.click_interval_ms.map(Duration::from_millis)),
+            options.humanize || config.humanize,
+        )?,
+        rows: 0..usize::MAX,
+        step: options.step,
+        monitors: Layout::default(),
+        logical_pixels: options.logical_pixels || config.logical_pixels,
+        drag_modifiers: Vec::new(),
+        positions: HashMap::new(),
+        keep_going: options.keep_going || config.keep_going,
+        failures: Vec::new(),
+        json_log: options.log_json.as_deref().map(JsonLog::open).transpose()?,
+        resolved: Vec::new(),
+        tests: None,
+    };
+    if playback.logical_pixels {
+        monitors::use_physical_pixels();
+    }
+    
+    // Per-step results and injected events for the HTML report
+    if options.report.is_some() {
+        playback.injector.enable_journal();
+        playback.report = Some(Report::new());
+    }
+    
+    if options.timings {
+        let move_duration = options.move_duration.or(config.move_duration_ms.map(Duration::from_millis)).unwrap_or_default();
+        playback.timings = Some(Timings::new(move_duration, options.speed.unwrap_or(1.0)));
+    }
+    
+    // uinput only knows where it last put the cursor, which these can't go by
+    if !playback.injector.reads_cursor() && options.target.is_none() {
+        let needs_cursor = [
+            ("--restore-cursor", options.restore_cursor || config.restore_cursor),
+            ("--pause-on-user-input", options.pause_on_user_input),
+            ("--failsafe", options.failsafe || config.failsafe),
+        ];
+        if let Some((flag, _)) = needs_cursor.iter().find(|(_, on)| *on) {
+            return Err(format!("{} needs to see the cursor, which the uinput backend can't; use --backend enigo", flag).into());
+        }
+    }
+    
+    // Watch for the user grabbing the mouse during playback
+    if options.pause_on_user_input {
+        playback.user_input_watch = Some(UserInputWatch::new(Arc::clone(controls), resume_key(options)?));
+    }
+    Ok(playback)
+}
+
+// Show the step just played and wait for Enter; false if the user asked to stop
+fn wait_for_next_step(index: usize, record: &MouseAction) -> Result<bool, Box<dyn Error>> {
+    print!("Step {} (row {}) done: {} {}. Enter for the next step, q to stop: ",
+        index + 1, lint::row_number(index), record.action, report::details(record));
+    io::stdout().flush()?;
+    
+    // End of input stops too, so a closed stdin can't run the rest unattended
+    let mut answer = String::new();
+    let read = io::stdin().read_line(&mut answer)?;
+    Ok(read > 0 && !answer.trim().eq_ignore_ascii_case("q"))
+}
+
+fn resume_key(options: &Options) -> Result<rdev::Key, Box<dyn Error>> {
+    let key_name = options.resume_key.as_deref().unwrap_or("F9");
+    controls::parse_key(key_name).ok_or_else(|| format!("Unknown resume key: {}", key_name).into())
+}
+
+// Wait before a row's first retry unless it sets retry_delay_ms
+const DEFAULT_RETRY_DELAY_MS: u64 = 500;
+
+// Everything needed to play back a script
+struct Playback {
+    injector: Injector,
+    controls: Arc<Controls>,
+    lock_watch: LockWatch,
+    foreground_guard: Option<ForegroundGuard>,
+    user_input_watch: Option<UserInputWatch>,
+    bounds: Bounds,
+    state: Option<StateFile>,
+    // Where to save a screenshot when a step fails
+    failure_dir: Option<PathBuf>,
+    // Agent that executes the actions, for `--target`
+    remote: Option<RemoteTarget>,
+    report: Option<Report>,
+    timings: Option<Timings>,
+    // Recreate assert_region_matches baselines instead of comparing
+    update_baselines: bool,
+    // Smoothing and humanizing of moves and clicks
+    motion: Motion,
+    // Indices of the rows --from-row and --to-row leave to play
+    rows: Range<usize>,
+    // --step: hand back to the user after every action
+    step: bool,
+    // Where each monitor sits, for rows with a monitor column
+    monitors: Layout,
+    // --logical-pixels: scale positions to physical pixels first
+    logical_pixels: bool,
+    // Modifiers a drag row holds until the release row
+    drag_modifiers: Vec<Key>,
+    // Cursor positions save_position rows took, by name
+    positions: HashMap<String, (i32, i32)>,
+    // --keep-going: steps that failed so far, with their errors
+    keep_going: bool,
+    failures: Vec<(usize, String)>,
+    // --log-json, and the desktop positions the current step resolved for it
+    json_log: Option<JsonLog>,
+    resolved: Vec<(i32, i32)>,
+    // --test: the test case each section is, and how it went
+    tests: Option<TestRun>,
+}
+
+// How a row went, for the run loop to pick the next one
+enum Played {
+    Done,
+    // An if_pixel row whose pixel didn't match
+    NoMatch,
+    // A step that failed under --keep-going or --test
+    Failed,
+}
+
+impl Playback {
+    // Process each row of the script in order
+    fn run(&mut self, actions: &[MouseAction]) -> Result<(), Box<dyn Error>> {
+        // Checkpointed section currently running, and whether we're skipping a finished one
+        let mut current_section: Option<&str> = None;
+        let mut skipping = false;
+        // A section with a failed step isn't marked completed, so it runs again
+        let mut section_failed = false;
+        let mut flow = Flow::new(actions)?;
+        
+        // A jump back above --from-row is followed; going past --to-row ends the run
+        let mut next = self.rows.start;
+        while next < actions.len().min(self.rows.end) {
+            let index = next;
+            let record = &actions[index];
+            next += 1;
+            if self.controls.run_aborted() {
+                return Err(format!("Aborted before step {}", index + 1).into());
+            }
+            
+            if record.action == "checkpoint" {
+                // A failed test's section was skipped up to here
+                skipping = false;
+                let Some(state) = self.state.as_mut() else { continue };
+                let name = record.name.as_deref().ok_or_else(|| format!("Step {}: checkpoint needs a name", index + 1))?;
+                
+                // Reaching a checkpoint completes the section before it
+                if let Some(finished) = current_section.take().filter(|_| !section_failed) {
+                    state.mark_done(finished)?;
+                }
+                section_failed = false;
+                skipping = state.is_done(name);
+                if skipping {
+                    info!("Skipping section '{}' (already completed)", name);
+                } else {
+                    current_section = Some(name);
+                }
+                continue;
+            }
+            if skipping {
+                if let Some(report) = self.report.as_mut() {
+                    report.skip(index);
+                }
+                continue;
+            }
+            
+            // Loops and the like only decide which row comes next
+            if flow::is_flow(&record.action) {
+                next = flow.next(index, record);
+                continue;
+            }
+            
+            match self.play_row(index, record, actions.len())? {
+                Played::Done => {},
+                Played::NoMatch => next = flow.otherwise(index, record),
+                // A failed test's section is skipped up to the next checkpoint
+                Played::Failed => {
+                    skipping = self.tests.is_some() && !self.keep_going;
+                    section_failed = true;
+                },
+            }
+        }
+        
+        if let (Some(state), Some(finished), false) = (self.state.as_mut(), current_section, section_failed) {
+            state.mark_done(finished)?;
+        }
+        self.failed_steps()
+    }
+    
+    // Rows decoded one at a time by `stream_script`, which only run in order
+    fn run_decoded(&mut self, records: impl Iterator<Item = Result<MouseAction, String>>, total_steps: usize) -> Result<(), Box<dyn Error>> {
+        for (index, record) in records.enumerate() {
+            let record = record?;
+            if self.controls.run_aborted() {
+                return Err(format!("Aborted before step {}", index + 1).into());
+            }
+            self.play_row(index, &record, total_steps)?;
+        }
+        self.failed_steps()
+    }
+    
+    // The run's result from the steps that failed under --keep-going or --test
+    fn failed_steps(&self) -> Result<(), Box<dyn Error>> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        // The failed tests are listed with the results
+        if let Some(tests) = &self.tests {
+            return Err(format!("{} test(s) failed", tests.failed()).into());
+        }
+        
+        // Every failure at once, for --keep-going
+        let rows: Vec<String> = self.failures.iter()
+            .map(|(index, error)| format!("  row {}: {}", lint::row_number(*index), error))
+            .collect();
+        error!("{} step(s) failed:\n{}", self.failures.len(), rows.join("\n"));
+        Err(format!("{} step(s) failed", self.failures.len()).into())
+    }
+    
+    // Wait out a row's delay and play it, with its retries, and record how it went
+    fn play_row(&mut self, index: usize, record: &MouseAction, total_steps: usize) -> Result<Played, Box<dyn Error>> {
+        self.controls.set_progress(index + 1, total_steps);
+        let budget = watchdog::budget(record, self.motion.duration(), self.controls.delay_scale());
+        let delay = Duration::from_millis(record.delay_ms.unwrap_or(0)).mul_f64(self.controls.delay_scale());
+        self.controls.start_step(delay.saturating_add(budget));
+        self.controls.publish(Event::Step { step: index + 1, total_steps, action: record.action.clone() });
+        info!("Step {} (row {}): {} {}", index + 1, lint::row_number(index), record.action, report::details(record));
+        debug!("{:?}", record);
+        let step_started = Instant::now();
+        
+        // Apply delay if specified
+        self.controls.sleep(delay);
+        
+        // Hold here while paused over the control socket
+        self.controls.wait_while_paused(index + 1);
+        if self.controls.run_aborted() {
+            return Err(format!("Aborted at step {}", index + 1).into());
+        }
+        
+        // Conditions pick the next row from what is on screen
+        if record.action == "if_pixel" {
+            let matches = self.pixel_matches(record).map_err(|e| format!("Step {} (row {}): {}", index + 1, lint::row_number(index), e))?;
+            return Ok(if matches { Played::Done } else { Played::NoMatch });
+        }
+        
+        let started = Instant::now();
+        let mut result = self.attempt(index, record);
+        let mut attempts = 1;
+        let mut wait = Duration::from_millis(record.retry_delay_ms.unwrap_or(DEFAULT_RETRY_DELAY_MS));
+        for retry in 1..=record.retries.unwrap_or(0) {
+            let Err(error) = &result else { break };
+            if self.controls.run_aborted() {
+                break;
+            }
+            warn!("{}; retrying in {} ms ({} of {})", error, wait.as_millis(), retry, record.retries.unwrap_or(0));
+            self.controls.publish(Event::Retry { step: index + 1, attempt: retry + 1, error: error.to_string() });
+            // Each retry gets the step's budget again, after its backoff
+            let backoff = wait.mul_f64(self.controls.delay_scale());
+            self.controls.start_step(backoff.saturating_add(budget));
+            self.controls.sleep(backoff);
+            wait *= 2;
+            result = self.attempt(index, record);
+            attempts += 1;
+        }
+        let duration = started.elapsed();
+        self.controls.publish(Event::StepFinished { step: index + 1, duration_ms: duration.as_millis() as u64, ok: result.is_ok() });
+        if let Some(report) = self.report.as_mut() {
+            report.record(index, duration, result.as_ref().err().map(|e| e.to_string()), self.injector.take_journal());
+        }
+        if let Some(timings) = self.timings.as_mut() {
+            timings.record(index, step_started.elapsed());
+        }
+        if let Some(tests) = self.tests.as_mut() {
+            tests.record(index, duration, result.as_ref().err().map(|e| e.to_string()));
+        }
+        if let Some(log) = self.json_log.as_mut() {
+            log.step(index, record, &self.resolved, step_started.elapsed(), attempts, result.as_ref().err().map(|e| e.to_string()));
+        }
+        if let Err(error) = result {
+            if !(self.keep_going || self.tests.is_some()) || self.controls.run_aborted() {
+                return Err(error);
+            }
+            // Under --test a failed step fails its test, and the next test is played
+            match self.tests.as_ref().filter(|_| !self.keep_going) {
+                Some(tests) => warn!("{}; skipping the rest of test '{}'", error, tests.name(index)),
+                None => warn!("{}; continuing with the next step", error),
+            }
+            self.failures.push((index, error.to_string()));
+            return Ok(Played::Failed);
+        }
+        
+        if self.step && !wait_for_next_step(index, record)? {
+            self.controls.request_abort();
+            return Err(format!("Stopped after step {}", index + 1).into());
+        }
+        Ok(Played::Done)
+    }
+    
+    // Whether the pixel an if_pixel row names has its color, within the tolerance
+    fn pixel_matches(&self, record: &MouseAction) -> Result<bool, Box<dyn Error>> {
+        if self.remote.is_some() {
+            return Err("if_pixel can't be used with --target; the screen it samples is this machine's".into());
+        }
+        let (found, matches) = sample_pixel(record)?;
+        info!("Pixel at ({}, {}) is {}, {} {}", record.x_position.unwrap_or(0), record.y_position.unwrap_or(0), found,
+            if matches { "matching" } else { "not matching" }, record.color.as_deref().unwrap_or(""));
+        Ok(matches)
+    }
+    
+    // One try at a step, here or on the agent
+    fn attempt(&mut self, index: usize, record: &MouseAction) -> Result<(), Box<dyn Error>> {
+        // The watchdog may have given up during the delay or the backoff already
+        if let Some(reason) = self.controls.take_cancelled_step() {
+            return Err(format!("Step {} ({}) {}", index + 1, record.action, reason).into());
+        }
+        self.resolved.clear();
+        let result = match self.remote.as_mut() {
+            Some(remote) => remote.step(index + 1, record),
+            None => self.perform(index, record),
+        };
+        // A stopped step fails even if what it was stuck in returned normally
+        match self.controls.take_cancelled_step() {
+            Some(reason) => Err(format!("Step {} ({}) {}", index + 1, record.action, reason).into()),
+            None => result,
+        }
+    }
+    
+    // Inject one step on this machine, after the lock and user-input checks
+    fn perform(&mut self, index: usize, record: &MouseAction) -> Result<(), Box<dyn Error>> {
+        // Don't inject into a lock screen
+        self.lock_watch.check(index + 1, &self.controls)?;
+        
+        // Nor into an application the guard conditions rule out
+        if let Some(guard) = self.foreground_guard.as_mut() {
+            guard.check(index + 1, &self.controls)?;
+        }
+        
+        // Pause if the user moved the mouse since our last action
+        if let Some(watch) = self.user_input_watch.as_mut() {
+            watch.check(self.injector.location(), index + 1);
+        }
+        
+        self.injector.set_row(lint::row_number(index));
+        if let Err(error) = self.execute(record) {
+            let mut message = format!("Step {} ({}) failed: {}", index + 1, record.action, error);
+            // Evidence of what the screen looked like, for failures on unattended machines
+            if let Some(path) = self.failure_dir.as_deref().and_then(|dir| screen::failure_screenshot(dir, index + 1, lint::row_number(index))) {
+                message.push_str(&format!(" (screenshot: {})", path.display()));
+                if let Some(report) = self.report.as_mut() {
+                    report.attach_screenshot(path);
+                }
+            }
+            return Err(message.into());
+        }
+        
+        if let Some(watch) = self.user_input_watch.as_mut() {
+            watch.record(self.injector.location());
+        }
+        Ok(())
+    }
+    
+    // Execute one row, honoring its repeat count
+    fn execute(&mut self, record: &MouseAction) -> Result<(), Box<dyn Error>> {
+        let injector = &mut self.injector;
+        let bounds = &self.bounds;
+        let controls = &self.controls;
+        let motion = &mut self.motion;
+        let monitors = &mut self.monitors;
+        let logical_pixels = self.logical_pixels;
+        let resolved = &mut self.resolved;
+        
+        // A relative move is scaled like a position, but not placed on a monitor
+        let offset = match record.x_position.zip(record.y_position) {
+            Some((x, y)) if logical_pixels && record.action == "move_relative" => Some(monitors.physical(record.monitor, x, y)?),
+            offset => offset,
+        };
+        
+        // Positions in a window or on a given monitor are checked against it and moved onto the desktop
+        let mut resolve = |x: i32, y: i32| -> Result<(i32, i32), String> {
+            let (x, y) = if logical_pixels { monitors.physical(record.monitor, x, y)? } else { (x, y) };
+            let point = match (record.window.as_deref(), record.monitor) {
+                (Some(title), _) => window::resolve(title, x, y, bounds.policy()),
+                (None, Some(monitor)) => monitors.resolve(monitor, x, y, bounds.policy()),
+                (None, None) => bounds.resolve(x, y),
+            }?;
+            resolved.push(point);
+            Ok(point)
+        };
+        
+        // Keys held while a click or drag presses, from the modifiers column
+        let modifiers = match record.modifiers.as_deref() {
+            Some(spec) if keyboard::takes_modifiers(&record.action) => keyboard::parse_modifiers(spec)?,
+            _ => Vec::new(),
+        };
+        
+        // Get repeat count (default to 1)
+        let repeat_count = record.repeat_count.unwrap_or(1);
+        
+        // Execute the action the specified number of times
+        for _ in 0..repeat_count {
+            if !modifiers.is_empty() {
+                debug!("Holding {}", record.modifiers.as_deref().unwrap_or(""));
+            }
+            for &key in modifiers.iter().filter(|key| !self.drag_modifiers.contains(key)) {
+                injector.key_down(key);
+            }
+            
+            // In a closure so the modifiers are let go of below however the row ends
+            let played = (|| -> Result<(), Box<dyn Error>> {
+                match record.action.as_str() {
+                    "move" => {
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Moving to position: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), record.duration_ms);
+                        }
+                    },
+                    "move_relative" => {
+                        if let Some((x, y)) = offset {
+                            if !injector.knows_cursor() {
+                                return Err("the backend can't see the cursor, so move_relative needs a move to a position before it".into());
+                            }
+                            // Check where the move would land, keeping it relative if allowed
+                            let (current_x, current_y) = injector.location();
+                            let (target_x, target_y) = bounds.resolve(current_x + x, current_y + y)?;
+                            let (x, y) = (target_x - current_x, target_y - current_y);
+                            debug!("Moving relatively by: ({}, {})", x, y);
+                            injector.move_relative(x, y);
+                        }
+                    },
+                    "click" => {
+                        // First move to position if specified
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Moving to position: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), record.duration_ms);
+                        }
+                        
+                        // Then click with specified button (default to left)
+                        let button = record.button.as_deref().and_then(parse_button).unwrap_or(MouseButton::Left);
+                        
+                        debug!("Clicking with {:?} button", button);
+                        motion.click(injector, controls, button);
+                    },
+                    "double_click" | "triple_click" => {
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Moving to position: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), record.duration_ms);
+                        }
+                        
+                        let button = record.button.as_deref().and_then(parse_button).unwrap_or(MouseButton::Left);
+                        let (count, verb) = if record.action == "triple_click" { (3, "Triple") } else { (2, "Double") };
+                        
+                        debug!("{}-clicking with {:?} button", verb, button);
+                        motion.multi_click(injector, controls, button, count, record.interval_ms);
+                    },
+                    "save_position" => {
+                        let name = record.name.as_deref().filter(|name| !name.is_empty()).ok_or("save_position needs a name")?;
+                        if !injector.knows_cursor() {
+                            return Err("the backend can't see the cursor, so save_position needs a move to a position before it".into());
+                        }
+                        let (x, y) = injector.location();
+                        debug!("Saving position '{}': ({}, {})", name, x, y);
+                        self.positions.insert(name.to_string(), (x, y));
+                    },
+                    "restore_position" => {
+                        let name = record.name.as_deref().filter(|name| !name.is_empty()).ok_or("restore_position needs a name")?;
+                        let &(x, y) = self.positions.get(name).ok_or_else(|| format!("no position was saved as '{}'", name))?;
+                        debug!("Restoring position '{}': ({}, {})", name, x, y);
+                        motion.move_to(injector, controls, (x, y), record.duration_ms);
+                    },
+                    "drag_to" | "draw_circle" | "draw_rectangle" | "draw_polyline" => {
+                        // Curves and shapes are worked out once their defining points are on the desktop
+                        let points = stroke::outline(record, &mut resolve)?;
+                        let button = record.button.as_deref().and_then(parse_button).unwrap_or(MouseButton::Left);
+                        
+                        let (from, to) = (points[0], points[points.len() - 1]);
+                        match record.action.as_str() {
+                            "drag_to" => debug!("Dragging from ({}, {}) to ({}, {})", from.0, from.1, to.0, to.1),
+                            action => debug!("Drawing {} from ({}, {})", action.trim_start_matches("draw_"), from.0, from.1),
+                        }
+                        motion.drag(injector, controls, &points, button, stroke::duration_ms(record));
+                    },
+                    "click_and_hold" => {
+                        // duration_ms is the hold here, so the move takes --move-duration
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Moving to position: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), None);
+                        }
+                        
+                        let button = record.button.as_deref().and_then(parse_button).unwrap_or(MouseButton::Left);
+                        let hold = record.duration_ms.unwrap_or(0);
+                        
+                        // Not scaled by --speed: long presses are told apart from clicks by how long they are
+                        debug!("Holding {:?} button for {} ms", button, hold);
+                        injector.button_down(button);
+                        controls.sleep(Duration::from_millis(hold));
+                        injector.button_up(button);
+                    },
+                    "right_click" => {
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Moving to position: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), record.duration_ms);
+                        }
+                        debug!("Right-clicking");
+                        motion.click(injector, controls, MouseButton::Right);
+                    },
+                    "drag" => {
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Starting drag at: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), record.duration_ms);
+                            injector.button_down(MouseButton::Left);
+                        }
+                    },
+                    "release" => {
+                        if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+                            let (x, y) = resolve(x, y)?;
+                            debug!("Releasing at: ({}, {})", x, y);
+                            motion.move_to(injector, controls, (x, y), record.duration_ms);
+                        }
+                        debug!("Releasing mouse button");
+                        injector.button_up(MouseButton::Left);
+                    },
+                    "scroll" => {
+                        let (dx, dy) = scroll_direction(record);
+                        let amount = repeat_count as i32;
+                        let mut scroll = |units: i32| if dx != 0 { injector.scroll_x(dx * units) } else { injector.scroll_y(dy * units) };
+                        
+                        // With a duration, one notch at a time for applications that only render while
+                        // scrolling, with every repeat's notches spread over the row's duration
+                        match record.duration_ms.filter(|&duration| duration > 0 && amount > 1) {
+                            Some(duration) => {
+                                debug!("Scrolling {} by {} units over {} ms", record.modifiers.as_deref().unwrap_or("up"), amount,
+                                    duration / u64::from(repeat_count));
+                                // repeat_count² notches in all; squared as a float, which can't overflow
+                                let gap = Duration::from_millis(duration).mul_f64(controls.delay_scale()).div_f64(f64::from(repeat_count).powi(2));
+                                for _ in 0..amount {
+                                    if controls.abort_requested() {
+                                        break;
+                                    }
+                                    scroll(1);
+                                    controls.sleep(gap);
+                                }
+                            },
+                            None => {
+                                debug!("Scrolling {} by {} units", record.modifiers.as_deref().unwrap_or("up"), amount);
+                                scroll(amount);
+                            },
+                        }
+                    },
+                    "wait" => {
+                        debug!("Waiting...");
+                        // Already handled by the delay logic
+                    },
+                    "set_var" => {
+                        // Already applied to the rows after it when the script was loaded
+                    },
+                    "if_pixel" => {
+                        // A condition only decides which row plays next
+                    },
+                    "type_text" | "snippet" => {
+                        let text = record.text.as_deref().ok_or_else(|| format!("{} needs text", record.action))?;
+                        debug!("Typing {} character(s)", text.chars().count());
+                        type_text(injector, &self.controls, text, record.interval_ms.unwrap_or(0))?;
+                    },
+                    "key_press" => {
+                        let keys = keyboard::parse_chord(record.key.as_deref().unwrap_or(""))?;
+                        debug!("Pressing {}", record.key.as_deref().unwrap_or(""));
+                        injector.chord(&keys);
+                    },
+                    "assert_region_matches" => {
+                        let (Some(x), Some(y), Some(width), Some(height)) = (record.x_position, record.y_position, record.width, record.height) else {
+                            return Err("assert_region_matches needs x_position, y_position, width and height".into());
+                        };
+                        let image = record.image.as_deref().ok_or("assert_region_matches needs a baseline image")?;
+                        debug!("Comparing {}x{} at ({}, {}) with {}", width, height, x, y, image);
+                        baseline::check(Region { x, y, width, height }, Path::new(image), record.tolerance.unwrap_or(0), self.update_baselines)?;
+                    },
+                    "assert_pixel" => {
+                        let (found, matches) = sample_pixel(record)?;
+                        debug!("Pixel at ({}, {}) is {}", record.x_position.unwrap_or(0), record.y_position.unwrap_or(0), found);
+                        if !matches {
+                            return Err(format!("the pixel at ({}, {}) is {}, expected {} (tolerance {})", record.x_position.unwrap_or(0),
+                                record.y_position.unwrap_or(0), found, record.color.as_deref().unwrap_or(""), record.tolerance.unwrap_or(0)).into());
+                        }
+                    },
+                    "assert_cursor_position" => {
+                        let (Some(x), Some(y)) = (record.x_position, record.y_position) else {
+                            return Err("assert_cursor_position needs x_position and y_position".into());
+                        };
+                        if !injector.reads_cursor() {
+                            return Err("the backend can't see the cursor, so it can't be asserted; use --backend enigo".into());
+                        }
+                        let (x, y) = resolve(x, y)?;
+                        let (current_x, current_y) = injector.location();
+                        let tolerance = i32::from(record.tolerance.unwrap_or(0));
+                        debug!("Cursor is at ({}, {})", current_x, current_y);
+                        if (current_x - x).abs() > tolerance || (current_y - y).abs() > tolerance {
+                            return Err(format!("the cursor is at ({}, {}), expected ({}, {})", current_x, current_y, x, y).into());
+                        }
+                    },
+                    "assert_window_exists" => {
+                        let title = record.window.as_deref().ok_or("assert_window_exists needs a window title")?;
+                        let found = window::find_within(title, record.timeout_ms.map(Duration::from_millis), controls)?;
+                        debug!("Found window '{}'", found.title);
+                    },
+                    "screenshot" => {
+                        let region = match (record.x_position, record.y_position, record.width, record.height) {
+                            (Some(x), Some(y), Some(width), Some(height)) => Some(Region { x, y, width, height }),
+                            (None, None, None, None) => None,
+                            _ => return Err("screenshot needs x_position, y_position, width and height for a region, or none of them for the whole screen".into()),
+                        };
+                        let path = Path::new(record.image.as_deref().unwrap_or(screen::DEFAULT_SCREENSHOT));
+                        let saved = screen::screenshot(path, region)?;
+                        debug!("Saved screenshot {}", saved.display());
+                    },
+                    "wait_for_image" => {
+                        let image = record.image.as_deref().ok_or("wait_for_image needs an image")?;
+                        let timeout = record.timeout_ms.map_or(locate::DEFAULT_TIMEOUT, Duration::from_millis);
+                        debug!("Waiting up to {} ms for {}", timeout.as_millis(), image);
+                        let (x, y) = locate::wait_for(Path::new(image), record.tolerance.unwrap_or(0), timeout, &self.controls)?;
+                        debug!("Found {} at ({}, {})", image, x, y);
+                    },
+                    "focus_window" => {
+                        let title = record.window.as_deref().ok_or("focus_window needs a window title")?;
+                        window::focus(title, record.text.as_deref(), record.timeout_ms.map(Duration::from_millis), controls)?;
+                    },
+                    "wait_for_window" => {
+                        let title = record.window.as_deref().ok_or("wait_for_window needs a window title")?;
+                        let timeout = record.timeout_ms.map_or(window::DEFAULT_TIMEOUT, Duration::from_millis);
+                        debug!("Waiting up to {} ms for a window matching '{}'", timeout.as_millis(), title);
+                        let found = window::find_within(title, Some(timeout), controls)?;
+                        debug!("Found window '{}'", found.title);
+                    },
+                    "move_window" | "resize_window" | "maximize_window" | "close_window" => {
+                        window::arrange(record, controls)?;
+                    },
+                    "key_down" | "key_up" => {
+                        let name = record.key.as_deref().unwrap_or("");
+                        let key = keyboard::parse_key(name).ok_or_else(|| format!("unknown key '{}'", name))?;
+                        if record.action == "key_down" {
+                            debug!("Holding {}", name);
+                            injector.key_down(key);
+                        } else {
+                            debug!("Releasing {}", name);
+                            injector.key_up(key);
+                        }
+                    },
+                    _ => {
+                        warn!("Unknown action: {}", record.action);
+                    }
+                }
+
+                Ok(())
+            })();
+            
+            // A drag keeps its modifiers down until the release, so ctrl+drag copies on drop
+            let mut pressed = if record.action == "release" { std::mem::take(&mut self.drag_modifiers) } else { Vec::new() };
+            for &key in &modifiers {
+                if !pressed.contains(&key) && !self.drag_modifiers.contains(&key) {
+                    pressed.push(key);
+                }
+            }
+            if record.action == "drag" && played.is_ok() {
+                self.drag_modifiers.append(&mut pressed);
+            } else {
+                for &key in pressed.iter().rev() {
+                    injector.key_up(key);
+                }
+            }
+            played?;
+        }
+        Ok(())
+    }
+}
+
+// The color of the pixel an if_pixel or assert_pixel row names, as #rrggbb,
+// and whether it's the row's color within the tolerance
+fn sample_pixel(record: &MouseAction) -> Result<(String, bool), Box<dyn Error>> {
+    let (Some(x), Some(y)) = (record.x_position, record.y_position) else {
+        return Err(format!("{} needs x_position and y_position", record.action).into());
+    };
+    let color = record.color.as_deref().unwrap_or("");
+    let expected = screen::parse_color(color).ok_or_else(|| format!("{} needs a color like #1e90ff, got '{}'", record.action, color))?;
+    let found = screen::pixel(x, y)?;
+    let tolerance = record.tolerance.unwrap_or(0);
+    let matches = expected.iter().zip(found).all(|(&wanted, got)| wanted.abs_diff(got) <= tolerance);
+    Ok((format!("#{:02x}{:02x}{:02x}", found[0], found[1], found[2]), matches))
+}
+
+// One wheel notch of a scroll row, from its direction in the modifiers column:
+// up (the default), down, left or right
+fn scroll_direction(record: &MouseAction) -> (i32, i32) {
+    match record.modifiers.as_deref() {
+        Some("down") => (0, -1),
+        Some("left") => (-1, 0),
+        Some("right") => (1, 0),
+        _ => (0, 1),
+    }
+}
+
+// "left", "right", "middle", "back" or "forward", or an X11 button number:
+// 1 left, 2 middle, 3 right, 4-7 the scroll wheel, 8 back and 9 forward
+fn parse_button(name: &str) -> Option<MouseButton> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "left" | "1" => Some(MouseButton::Left),
+        "middle" | "2" => Some(MouseButton::Middle),
+        "right" | "3" => Some(MouseButton::Right),
+        "4" => Some(MouseButton::ScrollUp),
+        "5" => Some(MouseButton::ScrollDown),
+        "6" => Some(MouseButton::ScrollLeft),
+        "7" => Some(MouseButton::ScrollRight),
+        "back" | "8" => Some(MouseButton::Back),
+        "forward" | "9" => Some(MouseButton::Forward),
+        _ => None,
+    }
+}
+
+// "250ms", "30s", "10m" or "2h"; a bare number is milliseconds
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit.trim() {
+        "" | "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// Type text line by line, pressing Enter between lines, optionally one character at a time
+fn type_text(injector: &mut Injector, controls: &Controls, text: &str, interval_ms: u64) -> Result<(), String> {
+    let pause = Duration::from_millis(interval_ms).mul_f64(controls.delay_scale());
+    let mut buffer = [0u8; 4];
+    for (number, line) in text.split('\n').enumerate() {
+        if number > 0 {
+            injector.chord(&[Key::Return]);
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if interval_ms == 0 {
+            injector.type_text(line)?;
+            continue;
+        }
+        for c in line.chars() {
+            if controls.abort_requested() {
+                return Ok(());
+            }
+            injector.type_text(c.encode_utf8(&mut buffer))?;
+            controls.sleep(pause);
+        }
+    }
+    Ok(())
+}
+
+// Read every row of a script before anything runs
+fn load_script(path: &str, vars: &Vars) -> Result<Vec<MouseAction>, Box<dyn Error>> {
+    if binary::is_binary(Path::new(path)) {
+        return Ok(binary::read(Path::new(path))?.collect::<Result<_, _>>()?);
+    }
+    
+    // Templates are rendered to CSV first
+    let text = if template::is_template(Path::new(path)) {
+        template::render(Path::new(path), vars)?
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?
+    };
+    if yaml::is_yaml(Path::new(path)) {
+        return yaml::parse(&text, path, vars);
+    }
+    parse_script(&text, path, vars)
+}
+
+// Parse CSV script text; `source` names it in error messages
+fn parse_script(text: &str, source: &str, vars: &Vars) -> Result<Vec<MouseAction>, Box<dyn Error>> {
+    let mut reader = Reader::from_reader(text.as_bytes());
+    let headers = reader.headers()?.clone();
+    // set_var rows add to these for the rows after them
+    let mut vars = vars.clone();
+    // Measured if a position is given as a percentage
+    let mut screen = Layout::default();
+    
+    // Collect every bad row so they can all be fixed in one go
+    let mut actions = Vec::new();
+    let mut problems = Vec::new();
+    for result in reader.records() {
+        let row = match result {
+            Ok(row) => row,
+            Err(error) => {
+                problems.push(describe_csv_error(&error));
+                continue;
+            },
+        };
+        let substituted = vars::substitute_record(&row, &vars).and_then(|row| vars::evaluate_record(&row, &headers, &mut screen));
+        let row = match substituted {
+            Ok(row) => row,
+            Err(error) => {
+                problems.push(format!("row {}: {}", row.position().map_or(0, |pos| pos.line()), error));
+                continue;
+            },
+        };
+        match row.deserialize::<MouseAction>(Some(&headers)) {
+            Ok(record) => {
+                set_var(&record, &mut vars);
+                actions.push(record);
+            },
+            Err(error) => problems.push(describe_parse_error(&error, &headers, &row)),
+        }
+    }
+    
+    if problems.is_empty() {
+        Ok(actions)
+    } else {
+        for problem in &problems {
+            error!("{}: {}", source, problem);
+        }
+        Err(format!("{} has {} invalid row(s)", source, problems.len()).into())
+    }
+}
+
+// Variables are set as the script is loaded, so they apply in file order
+fn set_var(record: &MouseAction, vars: &mut Vars) {
+    if let ("set_var", Some(name)) = (record.action.as_str(), record.name.as_deref()) {
+        vars.insert(name.to_string(), record.text.clone().unwrap_or_default());
+    }
+}
+
+// Explain a malformed CSV row, e.g. one with the wrong number of fields
+fn describe_csv_error(error: &csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::UnequalLengths { pos, expected_len, len } => {
+            let line = pos.as_ref().map_or(0, |pos| pos.line());
+            format!("row {}: expected {} fields like the header, found {}", line, expected_len, len)
+        },
+        _ => error.to_string(),
+    }
+}
+
+// Point at the row, column and value that failed to parse
+fn describe_parse_error(error: &csv::Error, headers: &StringRecord, row: &StringRecord) -> String {
+    let line = row.position().map_or(0, |pos| pos.line());
+    let csv::ErrorKind::Deserialize { err, .. } = error.kind() else {
+        return format!("row {}: {}", line, error);
+    };
+    
+    match err.field() {
+        Some(field) => {
+            let column = headers.get(field as usize).unwrap_or("?");
+            let value = row.get(field as usize).unwrap_or("");
+            format!("row {}: {} {}, got '{}'", line, column, column_hint(column), value)
+        },
+        None => format!("row {}: {}", line, err),
+    }
+}
+
+// What a valid value looks like for each column
+fn column_hint(column: &str) -> &'static str {
+    match column {
+        "x_position" | "y_position" => "must be a whole number of pixels",
+        "delay_ms" | "interval_ms" | "retry_delay_ms" => "must be a whole number of milliseconds",
+        "repeat_count" | "retries" => "must be a whole number of at least 0",
+        "width" | "height" => "must be a whole number of pixels of at least 0",
+        "tolerance" => "must be a whole number from 0 to 255",
+        _ => "has an invalid value",
+    }
+}
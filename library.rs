@@ -0,0 +1,277 @@
+// The per-user script library, so scripts can be run by name.
+//
+//   Linux    $XDG_DATA_HOME/mouse-automation/scripts (~/.local/share/...)
+//   macOS    ~/Library/Application Support/mouse-automation/scripts
+//   Windows  %APPDATA%\mouse-automation\scripts
+//
+// `run daily-report` finds daily-report.csv there when no such file exists
+// in the current directory. The `scripts` subcommand manages the library;
+// descriptions and the last run of each script are kept in library.json
+// next to the scripts directory.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::signing;
+
+const APP_DIR: &str = "mouse-automation";
+const USAGE: &str = "Usage: mouse-automation scripts <list|add <file> [--name <name>] [--description <text>]|remove <name>|show <name>>";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Entry {
+    description: Option<String>,
+    // Unix seconds
+    added: Option<u64>,
+    last_run: Option<u64>,
+    // "ok" or the error that ended the run
+    last_result: Option<String>,
+}
+
+type Index = BTreeMap<String, Entry>;
+
+#[cfg(windows)]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+}
+
+// None when the platform's home or data directory isn't set
+pub fn library_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(APP_DIR).join("scripts"))
+}
+
+// A path as given, else a script of that name in the library
+pub fn resolve(requested: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = Path::new(requested);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    // Only bare names are looked up; a path that doesn't exist is just missing
+    let library = library_dir();
+    if let Some(dir) = library.as_ref().filter(|_| path.components().count() == 1) {
+        let candidates = ["", ".csv", ".yaml", ".yml", ".mab"].map(|extension| dir.join(format!("{}{}", requested, extension)));
+        if let Some(found) = candidates.into_iter().find(|candidate| candidate.is_file()) {
+            return Ok(found);
+        }
+    }
+
+    let searched = match library {
+        Some(dir) => format!(" (also looked in {})", dir.display()),
+        None => String::new(),
+    };
+    Err(format!("Script '{}' not found{}", requested, searched).into())
+}
+
+// The `scripts` subcommand
+pub fn manage(command: &str, argument: Option<&str>, name: Option<&str>, description: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let dir = library_dir().ok_or("No script library: the home directory is unknown")?;
+    match command {
+        "list" => list(&dir),
+        "add" => add(&dir, argument.ok_or(USAGE)?, name, description),
+        "remove" => remove(&dir, argument.ok_or(USAGE)?),
+        "show" => show(&dir, argument.ok_or(USAGE)?),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn list(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let index = load_index(dir)?;
+    let names = script_names(dir)?;
+    if names.is_empty() {
+        println!("No scripts in {}; add one with `scripts add <file>`", dir.display());
+        return Ok(());
+    }
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    for name in &names {
+        let entry = index.get(name);
+        let description = entry.and_then(|entry| entry.description.as_deref()).unwrap_or("");
+        println!("{:width$}  {:<12}  {}", name, last_run(entry), description, width = width);
+    }
+    Ok(())
+}
+
+fn add(dir: &Path, file: &str, name: Option<&str>, description: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let source = Path::new(file);
+    if !source.is_file() {
+        return Err(format!("'{}' is not a file", file).into());
+    }
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => source.file_stem().map(|stem| stem.to_string_lossy().into_owned()).ok_or("Can't name the script; use --name")?,
+    };
+    let target = script_path(dir, &name)?;
+    if target.exists() {
+        return Err(format!("A script named '{}' already exists; remove it first", name).into());
+    }
+
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    fs::copy(source, &target)?;
+    // Keep the signature with the script so --require-signature still passes
+    let signature = signing::signature_path(file);
+    if Path::new(&signature).is_file() {
+        fs::copy(&signature, signing::signature_path(&target.to_string_lossy()))?;
+    }
+
+    let mut index = load_index(dir)?;
+    index.insert(name.clone(), Entry {
+        description: description.map(str::to_string),
+        added: Some(now()),
+        ..Entry::default()
+    });
+    save_index(dir, &index)?;
+    println!("Added {} as '{}'", file, name);
+    Ok(())
+}
+
+fn remove(dir: &Path, name: &str) -> Result<(), Box<dyn Error>> {
+    let path = script_path(dir, name)?;
+    if !path.is_file() {
+        return Err(format!("No script named '{}' in {}", name, dir.display()).into());
+    }
+    fs::remove_file(&path)?;
+    let signature = signing::signature_path(&path.to_string_lossy());
+    if Path::new(&signature).is_file() {
+        fs::remove_file(&signature)?;
+    }
+
+    let mut index = load_index(dir)?;
+    if index.remove(name).is_some() {
+        save_index(dir, &index)?;
+    }
+    println!("Removed '{}'", name);
+    Ok(())
+}
+
+fn show(dir: &Path, name: &str) -> Result<(), Box<dyn Error>> {
+    let path = script_path(dir, name)?;
+    let content = fs::read_to_string(&path).map_err(|_| format!("No script named '{}' in {}", name, dir.display()))?;
+    let index = load_index(dir)?;
+    let entry = index.get(name);
+    println!("Name: {}", name);
+    println!("Path: {}", path.display());
+    if let Some(description) = entry.and_then(|entry| entry.description.as_deref()) {
+        println!("Description: {}", description);
+    }
+    if let Some(added) = entry.and_then(|entry| entry.added) {
+        println!("Added: {}", ago(added));
+    }
+    println!("Last run: {}", last_run(entry));
+    if let Some(error) = entry.and_then(|entry| entry.last_result.as_deref()).filter(|result| *result != "ok") {
+        println!("Last error: {}", error);
+    }
+    println!();
+    print!("{}", content);
+    Ok(())
+}
+
+// Note the outcome of a run if the script came from the library
+pub fn record_run(script: &Path, result: Result<(), String>) {
+    let Some(dir) = library_dir() else { return };
+    let in_library = match (script.parent().and_then(|parent| parent.canonicalize().ok()), dir.canonicalize()) {
+        (Some(parent), Ok(dir)) => parent == dir,
+        _ => false,
+    };
+    let Some(name) = script.file_stem().map(|stem| stem.to_string_lossy().into_owned()).filter(|_| in_library) else {
+        return;
+    };
+
+    let updated = load_index(&dir).and_then(|mut index| {
+        let entry = index.entry(name).or_default();
+        entry.last_run = Some(now());
+        entry.last_result = Some(result.err().unwrap_or_else(|| "ok".to_string()));
+        save_index(&dir, &index)
+    });
+    if let Err(error) = updated {
+        warn!("failed to update the script library: {}", error);
+    }
+}
+
+fn script_path(dir: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let name = name.strip_suffix(".csv").unwrap_or(name);
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!("Invalid script name '{}'; use letters, digits, '-', '_' and '.'", name).into());
+    }
+    Ok(dir.join(format!("{}.csv", name)))
+}
+
+fn script_names(dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "csv") {
+            if let Some(stem) = path.file_stem() {
+                names.push(stem.to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn index_path(dir: &Path) -> PathBuf {
+    dir.with_file_name("library.json")
+}
+
+fn load_index(dir: &Path) -> Result<Index, Box<dyn Error>> {
+    let path = index_path(dir);
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(serde_json::from_str(&text).map_err(|e| format!("Invalid '{}': {}", path.display(), e))?),
+        Err(_) => Ok(Index::new()),
+    }
+}
+
+fn save_index(dir: &Path, index: &Index) -> Result<(), Box<dyn Error>> {
+    let path = index_path(dir);
+    fs::create_dir_all(dir)?;
+    fs::write(&path, serde_json::to_string_pretty(index)?)
+        .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn last_run(entry: Option<&Entry>) -> String {
+    match entry.and_then(|entry| entry.last_run.zip(entry.last_result.as_deref())) {
+        Some((at, "ok")) => ago(at),
+        Some((at, _)) => format!("{} (failed)", ago(at)),
+        None => "never run".to_string(),
+    }
+}
+
+// "5m ago", "3h ago", "2d ago"
+fn ago(at: u64) -> String {
+    let seconds = now().saturating_sub(at);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
@@ -0,0 +1,404 @@
+// Static checks for common scripting mistakes.
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::{flow, keyboard, screen, stroke, window};
+use crate::MouseAction;
+
+// Repeats run back-to-back, so anything above this is effectively a tight loop,
+// for click repeats, loop passes and goto jumps alike
+const TIGHT_LOOP_REPEATS: u32 = 10;
+
+const KNOWN_ACTIONS: &[&str] = &[
+    "move", "move_relative", "click", "double_click", "triple_click", "click_and_hold", "right_click",
+    "drag", "release", "drag_to", "draw_circle", "draw_rectangle", "draw_polyline", "scroll", "wait", "checkpoint",
+    "type_text", "snippet", "key_press", "key_down", "key_up",
+    "assert_region_matches", "loop_start", "loop_end", "label", "goto",
+    "set_var", "if_pixel", "wait_for_image", "screenshot", "focus_window", "wait_for_window", "save_position", "restore_position",
+    "move_window", "resize_window", "maximize_window", "close_window",
+    "assert_pixel", "assert_cursor_position", "assert_window_exists",
+];
+
+// Actions whose coordinates are a pointer position, which a monitor column translates
+const POINTER_ACTIONS: &[&str] = &[
+    "move", "click", "double_click", "triple_click", "click_and_hold", "right_click", "drag", "release", "drag_to",
+    "draw_circle", "draw_rectangle", "draw_polyline", "assert_cursor_position",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    // Line in the CSV file; the header is row 1
+    pub row: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}: {}: {}", self.row, self.severity, self.message)
+    }
+}
+
+// CSV line number of the action at `index`
+pub fn row_number(index: usize) -> usize {
+    index + 2
+}
+
+// Check a whole script; `screen` is the display size when known
+pub fn lint(actions: &[MouseAction], screen: Option<(i32, i32)>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut push = |index: usize, severity: Severity, message: String| {
+        findings.push(Finding { row: row_number(index), severity, message });
+    };
+
+    let mut open_drag: Option<usize> = None;
+    // Keys held by key_down, with the row that pressed them
+    let mut held_keys: Vec<(String, usize)> = Vec::new();
+    let mut cursor_positioned = false;
+    // Rows of the loop_starts not yet closed
+    let mut open_loops: Vec<usize> = Vec::new();
+    // Names of the positions save_position rows have taken so far
+    let mut saved_positions: Vec<&str> = Vec::new();
+    // Labels can be jumped to from above or below, so they are collected first
+    let labels: Vec<&str> = actions.iter()
+        .filter(|record| record.action == "label")
+        .filter_map(|record| record.name.as_deref())
+        .collect();
+
+    for (index, record) in actions.iter().enumerate() {
+        let action = record.action.as_str();
+        let coordinates = record.x_position.zip(record.y_position);
+
+        if !KNOWN_ACTIONS.contains(&action) {
+            push(index, Severity::Error, format!("unknown action '{}'", action));
+            continue;
+        }
+
+        match action {
+            "drag" => {
+                if let Some(start) = open_drag {
+                    push(index, Severity::Error, format!("drag while the drag from row {} is still held", row_number(start)));
+                }
+                if coordinates.is_none() {
+                    push(index, Severity::Error, "drag needs x_position and y_position".to_string());
+                }
+                open_drag = Some(index);
+            },
+            "checkpoint" | "snippet" | "label" | "goto" | "set_var" | "save_position" | "restore_position" if record.name.as_deref().is_none_or(str::is_empty) => {
+                push(index, Severity::Error, format!("{} needs a name", action));
+            },
+            "assert_region_matches" => {
+                let has_region = record.x_position.is_some() && record.y_position.is_some()
+                    && record.width.is_some_and(|width| width > 0) && record.height.is_some_and(|height| height > 0);
+                if !has_region {
+                    push(index, Severity::Error, "assert_region_matches needs x_position, y_position, width and height".to_string());
+                }
+                if record.image.as_deref().is_none_or(str::is_empty) {
+                    push(index, Severity::Error, "assert_region_matches needs a baseline image".to_string());
+                }
+            },
+            "screenshot" => {
+                let region = [record.x_position.is_some(), record.y_position.is_some(), record.width.is_some(), record.height.is_some()];
+                if region.contains(&true) && region.contains(&false) {
+                    push(index, Severity::Error, "screenshot needs x_position, y_position, width and height for a region, or none of them".to_string());
+                }
+            },
+            _ if window::is_window_action(action) && record.window.as_deref().is_none_or(str::is_empty) => {
+                push(index, Severity::Error, format!("{} needs a window title", action));
+            },
+            "move_window" | "resize_window" => {
+                if let Err(error) = window::change(record) {
+                    push(index, Severity::Error, error);
+                }
+            },
+            "wait_for_image" if record.image.as_deref().is_none_or(str::is_empty) => {
+                push(index, Severity::Error, "wait_for_image needs an image".to_string());
+            },
+            "type_text" if record.text.as_deref().is_none_or(str::is_empty) => {
+                push(index, Severity::Error, "type_text needs text".to_string());
+            },
+            "key_press" => {
+                if let Err(error) = keyboard::parse_chord(record.key.as_deref().unwrap_or("")) {
+                    push(index, Severity::Error, format!("key_press: {}", error));
+                }
+            },
+            "restore_position" => {
+                let name = record.name.as_deref().unwrap_or("");
+                if !saved_positions.contains(&name) {
+                    push(index, Severity::Warning, format!("restore_position '{}' comes before any save_position of that name; it fails unless a jump saves one first", name));
+                }
+                cursor_positioned = true;
+            },
+            "save_position" => saved_positions.extend(record.name.as_deref()),
+            "key_down" | "key_up" => {
+                let name = record.key.as_deref().unwrap_or("");
+                if keyboard::parse_key(name).is_none() {
+                    push(index, Severity::Error, format!("{} needs a single key, got '{}'", action, name));
+                } else if action == "key_down" {
+                    held_keys.push((name.to_ascii_lowercase(), index));
+                } else if let Some(position) = held_keys.iter().position(|(held, _)| held.eq_ignore_ascii_case(name)) {
+                    held_keys.remove(position);
+                } else {
+                    push(index, Severity::Warning, format!("key_up '{}' without a preceding key_down", name));
+                }
+            },
+            "label" if labels.iter().filter(|&&name| Some(name) == record.name.as_deref()).count() > 1 => {
+                push(index, Severity::Error, format!("label '{}' is defined more than once", record.name.as_deref().unwrap_or("")));
+            },
+            "goto" | "if_pixel" if flow::jumps_to_label(record) && !labels.contains(&record.name.as_deref().unwrap_or("")) => {
+                push(index, Severity::Error, format!("{} to unknown label '{}'", action, record.name.as_deref().unwrap_or("")));
+            },
+            "if_pixel" | "assert_pixel" | "assert_cursor_position" if coordinates.is_none() => {
+                push(index, Severity::Error, format!("{} needs x_position and y_position", action));
+            },
+            "if_pixel" | "assert_pixel" if screen::parse_color(record.color.as_deref().unwrap_or("")).is_none() => {
+                push(index, Severity::Error, format!("{} needs a color like #1e90ff, got '{}'", action, record.color.as_deref().unwrap_or("")));
+            },
+            "move" | "move_relative" if coordinates.is_none() => {
+                push(index, Severity::Error, format!("{} needs x_position and y_position", action));
+            },
+            "drag_to" | "draw_circle" | "draw_rectangle" | "draw_polyline" => {
+                if let Err(error) = stroke::outline(record, |x, y| Ok((x, y))) {
+                    push(index, Severity::Error, error);
+                }
+            },
+
+            "scroll" if !matches!(record.modifiers.as_deref(), None | Some("up" | "down" | "left" | "right")) => {
+                push(index, Severity::Error, format!("scroll direction (modifiers) must be up, down, left or right, got '{}'", record.modifiers.as_deref().unwrap_or("")));
+            },
+            "loop_start" => open_loops.push(index),
+            "loop_end" if open_loops.pop().is_none() => {
+                push(index, Severity::Error, "loop_end without a loop_start".to_string());
+            },
+            "release" => match open_drag.take() {
+                Some(_) => {},
+                None => push(index, Severity::Warning, "release without a preceding drag".to_string()),
+            },
+            "click" | "double_click" | "triple_click" | "click_and_hold" | "right_click" => {
+                if coordinates.is_none() && !cursor_positioned {
+                    push(index, Severity::Warning, format!("{} has no coordinates and no earlier move; it clicks wherever the cursor is", action));
+                }
+                if action == "click_and_hold" && record.duration_ms.unwrap_or(0) == 0 {
+                    push(index, Severity::Warning, "click_and_hold has no duration_ms; it releases straight away".to_string());
+                }
+                let repeats = record.repeat_count.unwrap_or(1);
+                if repeats > TIGHT_LOOP_REPEATS {
+                    push(index, Severity::Warning, format!("{} repeats {} times back-to-back with no delay between clicks", action, repeats));
+                }
+            },
+            _ => {},
+        }
+
+        // Anything else is silently played as the left button
+        if let Some(button) = record.button.as_deref().filter(|button| crate::parse_button(button).is_none()) {
+            push(index, Severity::Error, format!("unknown button '{}'; use left, right, middle, back, forward or 1-9", button));
+        }
+
+        // Clicks and drags hold their modifiers; a scroll's are its direction, checked above
+        if let Some(spec) = record.modifiers.as_deref().filter(|spec| !spec.trim().is_empty()) {
+            if keyboard::takes_modifiers(action) {
+                if let Err(error) = keyboard::parse_modifiers(spec) {
+                    push(index, Severity::Error, format!("modifiers: {}", error));
+                }
+            } else if action != "scroll" {
+                push(index, Severity::Warning, format!("modifiers has no effect on {}; only clicks and drags hold them", action));
+            }
+        }
+
+        if record.path.is_some() && !matches!(action, "drag_to" | "draw_polyline") {
+            push(index, Severity::Warning, format!("path has no effect on {}; only drag_to and draw_polyline follow one", action));
+        }
+
+        if record.timeout_ms.is_some() && action != "wait_for_image" && !window::is_window_action(action) {
+            push(index, Severity::Warning, format!("timeout_ms has no effect on {}; only wait_for_image and the window actions wait", action));
+        }
+
+        // Loops, jumps and conditions never fail, so there is nothing to retry
+        if record.retries.is_some_and(|retries| retries > 0) && (flow::is_flow(action) || action == "if_pixel") {
+            push(index, Severity::Warning, format!("retries has no effect on {}; only actions are retried", action));
+        }
+        if record.retry_delay_ms.is_some() && record.retries.unwrap_or(0) == 0 {
+            push(index, Severity::Warning, "retry_delay_ms has no effect without retries".to_string());
+        }
+
+        // Only pointer positions are translated to another monitor or a window, and are checked against it during the run
+        match (record.monitor, record.window.as_ref()) {
+            (Some(0), _) => push(index, Severity::Error, "monitors are numbered from 1".to_string()),
+            (Some(_), Some(_)) => push(index, Severity::Error, "monitor and window can't both be set".to_string()),
+            (Some(_), None) if !POINTER_ACTIONS.contains(&action) => {
+                push(index, Severity::Warning, format!("monitor has no effect on {}; only pointer positions are translated", action));
+            },
+            (None, Some(_)) if !POINTER_ACTIONS.contains(&action) && !window::is_window_action(action) => {
+                push(index, Severity::Warning, format!("window has no effect on {}; only pointer positions are translated", action));
+            },
+            _ => {},
+        }
+        if let Some(Err(error)) = record.window.as_deref().map(window::pattern) {
+            push(index, Severity::Error, error);
+        }
+
+        // Coordinates are absolute for everything except relative moves, scrolls and waits
+        let translated = record.monitor.is_some() || record.window.is_some();
+        if let (Some((x, y)), Some((width, height)), false) = (coordinates, screen, translated) {
+            let absolute = !matches!(action, "move_relative" | "scroll" | "wait" | "checkpoint");
+            if absolute && (x < 0 || y < 0 || x >= width || y >= height) {
+                push(index, Severity::Error, format!("({}, {}) is outside the {}x{} screen", x, y, width, height));
+            }
+        }
+        if let (Some((x, y)), Some((width, height)), false) = (record.to_x.zip(record.to_y), screen, translated) {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                push(index, Severity::Error, format!("drop point ({}, {}) is outside the {}x{} screen", x, y, width, height));
+            }
+        }
+
+        if coordinates.is_some() && !matches!(action, "wait" | "scroll" | "checkpoint" | "move_window") {
+            cursor_positioned = true;
+        }
+    }
+
+    // Rows nothing can get to, which are left over from an edit or behind a goto that always jumps
+    let reached = reachable(actions);
+    for (index, record) in actions.iter().enumerate().filter(|&(index, _)| !reached[index]) {
+        if record.action == "label" {
+            push(index, Severity::Warning, format!("label '{}' is never reached; nothing jumps to it and playback never gets to it from the row before", record.name.as_deref().unwrap_or("")));
+        } else if index == 0 || reached[index - 1] {
+            let end = (index..actions.len()).find(|&next| reached[next]).unwrap_or(actions.len());
+            push(index, Severity::Warning, format!("rows {}-{} are never reached; nothing jumps to them and playback never gets to them from the row before", row_number(index), row_number(end - 1)));
+        }
+    }
+
+    for (start, end, passes) in loops(actions) {
+        let body = &actions[start..=end];
+        if body.iter().any(pauses) {
+            continue;
+        }
+        let kind = if actions[start].action == "loop_start" { "loop" } else { "goto loop" };
+        match passes {
+            // Leaving such a loop is up to a condition, which is at least worth a look
+            None if !body.iter().any(|record| record.action == "if_pixel") => {
+                push(end, Severity::Error, format!("{} back to row {} repeats forever with no delay, injecting as fast as it can until stopped", kind, row_number(start)));
+            },
+            None => push(end, Severity::Warning, format!("{} back to row {} has no delay; it repeats as fast as it can until the condition jumps out", kind, row_number(start))),
+            Some(passes) if passes > TIGHT_LOOP_REPEATS => {
+                push(end, Severity::Warning, format!("{} back to row {} repeats {} times back-to-back with no delay", kind, row_number(start), passes));
+            },
+            Some(_) => {},
+        }
+    }
+
+    if let Some(start) = open_drag {
+        push(start, Severity::Error, "drag is never released; the button stays held after the script ends".to_string());
+    }
+    for start in open_loops {
+        push(start, Severity::Error, "loop_start is never closed by a loop_end".to_string());
+    }
+    for (name, start) in held_keys {
+        push(start, Severity::Warning, format!("'{}' is never released by key_up; it is only let go when the run ends", name));
+    }
+
+    findings.sort_by_key(|finding| finding.row);
+    findings
+}
+
+// Which rows playback can get to from the first one, through loops, jumps and conditions
+fn reachable(actions: &[MouseAction]) -> Vec<bool> {
+    let mut labels = HashMap::new();
+    for (index, record) in actions.iter().enumerate().filter(|(_, record)| record.action == "label") {
+        labels.entry(record.name.as_deref().unwrap_or("")).or_insert(index);
+    }
+    let mut ends = HashMap::new();
+    let mut starts = HashMap::new();
+    let mut open = Vec::new();
+    for (index, record) in actions.iter().enumerate() {
+        match record.action.as_str() {
+            "loop_start" => open.push(index),
+            "loop_end" => if let Some(start) = open.pop() {
+                ends.insert(start, index);
+                starts.insert(index, start);
+            },
+            _ => {},
+        }
+    }
+
+    let mut reached = vec![false; actions.len()];
+    let mut pending = vec![0];
+    while let Some(index) = pending.pop() {
+        if index >= actions.len() || reached[index] {
+            continue;
+        }
+        reached[index] = true;
+        let record = &actions[index];
+        let label = labels.get(record.name.as_deref().unwrap_or("")).copied();
+        match record.action.as_str() {
+            // Without a limit it jumps every time, so the row after it only plays if something jumps there
+            "goto" => {
+                if record.repeat_count != Some(0) {
+                    pending.extend(label);
+                }
+                if record.repeat_count.is_some() {
+                    pending.push(index + 1);
+                }
+            },
+            "if_pixel" => {
+                pending.push(index + 1);
+                match label.filter(|_| flow::jumps_to_label(record)) {
+                    Some(target) => pending.push(target),
+                    None => pending.push(index + 2),
+                }
+            },
+            "loop_start" if record.repeat_count == Some(0) => pending.extend(ends.get(&index).map(|end| end + 1)),
+            "loop_end" => {
+                pending.push(index + 1);
+                pending.extend(starts.get(&index).map(|start| start + 1));
+            },
+            _ => pending.push(index + 1),
+        }
+    }
+    reached
+}
+
+// Every loop in the script: loop_start blocks, and gotos back to a label
+// above them, as the first and last row and how many times it repeats (None
+// for a goto that jumps every time)
+fn loops(actions: &[MouseAction]) -> Vec<(usize, usize, Option<u32>)> {
+    let mut found = Vec::new();
+    let mut open = Vec::new();
+    for (index, record) in actions.iter().enumerate() {
+        match record.action.as_str() {
+            "loop_start" => open.push(index),
+            "loop_end" => if let Some(start) = open.pop() {
+                found.push((start, index, Some(actions[start].repeat_count.unwrap_or(1))));
+            },
+            "goto" => {
+                let name = record.name.as_deref().unwrap_or("");
+                let target = actions[..index].iter().position(|row| row.action == "label" && row.name.as_deref() == Some(name));
+                if let Some(target) = target {
+                    found.push((target, index, record.repeat_count));
+                }
+            },
+            _ => {},
+        }
+    }
+    found
+}
+
+// Whether a row takes time of its own, which keeps a loop around it from spinning
+fn pauses(record: &MouseAction) -> bool {
+    record.delay_ms.unwrap_or(0) > 0 || record.duration_ms.unwrap_or(0) > 0 || record.interval_ms.unwrap_or(0) > 0
+        || matches!(record.action.as_str(), "wait_for_image" | "wait_for_window")
+}
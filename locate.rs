@@ -0,0 +1,62 @@
+// Finding an image on the screen, for the `wait_for_image` action.
+//
+// The template is slid over a capture of the primary monitor and matches at
+// the first position where every pixel is within the row's `tolerance` on
+// each color channel, so it should be cut from a screenshot taken at the same
+// scale. Transparent template pixels match anything, which lets a template
+// leave out a background that changes. The screen is captured again every
+// quarter second until the template shows up or the timeout passes.
+
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use xcap::image::{self, RgbaImage};
+use crate::screen;
+use crate::Controls;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Wait until `template` is on screen and return its top-left corner
+pub fn wait_for(path: &Path, tolerance: u8, timeout: Duration, controls: &Controls) -> Result<(i32, i32), Box<dyn Error>> {
+    let template = image::open(path)
+        .map_err(|e| format!("Failed to read image '{}': {}", path.display(), e))?
+        .to_rgba8();
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(found) = find(&screen::capture()?, &template, tolerance) {
+            return Ok(found);
+        }
+        if controls.abort_requested() {
+            return Err(format!("Aborted while waiting for '{}'", path.display()).into());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("'{}' didn't appear on screen within {} ms", path.display(), timeout.as_millis()).into());
+        }
+        controls.sleep(POLL_INTERVAL);
+    }
+}
+
+// Top-left corner of the first place `template` appears in `screen`
+pub fn find(screen: &RgbaImage, template: &RgbaImage, tolerance: u8) -> Option<(i32, i32)> {
+    if template.width() > screen.width() || template.height() > screen.height() {
+        return None;
+    }
+    // Only opaque pixels are compared, and each position stops at its first mismatch
+    let pixels: Vec<(u32, u32, [u8; 4])> = template.enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[3] > 0)
+        .map(|(x, y, pixel)| (x, y, pixel.0))
+        .collect();
+    for y in 0..=screen.height() - template.height() {
+        for x in 0..=screen.width() - template.width() {
+            let matches = pixels.iter().all(|&(dx, dy, wanted)| {
+                let got = screen.get_pixel(x + dx, y + dy).0;
+                wanted.iter().zip(got).take(3).all(|(&wanted, got)| wanted.abs_diff(got) <= tolerance)
+            });
+            if matches {
+                return Some((x as i32, y as i32));
+            }
+        }
+    }
+    None
+}
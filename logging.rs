@@ -0,0 +1,74 @@
+// Console and file output through `tracing`, for `-v`, `-q` and `--log-file`.
+//
+// Progress goes to stdout as plain lines, with warnings and errors marked as
+// such. `-q` leaves only the warnings and errors and `-qq` only the errors;
+// `-v` adds the details of every step (where it moved, what it clicked) and
+// `-vv` everything. With `--log-file`, messages from debug up are appended to
+// the file too, each with a timestamp and its level, however quiet the
+// console is, so an unattended run leaves its diagnostics behind.
+//
+// Output that is the point of a command, such as the findings of `validate`,
+// the steps of a dry run or the answer to `ctl status`, is printed as it is
+// and isn't affected by the level.
+
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::fs::OpenOptions;
+use std::io;
+use std::sync::Mutex;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+// Console level for the number of -v and -q flags
+pub fn level(verbose: u8, quiet: u8) -> LevelFilter {
+    match (verbose, quiet) {
+        (0, 0) => LevelFilter::INFO,
+        (0, 1) => LevelFilter::WARN,
+        (0, _) => LevelFilter::ERROR,
+        (1, _) => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+pub fn init(level: LevelFilter, log_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let file = log_file
+        .map(|path| OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("Failed to open log file '{}': {}", path, e)))
+        .transpose()?;
+    let console = tracing_subscriber::fmt::layer()
+        .event_format(Plain)
+        .with_writer(io::stdout)
+        .with_filter(level);
+    let file = file.map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_target(false)
+            .with_writer(Mutex::new(file))
+            .with_filter(level.max(LevelFilter::DEBUG))
+    });
+    tracing_subscriber::registry().with(console).with(file).try_init()?;
+    Ok(())
+}
+
+// Just the message, as the tool has always printed it
+struct Plain;
+
+impl<S, N> FormatEvent<S, N> for Plain
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let level = *event.metadata().level();
+        if level == Level::ERROR {
+            write!(writer, "Error: ")?;
+        } else if level == Level::WARN {
+            write!(writer, "Warning: ")?;
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
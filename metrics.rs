@@ -0,0 +1,145 @@
+// Prometheus metrics for the long-running modes.
+//
+// Counts runs and steps from the run events and keeps a histogram of step
+// durations. Served as text at /metrics by `serve`, and by any mode started
+// with `--metrics <address>`:
+//
+//   mouse_automation_runs_started_total
+//   mouse_automation_runs_finished_total{result="completed"|"failed"}
+//   mouse_automation_run_active
+//   mouse_automation_steps_total
+//   mouse_automation_step_failures_total
+//   mouse_automation_step_retries_total
+//   mouse_automation_step_duration_seconds (histogram)
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Response, Server};
+use tracing::{info, warn};
+use crate::events::{Event, EventBus};
+
+// Upper bounds of the step duration buckets, in seconds
+const BUCKETS: [f64; 10] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct Counters {
+    runs_started: u64,
+    runs_completed: u64,
+    runs_failed: u64,
+    active: bool,
+    steps: u64,
+    step_failures: u64,
+    step_retries: u64,
+    // Steps that took at most each bucket's bound
+    bucket_counts: [u64; BUCKETS.len()],
+    duration_sum: f64,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    // Count everything published on the bus from now on
+    pub fn watch(events: &EventBus) -> Arc<Metrics> {
+        let metrics = Arc::new(Metrics::default());
+        let observer = Arc::clone(&metrics);
+        events.observe(move |event| observer.record(event));
+        metrics
+    }
+
+    fn record(&self, event: &Event) {
+        let mut counters = self.counters.lock().unwrap();
+        match event {
+            Event::Started { .. } => {
+                counters.runs_started += 1;
+                counters.active = true;
+            },
+            Event::StepFinished { duration_ms, ok, .. } => {
+                counters.steps += 1;
+                if !ok {
+                    counters.step_failures += 1;
+                }
+                let seconds = *duration_ms as f64 / 1000.0;
+                counters.duration_sum += seconds;
+                for (count, bound) in counters.bucket_counts.iter_mut().zip(BUCKETS) {
+                    if seconds <= bound {
+                        *count += 1;
+                    }
+                }
+            },
+            Event::Retry { .. } => counters.step_retries += 1,
+            Event::Finished { ok, .. } => {
+                counters.active = false;
+                if *ok {
+                    counters.runs_completed += 1;
+                } else {
+                    counters.runs_failed += 1;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, lines: &[(String, String)]| {
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for (series, value) in lines {
+                let _ = writeln!(text, "{}{} {}", name, series, value);
+            }
+        };
+
+        metric("mouse_automation_runs_started_total", "counter", "Script runs started.",
+            &[(String::new(), counters.runs_started.to_string())]);
+        metric("mouse_automation_runs_finished_total", "counter", "Script runs finished, by result.", &[
+            ("{result=\"completed\"}".to_string(), counters.runs_completed.to_string()),
+            ("{result=\"failed\"}".to_string(), counters.runs_failed.to_string()),
+        ]);
+        metric("mouse_automation_run_active", "gauge", "Whether a script is running.",
+            &[(String::new(), u8::from(counters.active).to_string())]);
+        metric("mouse_automation_steps_total", "counter", "Script steps executed.",
+            &[(String::new(), counters.steps.to_string())]);
+        metric("mouse_automation_step_failures_total", "counter", "Script steps that failed.",
+            &[(String::new(), counters.step_failures.to_string())]);
+        metric("mouse_automation_step_retries_total", "counter", "Retries of failed script steps.",
+            &[(String::new(), counters.step_retries.to_string())]);
+
+        let mut histogram: Vec<(String, String)> = BUCKETS.iter().zip(counters.bucket_counts)
+            .map(|(bound, count)| (format!("_bucket{{le=\"{}\"}}", bound), count.to_string()))
+            .collect();
+        histogram.push(("_bucket{le=\"+Inf\"}".to_string(), counters.steps.to_string()));
+        histogram.push(("_sum".to_string(), counters.duration_sum.to_string()));
+        histogram.push(("_count".to_string(), counters.steps.to_string()));
+        metric("mouse_automation_step_duration_seconds", "histogram", "Time taken to execute each step, excluding its delay.", &histogram);
+        text
+    }
+}
+
+pub fn content_type() -> Header {
+    Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..]).expect("static header is valid")
+}
+
+// Serve /metrics on its own address for the modes without an HTTP server
+pub fn serve(address: &str, metrics: Arc<Metrics>) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    info!("Metrics on http://{}/metrics", address);
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                Response::from_string(metrics.render()).with_header(content_type())
+            } else {
+                Response::from_string("not found").with_status_code(404)
+            };
+            if let Err(error) = request.respond(response) {
+                warn!("failed to send metrics response: {}", error);
+            }
+        }
+    });
+    Ok(())
+}
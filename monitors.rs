@@ -0,0 +1,116 @@
+// The monitor layout, for rows with a `monitor` column.
+//
+// Monitors are numbered from 1 in the order the system lists them. A row with
+// a `monitor` has coordinates relative to that monitor's top-left corner, so a
+// script keeps working when the monitors are arranged differently on another
+// machine. `--out-of-bounds` applies to the row's monitor, and the position is
+// then moved onto the virtual desktop spanning every monitor, where the input
+// system works. The layout is read the first time a row needs it.
+//
+// With `--logical-pixels`, positions are in the logical pixels a scaled
+// display shows applications, and are multiplied by the display's scale
+// factor (1.5 at 150%) first. macOS already works in logical points.
+
+use xcap::Monitor;
+use crate::bounds::{Bounds, OutOfBounds};
+
+struct Display {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    primary: bool,
+    // Physical pixels per logical pixel
+    scale: f64,
+}
+
+#[derive(Default)]
+pub struct Layout {
+    displays: Option<Vec<Display>>,
+}
+
+impl Layout {
+    // Desktop position of (x, y) on monitor `number`
+    pub fn resolve(&mut self, number: u32, x: i32, y: i32, policy: OutOfBounds) -> Result<(i32, i32), String> {
+        let display = self.display(number)?;
+        let (x, y) = Bounds::new(policy, (display.width, display.height)).resolve(x, y)
+            .map_err(|e| format!("{} on monitor {}", e, number))?;
+        Ok((display.x + x, display.y + y))
+    }
+
+    // Size of monitor `number`, or of the primary monitor
+    pub fn size(&mut self, number: Option<u32>) -> Result<(i32, i32), String> {
+        let display = self.display_or_primary(number)?;
+        Ok((display.width, display.height))
+    }
+
+    // Logical (x, y) on monitor `number`, or the primary monitor, in physical pixels
+    pub fn physical(&mut self, number: Option<u32>, x: i32, y: i32) -> Result<(i32, i32), String> {
+        let scale = if cfg!(target_os = "macos") { 1.0 } else { self.display_or_primary(number)?.scale };
+        Ok(((f64::from(x) * scale).round() as i32, (f64::from(y) * scale).round() as i32))
+    }
+
+    fn display_or_primary(&mut self, number: Option<u32>) -> Result<&Display, String> {
+        match number {
+            Some(number) => self.display(number),
+            None => {
+                let displays = self.displays()?;
+                displays.iter().find(|display| display.primary).or(displays.first()).ok_or_else(|| "no monitors were found".to_string())
+            },
+        }
+    }
+
+    fn display(&mut self, number: u32) -> Result<&Display, String> {
+        let displays = self.displays()?;
+        number.checked_sub(1).and_then(|index| displays.get(index as usize))
+            .ok_or_else(|| format!("there is no monitor {}; {}", number, describe(displays)))
+    }
+
+    fn displays(&mut self) -> Result<&[Display], String> {
+        if self.displays.is_none() {
+            self.displays = Some(detect()?);
+        }
+        Ok(self.displays.as_deref().unwrap_or_default())
+    }
+}
+
+fn detect() -> Result<Vec<Display>, String> {
+    let monitors = Monitor::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+    Ok(monitors.iter()
+        .map(|monitor| Display {
+            x: monitor.x(),
+            y: monitor.y(),
+            width: monitor.width() as i32,
+            height: monitor.height() as i32,
+            primary: monitor.is_primary(),
+            scale: f64::from(monitor.scale_factor()).max(0.1),
+        })
+        .collect())
+}
+
+// "monitors are 1: 1920x1080 at (0, 0), primary; 2: ..."
+fn describe(displays: &[Display]) -> String {
+    if displays.is_empty() {
+        return "no monitors were found".to_string();
+    }
+    let listed: Vec<String> = displays.iter().enumerate()
+        .map(|(index, display)| format!("{}: {}x{} at ({}, {}){}", index + 1, display.width, display.height,
+            display.x, display.y, if display.primary { ", primary" } else { "" }))
+        .collect();
+    format!("monitors are {}", listed.join("; "))
+}
+
+// Have Windows report and take positions in physical pixels on every monitor,
+// instead of scaling them for a program it thinks predates high-DPI displays
+#[cfg(windows)]
+pub fn use_physical_pixels() {
+    use windows_sys::Win32::UI::HiDpi::{SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
+
+    // SAFETY: only changes how this process is scaled; fails harmlessly if already set
+    unsafe {
+        SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn use_physical_pixels() {}
@@ -0,0 +1,179 @@
+// Cursor movement and clicks, smoothed and humanized when asked.
+//
+// By default the cursor jumps to each position. A move with a duration is
+// split into small steps about every 8 ms, eased so the cursor speeds up and
+// slows down the way a hand does, for applications that ignore a cursor that
+// jumps or treat it as suspicious. Every step goes through the injector, so
+// the audit log sees each of them; the bounds are only checked for the
+// target. Durations are scaled by the playback speed like delays are.
+//
+// Humanized playback (`--humanize`) adds what tells a hand from a script:
+// targets land a couple of pixels off without leaving the screen, paths bow
+// slightly to one side, moves without a duration take one that grows with the
+// distance, and buttons are held for a varying moment instead of clicked
+// instantly.
+
+use std::error::Error;
+use std::time::Duration;
+use enigo::MouseButton;
+use crate::injector::Injector;
+use crate::random::Random;
+use crate::stroke;
+use crate::Controls;
+
+const STEP_INTERVAL: Duration = Duration::from_millis(8);
+// Between the clicks of a double or triple click, unless --click-interval sets it
+const CLICK_INTERVAL: Duration = Duration::from_millis(10);
+// How far a humanized target may land from the one in the script
+const JITTER: i32 = 2;
+
+pub struct Motion {
+    // How long moves take unless a row sets duration_ms
+    duration: Duration,
+    // Between the clicks of a double or triple click, unless a row sets interval_ms
+    click_interval: Option<Duration>,
+    humanize: Option<Random>,
+}
+
+impl Motion {
+    pub fn new(duration: Duration, click_interval: Option<Duration>, humanize: bool) -> Result<Motion, Box<dyn Error>> {
+        let humanize = if humanize { Some(Random::new(Random::seed()?)) } else { None };
+        Ok(Motion { duration, click_interval, humanize })
+    }
+
+    // How long a move takes unless its row sets duration_ms
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    // Move to a position, over the row's duration_ms if it has one
+    pub fn move_to(&mut self, injector: &mut Injector, controls: &Controls, (x, y): (i32, i32), duration_ms: Option<u64>) {
+        let (from_x, from_y) = injector.location();
+        let mut duration = duration_ms.map_or(self.duration, Duration::from_millis);
+        let mut target = (x, y);
+        // How far the path bows to the side, as a fraction of its length
+        let mut bend = 0.0;
+        if let Some(random) = self.humanize.as_mut() {
+            let jittered = (x + random.below(2 * JITTER as u32 + 1) as i32 - JITTER, y + random.below(2 * JITTER as u32 + 1) as i32 - JITTER);
+            target = within_screen(injector.display_size(), (x, y), jittered);
+            if duration.is_zero() {
+                let distance = f64::from(x - from_x).hypot(f64::from(y - from_y));
+                duration = Duration::from_millis(80 + distance as u64 / 4);
+            }
+            duration = duration.mul_f64(random.between(0.8, 1.2));
+            bend = random.between(-0.12, 0.12);
+        }
+        // --speed scales moves along with delays
+        duration = duration.mul_f64(controls.delay_scale());
+
+        let steps = (duration.as_millis() / STEP_INTERVAL.as_millis()) as u32;
+        if steps >= 2 && (from_x, from_y) != target {
+            let (dx, dy) = (f64::from(target.0 - from_x), f64::from(target.1 - from_y));
+            let pause = duration / steps;
+            for step in 1..steps {
+                let progress = ease(f64::from(step) / f64::from(steps));
+                // Off the straight line most in the middle, perpendicular to it
+                let offset = bend * 4.0 * progress * (1.0 - progress);
+                injector.move_to(
+                    from_x + (dx * progress - dy * offset).round() as i32,
+                    from_y + (dy * progress + dx * offset).round() as i32,
+                );
+                controls.sleep(pause);
+                // Stop where the cursor is rather than finishing the move
+                if controls.abort_requested() {
+                    return;
+                }
+            }
+        }
+        injector.move_to(target.0, target.1);
+    }
+
+    pub fn click(&mut self, injector: &mut Injector, controls: &Controls, button: MouseButton) {
+        match self.humanize.as_mut() {
+            Some(random) => {
+                injector.button_down(button);
+                controls.sleep(Duration::from_millis(random.between(40.0, 120.0) as u64));
+                injector.button_up(button);
+            },
+            None => injector.click(button),
+        }
+    }
+
+    // Press at the first point, glide through the rest over duration_ms and
+    // release at the last. The button is released even when the run is
+    // stopped partway.
+    pub fn drag(&mut self, injector: &mut Injector, controls: &Controls, points: &[(i32, i32)], button: MouseButton, duration_ms: u64) {
+        let (Some(&from), Some(&to)) = (points.first(), points.last()) else { return };
+        self.move_to(injector, controls, from, None);
+        injector.button_down(button);
+        if points.len() > 2 {
+            self.trace(injector, controls, points, Duration::from_millis(duration_ms));
+        } else {
+            self.move_to(injector, controls, to, Some(duration_ms));
+        }
+        injector.button_up(button);
+    }
+
+    // Follow a polyline at an even speed, for strokes that shouldn't ease in and out
+    pub fn trace(&mut self, injector: &mut Injector, controls: &Controls, points: &[(i32, i32)], duration: Duration) {
+        let duration = duration.mul_f64(controls.delay_scale());
+        let steps = ((duration.as_millis() / STEP_INTERVAL.as_millis()) as u32).max(1);
+        for step in 1..=steps {
+            let (x, y) = stroke::along(points, f64::from(step) / f64::from(steps));
+            injector.move_to(x, y);
+            if step < steps {
+                controls.sleep(duration / steps);
+                if controls.abort_requested() {
+                    return;
+                }
+            }
+        }
+    }
+
+    // `count` clicks as one double or triple click, `interval_ms` apart if the row sets it.
+    // The gap isn't scaled by --speed: the system only takes clicks this close as one.
+    pub fn multi_click(&mut self, injector: &mut Injector, controls: &Controls, button: MouseButton, count: u32, interval_ms: Option<u64>) {
+        for click in 0..count {
+            if click > 0 {
+                let gap = match (interval_ms.map(Duration::from_millis).or(self.click_interval), self.humanize.as_mut()) {
+                    (Some(interval), _) => interval,
+                    (None, Some(random)) => Duration::from_millis(random.between(60.0, 140.0) as u64),
+                    (None, None) => CLICK_INTERVAL,
+                };
+                controls.sleep(gap);
+            }
+            self.click(injector, controls, button);
+        }
+    }
+}
+
+// A humanized target, kept on the screen along each axis the script's target
+// is on; targets off it, let through by --out-of-bounds ignore, are left be
+fn within_screen((width, height): (i32, i32), (x, y): (i32, i32), (jittered_x, jittered_y): (i32, i32)) -> (i32, i32) {
+    let keep = |target: i32, jittered: i32, extent: i32| {
+        if (0..extent).contains(&target) { jittered.clamp(0, extent - 1) } else { jittered }
+    };
+    (keep(x, jittered_x, width), keep(y, jittered_y, height))
+}
+
+// Slow at both ends, fastest in the middle
+fn ease(progress: f64) -> f64 {
+    progress * progress * (3.0 - 2.0 * progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_stays_on_the_screen() {
+        let screen = (1920, 1080);
+        assert_eq!(within_screen(screen, (0, 1079), (-2, 1081)), (0, 1079));
+        assert_eq!(within_screen(screen, (1919, 0), (1921, 1)), (1919, 1));
+        assert_eq!(within_screen(screen, (500, 500), (498, 502)), (498, 502));
+        // Off-screen targets were let through on purpose
+        assert_eq!(within_screen(screen, (-100, 2000), (-102, 2001)), (-102, 2001));
+        // An unknown screen size bounds nothing
+        assert_eq!(within_screen((0, 0), (0, 0), (-1, -2)), (-1, -2));
+    }
+}
@@ -0,0 +1,106 @@
+// MQTT trigger mode, started by the `mqtt` subcommand.
+//
+// Subscribes to the topics mapped in the `[mqtt.topics]` table of the config
+// and starts the mapped script when a message arrives on one (the payload is
+// ignored). Topics are matched exactly, without wildcards. Progress events and
+// refused triggers are published as JSON to the status topic.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::runs::Runs;
+use crate::Options;
+
+pub const DEFAULT_BROKER: &str = "localhost:1883";
+pub const DEFAULT_CLIENT_ID: &str = "mouse-automation";
+pub const DEFAULT_STATUS_TOPIC: &str = "mouse-automation/status";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    // host:port of the broker
+    pub broker: Option<String>,
+    pub client_id: Option<String>,
+    pub status_topic: Option<String>,
+    // Topic -> script name in the scripts directory
+    pub topics: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct Rejected<'a> {
+    event: &'a str,
+    topic: &'a str,
+    script: &'a str,
+    error: String,
+}
+
+pub fn listen(broker: Option<&str>, options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    let settings = config.mqtt.clone();
+    if settings.topics.is_empty() {
+        return Err("No MQTT topics configured; map topics to scripts in the [mqtt.topics] table of the config".into());
+    }
+
+    let broker = broker.or(settings.broker.as_deref()).unwrap_or(DEFAULT_BROKER);
+    let (host, port) = broker.rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        .ok_or_else(|| format!("Invalid MQTT broker '{}', expected host:port", broker))?;
+    let status_topic = settings.status_topic.clone().unwrap_or_else(|| DEFAULT_STATUS_TOPIC.to_string());
+
+    let runs = Runs::new(options, config)?;
+    let mut mqtt_options = MqttOptions::new(settings.client_id.as_deref().unwrap_or(DEFAULT_CLIENT_ID), host, port);
+    mqtt_options.set_keep_alive(Duration::from_secs(30));
+    // Room for every subscription so subscribing never blocks the event loop
+    let (client, mut connection) = Client::new(mqtt_options, settings.topics.len() + 16);
+
+    // Forward progress events of every run to the status topic
+    let updates = runs.events().subscribe();
+    let publisher = client.clone();
+    let events_topic = status_topic.clone();
+    thread::spawn(move || {
+        for update in updates {
+            if let Err(error) = publisher.publish(events_topic.as_str(), QoS::AtLeastOnce, false, update) {
+                warn!("failed to publish MQTT status: {}", error);
+            }
+        }
+    });
+
+    info!("Listening for MQTT triggers on {} (status on '{}')", broker, status_topic);
+    for notification in connection.iter() {
+        let publish = match notification {
+            Ok(Event::Incoming(Packet::Publish(publish))) => publish,
+            // Subscribe on every (re)connect, the broker forgets clean sessions
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                for (topic, script) in &settings.topics {
+                    client.subscribe(topic.as_str(), QoS::AtLeastOnce)?;
+                    info!("Subscribed to '{}' -> {}", topic, script);
+                }
+                continue;
+            },
+            Ok(_) => continue,
+            Err(error) => {
+                // The client reconnects on the next iteration
+                warn!("MQTT connection error: {}", error);
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            },
+        };
+
+        let Some(script) = settings.topics.get(&publish.topic) else {
+            continue;
+        };
+        info!("Trigger on '{}': starting {}", publish.topic, script);
+        if let Err(error) = runs.start(script) {
+            warn!("Refused trigger on '{}': {}", publish.topic, error);
+            let rejected = Rejected { event: "rejected", topic: &publish.topic, script, error: error.to_string() };
+            let payload = serde_json::to_string(&rejected).unwrap_or_default();
+            client.publish(status_topic.as_str(), QoS::AtLeastOnce, false, payload)?;
+        }
+    }
+    Ok(())
+}
+
@@ -0,0 +1,115 @@
+// Action categories and the sandbox policy that can refuse them.
+//
+// Every row falls in one category: `pointer` for the mouse, `keyboard` for
+// typing and keys, `window` for raising, arranging, closing and waiting for
+// windows, `screen` for the rows that read the screen or save it to a file,
+// and `launch` for a focus_window with a command, which starts a program
+// through the shell when the window isn't open. `shell` is another name for
+// `launch`. `clipboard` is for the rows that read or set the clipboard; no
+// action does yet, so denying it refuses nothing today but keeps policies
+// written for it valid.
+
+use std::error::Error;
+use std::fmt;
+use serde::Deserialize;
+use crate::{window, MouseAction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Pointer,
+    Keyboard,
+    Window,
+    Screen,
+    #[serde(alias = "shell")]
+    Launch,
+    Clipboard,
+}
+
+impl Category {
+    pub fn parse(name: &str) -> Option<Category> {
+        let category = match name.trim().to_ascii_lowercase().as_str() {
+            "pointer" => Category::Pointer,
+            "keyboard" => Category::Keyboard,
+            "window" => Category::Window,
+            "screen" => Category::Screen,
+            "launch" | "shell" => Category::Launch,
+            "clipboard" => Category::Clipboard,
+            _ => return None,
+        };
+        Some(category)
+    }
+
+    // Categories that can affect the system beyond the cursor
+    pub fn is_dangerous(self) -> bool {
+        self == Category::Launch
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Category::Pointer => "pointer",
+            Category::Keyboard => "keyboard",
+            Category::Window => "window",
+            Category::Screen => "screen",
+            Category::Launch => "launch",
+            Category::Clipboard => "clipboard",
+        };
+        f.write_str(name)
+    }
+}
+
+// Which category a row belongs to
+pub fn category(record: &MouseAction) -> Category {
+    match record.action.as_str() {
+        // Runs its command when the window isn't open
+        "focus_window" if record.text.is_some() => Category::Launch,
+        action if window::is_window_action(action) => Category::Window,
+        "screenshot" | "assert_region_matches" | "wait_for_image" | "if_pixel" | "assert_pixel" => Category::Screen,
+        "type_text" | "snippet" | "key_press" | "key_down" | "key_up" => Category::Keyboard,
+        _ => Category::Pointer,
+    }
+}
+
+// Categories the runner refuses to execute
+#[derive(Debug, Default)]
+pub struct Policy {
+    denied: Vec<Category>,
+}
+
+impl Policy {
+    pub fn deny(&mut self, category: Category) {
+        if !self.denied.contains(&category) {
+            self.denied.push(category);
+        }
+    }
+
+    // Parse a comma separated list such as "shell,launch,clipboard"
+    pub fn deny_list(&mut self, list: &str) -> Result<(), Box<dyn Error>> {
+        for name in list.split(',').filter(|name| !name.trim().is_empty()) {
+            let category = Category::parse(name)
+                .ok_or_else(|| format!("Unknown action category: {}", name.trim()))?;
+            self.deny(category);
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, record: &MouseAction) -> bool {
+        !self.denied.contains(&category(record))
+    }
+
+    // Fail before playback if any step uses a denied category
+    pub fn check(&self, actions: &[MouseAction]) -> Result<(), Box<dyn Error>> {
+        let refused: Vec<String> = actions.iter().enumerate()
+            .filter(|(_, record)| !self.allows(record))
+            .map(|(index, record)| format!("step {}: {} ({})", index + 1, record.action, category(record)))
+            .collect();
+
+        if refused.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Script uses denied actions:\n  {}", refused.join("\n  ")).into())
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// Checks that the input backend can actually inject events, run before the
+// first one is sent so a script doesn't fail silently halfway through.
+// `needs_elevation` is set for --block-input, which needs admin on Windows.
+
+use std::error::Error;
+
+// X11 injection needs a display; under Wayland it only reaches XWayland windows
+#[cfg(target_os = "linux")]
+pub fn check(_needs_elevation: bool) -> Result<(), Box<dyn Error>> {
+    use std::env;
+    use tracing::warn;
+
+    let display = env::var("DISPLAY").unwrap_or_default();
+    if display.is_empty() {
+        return Err("No X display available for input injection.\n\
+            Fix: run from a graphical session, or export DISPLAY (e.g. DISPLAY=:0) \
+            and make sure this user may connect to it (xhost +SI:localuser:$USER)."
+            .into());
+    }
+
+    if crate::backend::wayland_session() {
+        warn!("Wayland session detected. Injected input only reaches XWayland windows; \
+            native Wayland apps will ignore it. Allow writing to /dev/uinput and pass --backend uinput \
+            (see Input backends in the README), or log into an X11 session if the target is a Wayland app.");
+    }
+    Ok(())
+}
+
+// Injecting events requires the Accessibility permission
+#[cfg(target_os = "macos")]
+pub fn check(_needs_elevation: bool) -> Result<(), Box<dyn Error>> {
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+
+    // SAFETY: takes no arguments and only reads the permission state
+    if !unsafe { AXIsProcessTrusted() } {
+        return Err("This process is not allowed to control the computer, so input would be dropped.\n\
+            Fix: open System Settings > Privacy & Security > Accessibility, enable the terminal \
+            (or app) running mouse-automation, then restart it."
+            .into());
+    }
+    Ok(())
+}
+
+// SendInput needs the interactive desktop, and UIPI drops input sent to elevated windows
+#[cfg(windows)]
+pub fn check(needs_elevation: bool) -> Result<(), Box<dyn Error>> {
+    use tracing::info;
+
+    if crate::session::is_locked() {
+        return Err("The input desktop is not accessible (locked session, secure desktop or service session).\n\
+            Fix: run from an unlocked, interactive desktop session."
+            .into());
+    }
+
+    let elevated = is_elevated();
+    if needs_elevation && !elevated {
+        return Err("--block-input requires administrator rights.\n\
+            Fix: start the terminal with \"Run as administrator\"."
+            .into());
+    }
+    if !elevated {
+        info!("Note: not running elevated. Windows (UIPI) silently drops input sent to apps running \
+            as administrator; use an elevated terminal if the target app is elevated.");
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn is_elevated() -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    // SAFETY: the token handle is closed before returning and the output buffer is sized for TOKEN_ELEVATION
+    unsafe {
+        let mut token: HANDLE = 0;
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+        let mut size = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        CloseHandle(token);
+        ok != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn check(_needs_elevation: bool) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
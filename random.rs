@@ -0,0 +1,44 @@
+// Small pseudo-random generator for the fuzzer and humanized playback.
+//
+// SplitMix64: tiny, and unlike a library generator its sequence for a seed
+// never changes, so a fuzzing run can be repeated from its seed.
+
+use std::error::Error;
+use crate::screen::Region;
+
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random(seed)
+    }
+
+    // A seed from the operating system
+    pub fn seed() -> Result<u64, Box<dyn Error>> {
+        let mut bytes = [0u8; 8];
+        getrandom::getrandom(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, bound: u32) -> u32 {
+        (self.next() % u64::from(bound.max(1))) as u32
+    }
+
+    // Uniform in low..high
+    pub fn between(&mut self, low: f64, high: f64) -> f64 {
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        low + (high - low) * unit
+    }
+
+    pub fn point(&mut self, region: Region) -> (i32, i32) {
+        (region.x + self.below(region.width) as i32, region.y + self.below(region.height) as i32)
+    }
+}
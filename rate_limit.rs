@@ -0,0 +1,43 @@
+// Ceiling on clicks per second, independent of script delays.
+
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+pub const DEFAULT_MAX_CLICKS_PER_SECOND: u32 = 20;
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+// Sliding one-second window so short bursts such as double-clicks stay intact
+pub struct ClickLimiter {
+    max_per_second: u32,
+    recent: VecDeque<Instant>,
+    throttled: bool,
+}
+
+impl ClickLimiter {
+    pub fn new(max_per_second: u32) -> Self {
+        ClickLimiter { max_per_second: max_per_second.max(1), recent: VecDeque::new(), throttled: false }
+    }
+
+    // Block until another click is allowed
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        while self.recent.front().is_some_and(|&at| now.duration_since(at) >= WINDOW) {
+            self.recent.pop_front();
+        }
+
+        if self.recent.len() >= self.max_per_second as usize {
+            if !self.throttled {
+                warn!("click rate capped at {} per second", self.max_per_second);
+                self.throttled = true;
+            }
+            if let Some(oldest) = self.recent.pop_front() {
+                thread::sleep(WINDOW.saturating_sub(now.duration_since(oldest)));
+            }
+        }
+
+        self.recent.push_back(Instant::now());
+    }
+}
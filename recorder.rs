@@ -0,0 +1,467 @@
+// Input recorder, started by the `record` subcommand.
+//
+// Writes what is done with the mouse and keyboard as a script that plays it
+// back. Mouse moves are sampled every few milliseconds; a press and release
+// in place becomes a click (two in quick succession a double click, a long
+// one a click_and_hold), and a left press released elsewhere a drag and
+// release. Wheel notches become scroll rows. Runs of ordinary characters
+// become one `type_text` row replayed at the speed they were typed, and other
+// keys, or anything pressed with ctrl, alt or meta held, become `key_press`
+// chords. Each row's delay_ms is the time since the previous row. Rows are
+// flushed as they are recorded.
+
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use csv::Writer;
+use rdev::{listen, Button, Event, EventType, Key};
+use tracing::{info, warn};
+use crate::controls::parse_key;
+use crate::MouseAction;
+
+const DEFAULT_STOP_KEY: &str = "F10";
+// Minimum time between recorded mouse moves
+const MOVE_SAMPLE: Duration = Duration::from_millis(30);
+// A press and release within this many pixels is a click rather than a drag
+const CLICK_TOLERANCE: f64 = 4.0;
+// Two clicks this close together in time make a double click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+// A press held in place at least this long is a click_and_hold
+const HOLD_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordOptions {
+    pub stop_key: Option<String>,
+    // Record clicks, drags and scrolls without the moves between them
+    pub no_moves: bool,
+}
+
+#[derive(Default)]
+struct Modifiers {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    meta: bool,
+}
+
+impl Modifiers {
+    // Update the held modifiers; returns whether the key was a modifier
+    fn track(&mut self, key: Key, pressed: bool) -> bool {
+        match key {
+            Key::ControlLeft | Key::ControlRight => self.ctrl = pressed,
+            Key::Alt | Key::AltGr => self.alt = pressed,
+            Key::ShiftLeft | Key::ShiftRight => self.shift = pressed,
+            Key::MetaLeft | Key::MetaRight => self.meta = pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    // "ctrl+shift+" and so on, in the order keyboard::parse_chord presses them
+    fn prefix(&self) -> String {
+        [(self.ctrl, "ctrl+"), (self.alt, "alt+"), (self.shift, "shift+"), (self.meta, "meta+")]
+            .iter()
+            .filter(|(held, _)| *held)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+// Characters typed since the last other key
+struct Typing {
+    text: String,
+    started: SystemTime,
+    last: SystemTime,
+}
+
+// A mouse button held down
+struct Press {
+    button: Button,
+    at: (f64, f64),
+    time: SystemTime,
+}
+
+// A click held back in case a second one makes it a double click
+struct PendingClick {
+    row: MouseAction,
+    at: (f64, f64),
+    time: SystemTime,
+    seen: Instant,
+}
+
+struct Recorder {
+    writer: Writer<File>,
+    no_moves: bool,
+    modifiers: Modifiers,
+    typing: Option<Typing>,
+    // Where the cursor is, and where the script last left it
+    cursor: (f64, f64),
+    written_cursor: Option<(i32, i32)>,
+    last_move: Option<SystemTime>,
+    press: Option<Press>,
+    pending_click: Option<PendingClick>,
+    // When the previous row happened
+    last_row: SystemTime,
+    rows: usize,
+}
+
+// rdev can only listen once per process, so one listener feeds every recording
+static SUBSCRIBERS: OnceLock<Mutex<Vec<Sender<Event>>>> = OnceLock::new();
+static LISTENER_ERROR: OnceLock<String> = OnceLock::new();
+
+fn subscribe() -> Receiver<Event> {
+    let (sender, receiver) = channel();
+    let mut started = false;
+    let subscribers = SUBSCRIBERS.get_or_init(|| {
+        started = true;
+        Mutex::new(Vec::new())
+    });
+    subscribers.lock().unwrap().push(sender);
+    if started {
+        thread::spawn(|| {
+            let result = listen(|event| {
+                let mut subscribers = SUBSCRIBERS.get().expect("initialized before listening").lock().unwrap();
+                subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+            });
+            if let Err(error) = result {
+                let _ = LISTENER_ERROR.set(format!("Input listener failed: {:?}", error));
+                // Disconnects every recording so it reports the error
+                SUBSCRIBERS.get().expect("initialized before listening").lock().unwrap().clear();
+            }
+        });
+    }
+    receiver
+}
+
+// Record until the stop key is pressed
+pub fn record(path: &str, options: &RecordOptions) -> Result<(), Box<dyn Error>> {
+    let stop_name = options.stop_key.as_deref().unwrap_or(DEFAULT_STOP_KEY);
+    let stop_key = parse_key(stop_name).ok_or_else(|| format!("Unknown stop key: {}", stop_name))?;
+    info!("Recording mouse and keyboard to {}; press {} to stop", path, stop_name);
+    let rows = record_until(Path::new(path), options.no_moves, Some(stop_key), None)?;
+    info!("Recorded {} row(s) to {}", rows, path);
+    Ok(())
+}
+
+// Record for a fixed time, for remote clients that can't press a stop key
+pub fn record_for(path: &Path, duration: Duration) -> Result<usize, Box<dyn Error>> {
+    info!("Recording mouse and keyboard to {} for {} s", path.display(), duration.as_secs());
+    let rows = record_until(path, false, None, Some(Instant::now() + duration))?;
+    info!("Recorded {} row(s) to {}", rows, path.display());
+    Ok(rows)
+}
+
+fn record_until(path: &Path, no_moves: bool, stop_key: Option<Key>, deadline: Option<Instant>) -> Result<usize, Box<dyn Error>> {
+    let writer = Writer::from_path(path).map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
+    let receiver = subscribe();
+    let mut recorder = Recorder {
+        writer,
+        no_moves,
+        modifiers: Modifiers::default(),
+        typing: None,
+        cursor: (0.0, 0.0),
+        written_cursor: None,
+        last_move: None,
+        press: None,
+        pending_click: None,
+        last_row: SystemTime::now(),
+        rows: 0,
+    };
+    loop {
+        let timeout = deadline.map_or(Duration::from_millis(100), |deadline| {
+            deadline.saturating_duration_since(Instant::now()).min(Duration::from_millis(100))
+        });
+        let event = match receiver.recv_timeout(timeout) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    break;
+                }
+                recorder.expire_pending_click()?;
+                continue;
+            },
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(LISTENER_ERROR.get().cloned().unwrap_or_else(|| "Input listener stopped".to_string()).into());
+            },
+        };
+        if stop_key.is_some_and(|stop_key| event.event_type == EventType::KeyPress(stop_key)) {
+            break;
+        }
+        recorder.handle(event)?;
+    }
+    recorder.finish()?;
+    Ok(recorder.rows)
+}
+
+impl Recorder {
+    fn handle(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+        match event.event_type {
+            EventType::KeyPress(key) => self.handle_key(&event, key, true),
+            EventType::KeyRelease(key) => self.handle_key(&event, key, false),
+            EventType::MouseMove { x, y } => self.handle_move(event.time, (x, y)),
+            EventType::ButtonPress(button) => {
+                self.flush_typing()?;
+                self.press = Some(Press { button, at: self.cursor, time: event.time });
+                Ok(())
+            },
+            EventType::ButtonRelease(button) => self.handle_release(event.time, button),
+            EventType::Wheel { delta_x, delta_y } if delta_x != 0 || delta_y != 0 => self.handle_wheel(event.time, delta_x, delta_y),
+            EventType::Wheel { .. } => Ok(()),
+        }
+    }
+
+    fn handle_key(&mut self, event: &Event, key: Key, pressed: bool) -> Result<(), Box<dyn Error>> {
+        if self.modifiers.track(key, pressed) || !pressed {
+            return Ok(());
+        }
+        self.flush_pending_click()?;
+
+        // Shifted characters are plain typing too; the event names the character produced
+        let chord = self.modifiers.ctrl || self.modifiers.alt || self.modifiers.meta;
+        let typed = event.name.as_deref()
+            .filter(|text| !chord && !text.is_empty() && !text.chars().any(char::is_control));
+        if let Some(text) = typed {
+            match self.typing.as_mut() {
+                Some(typing) => {
+                    typing.text.push_str(text);
+                    typing.last = event.time;
+                },
+                None => self.typing = Some(Typing { text: text.to_string(), started: event.time, last: event.time }),
+            }
+            return Ok(());
+        }
+
+        self.flush_typing()?;
+        let Some(name) = key_name(key) else {
+            warn!("skipping {:?}, which has no key name", key);
+            return Ok(());
+        };
+        let chord = format!("{}{}", self.modifiers.prefix(), name);
+        info!("key_press {}", chord);
+        self.write(event.time, MouseAction { action: "key_press".to_string(), key: Some(chord), ..Default::default() })
+    }
+
+    fn handle_move(&mut self, time: SystemTime, to: (f64, f64)) -> Result<(), Box<dyn Error>> {
+        self.cursor = to;
+        // Jitter between the clicks of a double click, or while a button is down, isn't a move
+        let near_click = self.pending_click.as_ref().is_some_and(|pending| distance(pending.at, to) <= CLICK_TOLERANCE);
+        if self.no_moves || self.press.is_some() || near_click {
+            return Ok(());
+        }
+        if self.last_move.is_some_and(|last| millis_between(last, time) < MOVE_SAMPLE.as_millis() as u64) {
+            return Ok(());
+        }
+        self.flush_pending_click()?;
+        self.flush_typing()?;
+        self.last_move = Some(time);
+        self.write_at(time, "move", pixel(to), MouseAction::default())
+    }
+
+    fn handle_release(&mut self, time: SystemTime, button: Button) -> Result<(), Box<dyn Error>> {
+        let Some(press) = self.press.take().filter(|press| press.button == button) else {
+            return Ok(());
+        };
+        let release = self.cursor;
+        if button == Button::Left && distance(press.at, release) > CLICK_TOLERANCE {
+            self.flush_pending_click()?;
+            info!("drag ({}, {}) -> ({}, {})", press.at.0 as i32, press.at.1 as i32, release.0 as i32, release.1 as i32);
+            self.write_at(press.time, "drag", pixel(press.at), MouseAction::default())?;
+            return self.write_at(time, "release", pixel(release), MouseAction::default());
+        }
+
+        let (action, button_name) = match button {
+            Button::Left => ("click", "left"),
+            Button::Right => ("right_click", "right"),
+            Button::Middle => ("click", "middle"),
+            Button::Unknown(code) => match side_button(code) {
+                Some(name) => ("click", name),
+                None => {
+                    warn!("skipping mouse button {}, which scripts can't name", code);
+                    return Ok(());
+                },
+            },
+        };
+        let (x, y) = pixel(press.at);
+        let held = millis_between(press.time, time);
+        if held >= HOLD_TIME.as_millis() as u64 {
+            self.flush_pending_click()?;
+            info!("click_and_hold ({}, {}) for {} ms", x, y, held);
+            let row = MouseAction { button: Some(button_name.to_string()), duration_ms: Some(held), ..Default::default() };
+            return self.write_at(press.time, "click_and_hold", (x, y), row);
+        }
+        let row = MouseAction {
+            action: action.to_string(),
+            x_position: Some(x),
+            y_position: Some(y),
+            button: Some(button_name.to_string()),
+            ..Default::default()
+        };
+
+        if let Some(pending) = self.pending_click.take() {
+            let double = pending.row.action == "click" && row.action == "click" && pending.row.button == row.button
+                && distance(pending.at, press.at) <= CLICK_TOLERANCE
+                && millis_between(pending.time, press.time) <= DOUBLE_CLICK_TIME.as_millis() as u64;
+            if double {
+                info!("double_click ({}, {})", x, y);
+                return self.write_at(pending.time, "double_click", (x, y), pending.row);
+            }
+            self.pending_click = Some(pending);
+            self.flush_pending_click()?;
+        }
+        self.pending_click = Some(PendingClick { row, at: press.at, time: press.time, seen: Instant::now() });
+        Ok(())
+    }
+
+    fn handle_wheel(&mut self, time: SystemTime, delta_x: i64, delta_y: i64) -> Result<(), Box<dyn Error>> {
+        self.flush_pending_click()?;
+        self.flush_typing()?;
+        // Scrolling happens under the cursor, so put it there first
+        let cursor = pixel(self.cursor);
+        if self.written_cursor != Some(cursor) {
+            self.write_at(time, "move", cursor, MouseAction::default())?;
+        }
+        let (direction, notches) = match (delta_x, delta_y) {
+            (0, delta_y) => (if delta_y < 0 { "down" } else { "up" }, delta_y.unsigned_abs()),
+            (delta_x, _) => (if delta_x < 0 { "left" } else { "right" }, delta_x.unsigned_abs()),
+        };
+        info!("scroll {} x{}", direction, notches);
+        // One row per notch: playback scrolls repeat_count units for every repeat
+        for _ in 0..notches {
+            self.write(time, MouseAction { action: "scroll".to_string(), modifiers: Some(direction.to_string()), ..Default::default() })?;
+        }
+        Ok(())
+    }
+
+    // A click nobody followed up within the double-click time
+    fn expire_pending_click(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.pending_click.as_ref().is_some_and(|pending| pending.seen.elapsed() > DOUBLE_CLICK_TIME) {
+            self.flush_pending_click()?;
+        }
+        Ok(())
+    }
+
+    fn flush_pending_click(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(pending) = self.pending_click.take() else {
+            return Ok(());
+        };
+        let (x, y) = (pending.row.x_position.unwrap_or(0), pending.row.y_position.unwrap_or(0));
+        info!("{} ({}, {})", pending.row.action, x, y);
+        let action = pending.row.action.clone();
+        self.write_at(pending.time, &action, (x, y), pending.row)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.flush_pending_click()?;
+        self.flush_typing()
+    }
+
+    fn flush_typing(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(typing) = self.typing.take() else {
+            return Ok(());
+        };
+        let characters = typing.text.chars().count() as u64;
+        // Replay at the average speed it was typed
+        let interval = (characters > 1).then(|| millis_between(typing.started, typing.last) / (characters - 1));
+        info!("type_text ({} character(s))", characters);
+        self.write(typing.started, MouseAction {
+            action: "type_text".to_string(),
+            text: Some(typing.text),
+            interval_ms: interval,
+            ..Default::default()
+        })?;
+        self.last_row = typing.last;
+        Ok(())
+    }
+
+    // A row that leaves the cursor at the given point
+    fn write_at(&mut self, time: SystemTime, action: &str, (x, y): (i32, i32), row: MouseAction) -> Result<(), Box<dyn Error>> {
+        self.written_cursor = Some((x, y));
+        self.write(time, MouseAction { action: action.to_string(), x_position: Some(x), y_position: Some(y), ..row })
+    }
+
+    fn write(&mut self, time: SystemTime, mut row: MouseAction) -> Result<(), Box<dyn Error>> {
+        row.delay_ms = Some(millis_between(self.last_row, time));
+        self.writer.serialize(&row)?;
+        self.writer.flush()?;
+        self.last_row = time;
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+// The back or forward side button, from the code each platform reports for it
+fn side_button(code: u8) -> Option<&'static str> {
+    let (back, forward) = if cfg!(windows) { (1, 2) } else if cfg!(target_os = "macos") { (3, 4) } else { (8, 9) };
+    match code {
+        code if code == back => Some("back"),
+        code if code == forward => Some("forward"),
+        _ => None,
+    }
+}
+
+fn pixel((x, y): (f64, f64)) -> (i32, i32) {
+    (x.round() as i32, y.round() as i32)
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+fn millis_between(earlier: SystemTime, later: SystemTime) -> u64 {
+    later.duration_since(earlier).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+// The name keyboard::parse_key reads back, for keys recorded as chords
+fn key_name(key: Key) -> Option<String> {
+    let name = match key {
+        Key::Return | Key::KpReturn => "enter",
+        Key::Tab => "tab",
+        Key::Escape => "escape",
+        Key::Backspace => "backspace",
+        Key::Delete | Key::KpDelete => "delete",
+        Key::UpArrow => "up",
+        Key::DownArrow => "down",
+        Key::LeftArrow => "left",
+        Key::RightArrow => "right",
+        Key::Home => "home",
+        Key::End => "end",
+        Key::PageUp => "pageup",
+        Key::PageDown => "pagedown",
+        Key::Space => "space",
+        Key::CapsLock => "capslock",
+        Key::F1 => "f1",
+        Key::F2 => "f2",
+        Key::F3 => "f3",
+        Key::F4 => "f4",
+        Key::F5 => "f5",
+        Key::F6 => "f6",
+        Key::F7 => "f7",
+        Key::F8 => "f8",
+        Key::F9 => "f9",
+        Key::F10 => "f10",
+        Key::F11 => "f11",
+        Key::F12 => "f12",
+        Key::Minus => "-",
+        Key::Equal => "=",
+        Key::Comma => ",",
+        Key::Dot => ".",
+        Key::Slash => "/",
+        Key::SemiColon => ";",
+        Key::Quote => "'",
+        Key::BackQuote => "`",
+        Key::LeftBracket => "[",
+        Key::RightBracket => "]",
+        Key::BackSlash => "\\",
+        // Letters and digits: KeyA -> "a", Num1 -> "1"
+        other => {
+            let debug = format!("{:?}", other);
+            let rest = debug.strip_prefix("Key").or_else(|| debug.strip_prefix("Num"))?;
+            return (rest.len() == 1).then(|| rest.to_ascii_lowercase());
+        },
+    };
+    Some(name.to_string())
+}
@@ -0,0 +1,209 @@
+// HTML run report, for `--report`.
+//
+// A single self-contained file with the run's outcome and every step: its
+// status, how long it took, the events it injected and, for a failed step,
+// the error and the failure screenshot. It can be attached to a ticket and
+// opened anywhere.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+use crate::{lint, screen, MouseAction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+struct StepRecord {
+    index: usize,
+    status: Status,
+    duration: Duration,
+    error: Option<String>,
+    screenshot: Option<PathBuf>,
+    // Events injected by the step, from the injector's journal
+    log: Vec<String>,
+}
+
+pub struct Report {
+    started: SystemTime,
+    steps: Vec<StepRecord>,
+    // Captured by a failing step, attached when the step is recorded
+    screenshot: Option<PathBuf>,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report { started: SystemTime::now(), steps: Vec::new(), screenshot: None }
+    }
+
+    pub fn attach_screenshot(&mut self, path: PathBuf) {
+        self.screenshot = Some(path);
+    }
+
+    pub fn record(&mut self, index: usize, duration: Duration, error: Option<String>, log: Vec<String>) {
+        let status = if error.is_some() { Status::Failed } else { Status::Ok };
+        let screenshot = self.screenshot.take();
+        self.steps.push(StepRecord { index, status, duration, error, screenshot, log });
+    }
+
+    pub fn skip(&mut self, index: usize) {
+        self.steps.push(StepRecord {
+            index,
+            status: Status::Skipped,
+            duration: Duration::ZERO,
+            error: None,
+            screenshot: None,
+            log: Vec::new(),
+        });
+    }
+
+    pub fn write(&self, path: &str, script: Option<&str>, actions: &[MouseAction], outcome: Result<(), String>) -> Result<(), Box<dyn Error>> {
+        let elapsed = self.started.elapsed().unwrap_or_default();
+        let title = format!("Run report: {}", script.unwrap_or("script"));
+        let (outcome_class, outcome_text) = match &outcome {
+            Ok(()) => ("ok", "Completed".to_string()),
+            Err(error) => ("failed", format!("Failed: {}", error)),
+        };
+
+        let mut html = String::new();
+        writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", escape(&title))?;
+        html.push_str(STYLE);
+        writeln!(html, "</head>\n<body>\n<h1>{}</h1>", escape(&title))?;
+        writeln!(html, "<p class=\"{}\">{}</p>", outcome_class, escape(&outcome_text))?;
+        writeln!(html, "<p>Started {} UTC, took {:.1}s. {} of {} step(s) ran.</p>",
+            utc_timestamp(self.started), elapsed.as_secs_f64(),
+            self.steps.iter().filter(|step| step.status != Status::Skipped).count(), actions.len())?;
+
+        html.push_str("<table>\n<tr><th>Step</th><th>Row</th><th>Action</th><th>Details</th><th>Status</th><th>Time</th><th>Log</th></tr>\n");
+        for (index, record) in actions.iter().enumerate() {
+            let step = self.steps.iter().rev().find(|step| step.index == index);
+            let (class, status) = match step.map(|step| step.status) {
+                Some(Status::Ok) => ("ok", "ok"),
+                Some(Status::Failed) => ("failed", "failed"),
+                Some(Status::Skipped) => ("skipped", "skipped"),
+                None => ("skipped", "not run"),
+            };
+            let time = step.filter(|step| step.status != Status::Skipped)
+                .map_or(String::new(), |step| format!("{} ms", step.duration.as_millis()));
+            let log = step.map_or(String::new(), |step| step.log.iter().map(|line| escape(line)).collect::<Vec<_>>().join("<br>"));
+            writeln!(html, "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"log\">{}</td></tr>",
+                class, index + 1, lint::row_number(index), escape(&record.action), escape(&details(record)), status, time, log)?;
+
+            let Some(step) = step.filter(|step| step.status == Status::Failed) else { continue };
+            html.push_str("<tr class=\"failure\"><td colspan=\"7\">");
+            if let Some(error) = &step.error {
+                writeln!(html, "<pre>{}</pre>", escape(error))?;
+            }
+            if let Some(screenshot) = &step.screenshot {
+                match screen::data_uri(screenshot) {
+                    Ok(uri) => writeln!(html, "<img src=\"{}\" alt=\"Screen after step {} failed\">", uri, index + 1)?,
+                    Err(error) => writeln!(html, "<p>Screenshot {} unavailable: {}</p>", escape(&screenshot.display().to_string()), escape(&error.to_string()))?,
+                }
+            }
+            html.push_str("</td></tr>\n");
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, html).map_err(|e| format!("Failed to write report '{}': {}", path, e))?;
+        info!("Wrote run report to {}", path);
+        Ok(())
+    }
+}
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+tr.ok td:nth-child(5), p.ok { color: #1a7f37; }
+tr.failed td:nth-child(5), p.failed { color: #cf222e; font-weight: bold; }
+tr.skipped { color: #888; }
+tr.failure td { background: #fff5f5; }
+td.log { font-family: monospace; font-size: 0.85em; }
+img { max-width: 100%; border: 1px solid #ccc; margin-top: 0.5em; }
+</style>
+";
+
+// The row's non-empty columns besides the action
+pub fn details(record: &MouseAction) -> String {
+    let mut parts = Vec::new();
+    if let (Some(x), Some(y)) = (record.x_position, record.y_position) {
+        parts.push(format!("({}, {})", x, y));
+    }
+    if let (Some(x), Some(y)) = (record.to_x, record.to_y) {
+        parts.push(format!("to ({}, {})", x, y));
+    }
+    if let Some(monitor) = record.monitor {
+        parts.push(format!("on monitor {}", monitor));
+    }
+    if let Some(window) = &record.window {
+        parts.push(format!("in window '{}'", window));
+    }
+    if let Some(delay) = record.delay_ms.filter(|&delay| delay > 0) {
+        parts.push(format!("after {} ms", delay));
+    }
+    if let Some(button) = &record.button {
+        parts.push(format!("button {}", button));
+    }
+    if let Some(modifiers) = &record.modifiers {
+        parts.push(modifiers.clone());
+    }
+    if let Some(repeats) = record.repeat_count.filter(|&repeats| repeats != 1) {
+        parts.push(format!("x{}", repeats));
+    }
+    if let Some(name) = &record.name {
+        parts.push(format!("'{}'", name));
+    }
+    if let Some(key) = &record.key {
+        parts.push(key.clone());
+    }
+    if let (Some(width), Some(height)) = (record.width, record.height) {
+        parts.push(format!("{}x{}", width, height));
+    }
+    if let Some(image) = &record.image {
+        parts.push(image.clone());
+    }
+    if let Some(color) = &record.color {
+        parts.push(color.clone());
+    }
+    if let Some(duration) = record.duration_ms {
+        parts.push(format!("over {} ms", duration));
+    }
+    if let Some(timeout) = record.timeout_ms {
+        parts.push(format!("timeout {} ms", timeout));
+    }
+    // Typed text may be a password, so only its length is shown
+    if let Some(text) = &record.text {
+        parts.push(format!("{} character(s)", text.chars().count()));
+    }
+    parts.join(", ")
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// "2024-05-01 13:45:09"
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()) as i64;
+    let (days, of_day) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, of_day / 3600, of_day % 3600 / 60, of_day % 60)
+}
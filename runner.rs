@@ -0,0 +1,223 @@
+// The API for playing scripts from another Rust program.
+//
+// A `ScriptRunner` plays a `Script` back the way `mouse-automation
+// script.csv` does: snippets are expanded, the policy is checked, preflight
+// runs and every step goes through the lock screen, foreground and bounds
+// checks, the click rate limit and the audit log. Single actions can be
+// executed too; they share one playback, so the rate limit carries over from
+// one call to the next.
+//
+// `assert_events!` is for unit-testing scripts: it plays one into a
+// `SimulatedBackend` under virtual time, so delays cost nothing, fails the
+// test if the script fails or injects anything but the expected events, and
+// returns the time the delays would have taken.
+
+use std::error::Error;
+use std::path::Path;
+use std::slice;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::backend::SimulatedBackend;
+use crate::config::Config;
+use crate::controls::Controls;
+use crate::vars::Vars;
+use crate::{MouseAction, Options, Playback};
+
+// The rows of a script, in order
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    pub actions: Vec<MouseAction>,
+}
+
+impl Script {
+    // A CSV script, or a template that renders to one
+    pub fn load(path: impl AsRef<Path>) -> Result<Script, Box<dyn Error>> {
+        Self::load_with_vars(path, &Vars::new())
+    }
+
+    // The same, filling in `${name}` placeholders from `vars`
+    pub fn load_with_vars(path: impl AsRef<Path>, vars: &Vars) -> Result<Script, Box<dyn Error>> {
+        Ok(Script { actions: crate::load_script(&path.as_ref().to_string_lossy(), vars)? })
+    }
+
+    // CSV text with a header row, e.g. a script compiled into the program
+    pub fn parse(csv: &str) -> Result<Script, Box<dyn Error>> {
+        Ok(Script { actions: crate::parse_script(csv, "script", &Vars::new())? })
+    }
+}
+
+impl From<Vec<MouseAction>> for Script {
+    fn from(actions: Vec<MouseAction>) -> Script {
+        Script { actions }
+    }
+}
+
+pub struct ScriptRunner {
+    options: Options,
+    config: Config,
+    controls: Arc<Controls>,
+    // Built by the first single action and kept for the ones after it
+    playback: Option<Playback>,
+    steps: usize,
+}
+
+impl ScriptRunner {
+    // Configured from `mouse-automation.toml` in the current directory, if there is one
+    pub fn new() -> Result<ScriptRunner, Box<dyn Error>> {
+        Ok(Self::with_config(Config::load(None)?))
+    }
+
+    pub fn with_config(config: Config) -> ScriptRunner {
+        ScriptRunner {
+            options: Options::default(),
+            config,
+            controls: Controls::new(),
+            playback: None,
+            steps: 0,
+        }
+    }
+
+    // For pausing, aborting or watching a run from another thread
+    pub fn controls(&self) -> Arc<Controls> {
+        Arc::clone(&self.controls)
+    }
+
+    // Play into `backend` instead of the real mouse and keyboard, e.g. to check
+    // in a test what a script does. As nothing is injected, preflight, failure
+    // screenshots and the lock screen and foreground checks are skipped
+    pub fn simulate(&mut self, backend: &SimulatedBackend) {
+        self.options.simulated = Some(backend.clone());
+        self.options.skip_preflight = true;
+        self.playback = None;
+    }
+
+    // A value for `${name}` in scripts run with run_file, and in snippets
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.options.vars.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn run(&mut self, script: &Script) -> Result<(), Box<dyn Error>> {
+        let mut actions = script.actions.clone();
+        crate::expand_snippets(&mut actions, &self.config, &self.options.vars)?;
+        crate::check_policy(&actions, &self.options, &self.config)?;
+        crate::execute_script(&actions, &self.options, &self.config, &self.controls)
+    }
+
+    // Load a script, checking its signature if the config requires one, and run it
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref().to_string_lossy();
+        let actions = crate::prepare_script(&path, &self.options, &self.config, &self.options.vars)?;
+        crate::execute_script(&actions, &self.options, &self.config, &self.controls)
+    }
+
+    // One action on its own, checked like a step of a script; its delay_ms is waited first
+    pub fn execute(&mut self, action: &MouseAction) -> Result<(), Box<dyn Error>> {
+        let mut action = action.clone();
+        crate::expand_snippets(slice::from_mut(&mut action), &self.config, &self.options.vars)?;
+        crate::check_policy(slice::from_ref(&action), &self.options, &self.config)?;
+
+        let playback = match &mut self.playback {
+            Some(playback) => playback,
+            None => {
+                let backend = crate::backend_kind(&self.options, &self.config);
+                if !self.options.skip_preflight {
+                    backend.preflight(false)?;
+                }
+                self.playback.insert(crate::build_playback(&self.options, &self.config, &self.controls, backend)?)
+            },
+        };
+        if let Some(delay) = action.delay_ms {
+            self.controls.sleep(Duration::from_millis(delay).mul_f64(self.controls.delay_scale()));
+        }
+        self.steps += 1;
+        playback.perform(self.steps - 1, &action)
+    }
+
+    pub fn move_to(&mut self, x: i32, y: i32) -> Result<(), Box<dyn Error>> {
+        self.execute(&MouseAction { x_position: Some(x), y_position: Some(y), ..action("move") })
+    }
+
+    // "left", "right" or "middle", at the cursor
+    pub fn click(&mut self, button: &str) -> Result<(), Box<dyn Error>> {
+        self.execute(&MouseAction { button: Some(button.to_string()), ..action("click") })
+    }
+
+    pub fn type_text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.execute(&MouseAction { text: Some(text.to_string()), ..action("type_text") })
+    }
+
+    // A key or chord such as "ctrl+s"
+    pub fn key_press(&mut self, chord: &str) -> Result<(), Box<dyn Error>> {
+        self.execute(&MouseAction { key: Some(chord.to_string()), ..action("key_press") })
+    }
+}
+
+fn action(name: &str) -> MouseAction {
+    MouseAction { action: name.to_string(), ..Default::default() }
+}
+
+// Play a script into a simulated 1920x1080 screen with the default config and
+// check the injected events, e.g.
+//     assert_events!(Script::parse("action,x_position,y_position\nclick,400,300\n")?,
+//         [SimulatedEvent::MoveTo(400, 300), SimulatedEvent::Click(MouseButton::Left)]);
+#[macro_export]
+macro_rules! assert_events {
+    ($script:expr, [$($event:expr),* $(,)?]) => {{
+        let backend = $crate::SimulatedBackend::new(1920, 1080);
+        let mut runner = $crate::ScriptRunner::with_config($crate::Config::default());
+        runner.simulate(&backend);
+        runner.controls().use_virtual_time();
+        if let Err(error) = runner.run(&$script) {
+            panic!("script failed: {}\nevents so far: {:?}", error, backend.events());
+        }
+        let expected: &[$crate::SimulatedEvent] = &[$($event),*];
+        assert_eq!(backend.events(), expected, "the script injected different events");
+        runner.controls().virtual_elapsed()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::{env, process};
+    use enigo::MouseButton;
+    use crate::bounds::OutOfBounds;
+    use crate::{Config, MouseAction, Script, ScriptRunner, SimulatedBackend, SimulatedEvent};
+
+    fn row(action: &str, x: i32, y: i32, delay_ms: u64) -> MouseAction {
+        MouseAction { action: action.to_string(), x_position: Some(x), y_position: Some(y), delay_ms: Some(delay_ms), ..Default::default() }
+    }
+
+    #[test]
+    fn assert_events_counts_delays_without_waiting() {
+        let script = Script::from(vec![row("move", 100, 200, 5000), row("right_click", 300, 400, 2500)]);
+        let elapsed = assert_events!(script, [
+            SimulatedEvent::MoveTo(100, 200),
+            SimulatedEvent::MoveTo(300, 400),
+            SimulatedEvent::Click(MouseButton::Right),
+        ]);
+        assert_eq!(elapsed, Duration::from_millis(7500));
+    }
+
+    #[test]
+    #[should_panic(expected = "the script injected different events")]
+    fn assert_events_fails_on_other_events() {
+        assert_events!(Script::from(vec![row("click", 10, 10, 0)]), [SimulatedEvent::MoveTo(10, 10)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "script failed")]
+    fn assert_events_fails_when_the_script_does() {
+        assert_events!(Script::from(vec![MouseAction { action: "save_position".to_string(), ..Default::default() }]), []);
+    }
+
+    #[test]
+    fn simulated_failures_take_no_screenshot() {
+        let failure_dir = env::temp_dir().join(format!("mouse-automation-failures-{}", process::id()));
+        let config = Config { out_of_bounds: Some(OutOfBounds::Error), failure_dir: Some(failure_dir.to_string_lossy().into_owned()), ..Config::default() };
+        let mut runner = ScriptRunner::with_config(config);
+        runner.simulate(&SimulatedBackend::new(1920, 1080));
+        assert!(runner.run(&Script::from(vec![row("click", 5000, 5000, 0)])).is_err());
+        assert!(!failure_dir.exists());
+    }
+}
@@ -0,0 +1,329 @@
+// Script runs started on behalf of remote clients (HTTP, WebSocket, gRPC, agents).
+//
+// Scripts live in the scripts directory and are addressed by name. One script
+// runs at a time, each on its own thread, and the last run stays queryable
+// until the next one starts. A stream (`run --target` against an agent) and a
+// recording count as runs too.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde::Serialize;
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::controls::Controls;
+use crate::events::{Event, EventBus};
+use crate::metrics::{self, Metrics};
+use crate::recorder;
+use crate::vars::Vars;
+use crate::policy::{self, Policy};
+use crate::{MouseAction, Options, Playback};
+
+pub const DEFAULT_SCRIPTS_DIR: &str = "scripts";
+// Longest recording a remote client can ask for
+const MAX_RECORDING: Duration = Duration::from_secs(600);
+
+pub struct Runs {
+    scripts_dir: PathBuf,
+    settings: Arc<(Options, Config)>,
+    events: Arc<EventBus>,
+    metrics: Arc<Metrics>,
+    current: Mutex<Option<CurrentRun>>,
+    streaming: AtomicBool,
+    recording: AtomicBool,
+}
+
+struct CurrentRun {
+    script: String,
+    controls: Arc<Controls>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+#[derive(Serialize)]
+pub struct Status {
+    pub state: String,
+    pub script: Option<String>,
+    pub step: usize,
+    pub total_steps: usize,
+    pub error: Option<String>,
+}
+
+// Why a request about runs was refused, so each transport can map it to its own status codes
+#[derive(Debug)]
+pub enum RunError {
+    Busy,
+    Idle,
+    NotFound,
+    BadRequest(String),
+    Invalid(String),
+    Forbidden(String),
+    Failed(String),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::Busy => write!(f, "a script is already running"),
+            RunError::Idle => write!(f, "no script is running"),
+            RunError::NotFound => write!(f, "no such script"),
+            RunError::BadRequest(message) | RunError::Invalid(message) | RunError::Forbidden(message) | RunError::Failed(message) => {
+                write!(f, "{}", message)
+            },
+        }
+    }
+}
+
+impl Error for RunError {}
+
+impl Runs {
+    pub fn new(options: Options, config: Config) -> Result<Arc<Runs>, Box<dyn Error>> {
+        let scripts_dir = PathBuf::from(options.scripts_dir.as_deref().unwrap_or(DEFAULT_SCRIPTS_DIR));
+        fs::create_dir_all(&scripts_dir)?;
+        let events = Arc::new(EventBus::default());
+        let metrics = Metrics::watch(&events);
+        if let Some(address) = &options.metrics_address {
+            metrics::serve(address, Arc::clone(&metrics))?;
+        }
+        Ok(Arc::new(Runs {
+            scripts_dir,
+            settings: Arc::new((options, config)),
+            events,
+            metrics,
+            current: Mutex::new(None),
+            streaming: AtomicBool::new(false),
+            recording: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn scripts_dir(&self) -> &Path {
+        &self.scripts_dir
+    }
+
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.events
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    // Start a script on a background thread unless one is already running
+    pub fn start(&self, script: &str) -> Result<(), RunError> {
+        self.start_with_vars(script, &Vars::new())
+    }
+
+    // Start a script with extra variables on top of the `--var` ones
+    pub fn start_with_vars(&self, script: &str, extra: &Vars) -> Result<(), RunError> {
+        let mut current = self.current.lock().unwrap();
+        if self.streaming.load(Ordering::SeqCst) || self.recording.load(Ordering::SeqCst)
+            || current.as_ref().is_some_and(|run| !run.controls.is_finished()) {
+            return Err(RunError::Busy);
+        }
+
+        let path = match script_path(&self.scripts_dir, script) {
+            Ok(path) if path.exists() => path,
+            Ok(_) => return Err(RunError::NotFound),
+            Err(error) => return Err(RunError::BadRequest(error.to_string())),
+        };
+        let (options, config) = (&self.settings.0, &self.settings.1);
+        let mut vars = options.vars.clone();
+        vars.extend(extra.iter().map(|(name, value)| (name.clone(), value.clone())));
+        let actions = crate::prepare_script(&path.to_string_lossy(), options, config, &vars)
+            .map_err(|e| RunError::Invalid(e.to_string()))?;
+
+        // Nobody is at the terminal to confirm dangerous scripts
+        if !options.assume_yes && actions.iter().any(|record| policy::category(record).is_dangerous()) {
+            return Err(RunError::Forbidden("script contains dangerous actions; start the server with --yes to allow them".to_string()));
+        }
+
+        let controls = Controls::with_events(Arc::clone(&self.events));
+        controls.publish(Event::Started { script: script.to_string(), total_steps: actions.len() });
+        let error = Arc::new(Mutex::new(None));
+        *current = Some(CurrentRun {
+            script: script.to_string(),
+            controls: Arc::clone(&controls),
+            error: Arc::clone(&error),
+        });
+
+        let settings = Arc::clone(&self.settings);
+        thread::spawn(move || {
+            let result = crate::execute_script(&actions, &settings.0, &settings.1, &controls);
+            // Store the error before marking the run finished so status never reports a failure as completed
+            match result {
+                Ok(()) => info!("Automation completed successfully!"),
+                Err(e) => {
+                    warn!("Run failed: {}", e);
+                    *error.lock().unwrap() = Some(e.to_string());
+                },
+            }
+            controls.mark_finished();
+        });
+        Ok(())
+    }
+
+    pub fn status(&self) -> Status {
+        let current = self.current.lock().unwrap();
+        let Some(run) = current.as_ref() else {
+            return Status { state: "idle".to_string(), script: None, step: 0, total_steps: 0, error: None };
+        };
+
+        let error = run.error.lock().unwrap().clone();
+        let state = match (run.controls.is_finished(), run.controls.run_aborted(), &error) {
+            (true, true, _) => "stopped",
+            (true, false, Some(_)) => "failed",
+            (true, false, None) => "completed",
+            (false, _, _) => run.controls.state_name(),
+        };
+        Status {
+            state: state.to_string(),
+            script: Some(run.script.clone()),
+            step: run.controls.step(),
+            total_steps: run.controls.total_steps(),
+            error,
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.streaming.load(Ordering::SeqCst)
+            || self.recording.load(Ordering::SeqCst)
+            || self.current.lock().unwrap().as_ref().is_some_and(|run| !run.controls.is_finished())
+    }
+
+    // Apply a control action to the running script
+    pub fn with_active(&self, action: impl FnOnce(&Controls)) -> Result<(), RunError> {
+        match self.current.lock().unwrap().as_ref() {
+            Some(run) if !run.controls.is_finished() => {
+                action(&run.controls);
+                Ok(())
+            },
+            _ => Err(RunError::Idle),
+        }
+    }
+
+    // Prepare this machine to execute rows sent one at a time
+    pub fn stream(&self) -> Result<Stream<'_>, Box<dyn Error>> {
+        let mut current = self.current.lock().unwrap();
+        if current.as_ref().is_some_and(|run| !run.controls.is_finished()) {
+            return Err(RunError::Busy.into());
+        }
+
+        let (options, config) = (&self.settings.0, &self.settings.1);
+        // Streamed rows have no script file a signature could cover
+        if options.require_signature.is_some() || config.require_signature.is_some() {
+            return Err(RunError::Forbidden("this agent only runs signed scripts; push the script and run it instead".to_string()).into());
+        }
+        let policy = crate::build_policy(options, config)?;
+        // Claimed before the backend is opened, so a second controller can't open one too
+        if self.streaming.swap(true, Ordering::SeqCst) {
+            return Err("another controller is already playing back on this agent".into());
+        }
+        let release = || self.streaming.store(false, Ordering::SeqCst);
+        let backend = crate::backend_kind(options, config);
+        if !options.skip_preflight {
+            backend.preflight(false).inspect_err(|_| release())?;
+        }
+        // Registered as the current run so status, pause and stop reach it like any other
+        let controls = Controls::with_events(Arc::clone(&self.events));
+        let playback = crate::build_playback(options, config, &controls, backend).inspect_err(|_| release())?;
+
+        *current = Some(CurrentRun {
+            script: "(streamed)".to_string(),
+            controls: Arc::clone(&controls),
+            error: Arc::default(),
+        });
+        Ok(Stream { runs: self, playback, policy, controls })
+    }
+
+    // Record what is done on this machine into a new script and return its content
+    pub fn record(&self, name: &str, duration: Duration) -> Result<String, RunError> {
+        let path = script_path(&self.scripts_dir, name).map_err(|e| RunError::BadRequest(e.to_string()))?;
+        if duration.is_zero() || duration > MAX_RECORDING {
+            return Err(RunError::BadRequest(format!("recording duration must be 1 to {} seconds", MAX_RECORDING.as_secs())));
+        }
+        // Playback during a recording would be recorded too
+        if self.is_busy() || self.recording.swap(true, Ordering::SeqCst) {
+            return Err(RunError::Busy);
+        }
+        let result = recorder::record_for(&path, duration)
+            .and_then(|_| Ok(fs::read_to_string(&path)?))
+            .map_err(|e| RunError::Failed(e.to_string()));
+        self.recording.store(false, Ordering::SeqCst);
+        result
+    }
+
+    pub fn save_script(&self, name: &str, content: &str) -> Result<(), Box<dyn Error>> {
+        let path = script_path(&self.scripts_dir, name)?;
+        fs::write(&path, content)?;
+        info!("Saved uploaded script {}", path.display());
+        Ok(())
+    }
+
+    pub fn list_scripts(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.scripts_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "csv") {
+                if let Some(name) = path.file_name() {
+                    names.push(name.to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+// Rows executed one at a time on behalf of a remote `run --target`
+pub struct Stream<'a> {
+    runs: &'a Runs,
+    playback: Playback,
+    policy: Policy,
+    controls: Arc<Controls>,
+}
+
+impl Stream<'_> {
+    // One row, refused like it would be in a script started here
+    pub fn step(&mut self, step: usize, record: &MouseAction) -> Result<(), Box<dyn Error>> {
+        if self.controls.run_aborted() {
+            return Err("playback was stopped on this agent".into());
+        }
+        let category = policy::category(record);
+        if !self.policy.allows(record) {
+            return Err(format!("Step {}: {} ({}) is denied on this agent", step, record.action, category).into());
+        }
+        // Nobody is at the terminal to confirm dangerous actions
+        if !self.runs.settings.0.assume_yes && category.is_dangerous() {
+            return Err(RunError::Forbidden(format!("Step {}: {} is a dangerous action; start the agent with --yes to allow it", step, record.action)).into());
+        }
+
+        self.controls.wait_while_paused(step);
+        // The controller doesn't say how many rows are coming
+        self.controls.set_progress(step, step.max(self.controls.total_steps()));
+        self.playback.perform(step.saturating_sub(1), record)
+    }
+}
+
+impl Drop for Stream<'_> {
+    fn drop(&mut self) {
+        self.controls.mark_finished();
+        self.runs.streaming.store(false, Ordering::SeqCst);
+    }
+}
+
+// Resolve a script name inside the scripts directory, refusing anything that could escape it
+fn script_path(scripts_dir: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!("invalid script name '{}'", name).into());
+    }
+
+    let file_name = if name.ends_with(".csv") { name.to_string() } else { format!("{}.csv", name) };
+    Ok(scripts_dir.join(file_name))
+}
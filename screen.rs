@@ -0,0 +1,149 @@
+// Screen capture, for screenshot rows, failure screenshots, region and pixel
+// checks, and embedding images in reports.
+//
+// Captures the primary monitor at its native resolution.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use xcap::image::{imageops, RgbaImage};
+use xcap::Monitor;
+use tracing::warn;
+use crate::report;
+
+pub const DEFAULT_FAILURE_DIR: &str = "failures";
+pub const DEFAULT_SCREENSHOT: &str = "screenshots/screenshot.png";
+
+// A rectangle of the primary monitor, in pixels from its top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn primary() -> Result<Monitor, Box<dyn Error>> {
+    let monitors = Monitor::all().map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let monitor = monitors.iter()
+        .find(|monitor| monitor.is_primary())
+        .or(monitors.first())
+        .ok_or("No monitor to capture")?;
+    Ok(monitor.clone())
+}
+
+pub fn capture() -> Result<RgbaImage, Box<dyn Error>> {
+    Ok(primary()?.capture_image().map_err(|e| format!("Screen capture failed: {}", e))?)
+}
+
+// The primary monitor's size, found without going through the input system
+pub fn size() -> Option<(i32, i32)> {
+    let monitor = primary().ok()?;
+    Some((monitor.width() as i32, monitor.height() as i32)).filter(|&(width, height)| width > 0 && height > 0)
+}
+
+pub fn capture_region(region: Region) -> Result<RgbaImage, Box<dyn Error>> {
+    let screen = capture()?;
+    let fits = region.x >= 0 && region.y >= 0 && region.width > 0 && region.height > 0
+        && region.x as u64 + u64::from(region.width) <= u64::from(screen.width())
+        && region.y as u64 + u64::from(region.height) <= u64::from(screen.height());
+    if !fits {
+        return Err(format!("Region {}x{} at ({}, {}) is not within the {}x{} screen",
+            region.width, region.height, region.x, region.y, screen.width(), screen.height()).into());
+    }
+    Ok(imageops::crop_imm(&screen, region.x as u32, region.y as u32, region.width, region.height).to_image())
+}
+
+// The color of one pixel, as red, green and blue
+pub fn pixel(x: i32, y: i32) -> Result<[u8; 3], Box<dyn Error>> {
+    let [red, green, blue, _] = capture_region(Region { x, y, width: 1, height: 1 })?.get_pixel(0, 0).0;
+    Ok([red, green, blue])
+}
+
+// "#1e90ff" or "1e90ff"
+pub fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |start: usize| u8::from_str_radix(&hex[start..start + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+pub fn save(path: &Path) -> Result<(), Box<dyn Error>> {
+    write(&capture()?, path)
+}
+
+fn write(image: &RgbaImage, path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    image.save(path).map_err(|e| format!("Failed to save '{}': {}", path.display(), e))?;
+    Ok(())
+}
+
+// Save the screen or a region of it for a screenshot row, with the time added to
+// the file name: evidence/login.png becomes evidence/login-2024-05-01_13-45-09.png
+pub fn screenshot(path: &Path, region: Option<Region>) -> Result<PathBuf, Box<dyn Error>> {
+    let image = match region {
+        Some(region) => capture_region(region)?,
+        None => capture()?,
+    };
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("screenshot");
+    let time = report::utc_timestamp(SystemTime::now()).replace(' ', "_").replace(':', "-");
+    // Several screenshots in the same second, e.g. from a loop, get a counter
+    let mut stamped = path.with_file_name(format!("{}-{}.png", stem, time));
+    let mut count = 1;
+    while stamped.exists() {
+        count += 1;
+        stamped = path.with_file_name(format!("{}-{}-{}.png", stem, time, count));
+    }
+    write(&image, &stamped)?;
+    Ok(stamped)
+}
+
+// Capture the screen after a failed step, named for the step and its CSV row;
+// the time keeps earlier runs' captures
+pub fn failure_screenshot(dir: &Path, step: usize, row: usize) -> Option<PathBuf> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("step-{}-row-{}-{}.png", step, row, time));
+    match save(&path) {
+        Ok(()) => Some(path),
+        Err(error) => {
+            warn!("failed to capture a failure screenshot: {}", error);
+            None
+        },
+    }
+}
+
+// An image file as a `data:` URI, so SVG and HTML output stands alone
+pub fn data_uri(path: &Path) -> Result<String, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let mime_type = match path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    };
+    Ok(format!("data:{};base64,{}", mime_type, base64(&bytes)))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = u32::from(chunk[0]) << 16
+            | u32::from(*chunk.get(1).unwrap_or(&0)) << 8
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+        for position in 0..4 {
+            if position <= chunk.len() {
+                encoded.push(ALPHABET[(word >> (18 - 6 * position) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
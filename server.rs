@@ -0,0 +1,228 @@
+// HTTP API so other tools and dashboards can orchestrate playback.
+//
+//   GET  /status          state and progress of the current run
+//   GET  /scripts         scripts in the scripts directory
+//   PUT  /scripts/<name>  upload or replace a script (body is the CSV)
+//   POST /run             start a script, body {"script": "<name>"}
+//   POST /pause, /resume, /stop
+//   GET  /metrics         Prometheus metrics (see metrics.rs)
+//
+// With `--ws <address>` a WebSocket channel streaming progress events is
+// served as well (see websocket.rs), and with `--grpc <address>` the same
+// operations are offered over gRPC (see grpc.rs).
+//
+// A web page the user happens to visit can send requests to a local server
+// too, so a request that comes from a browser (it has an `Origin` header) is
+// refused unless its origin was allowed with `--allow-origin`, and POST /run
+// only takes a JSON body, which a page can't send to another origin without
+// asking first. With `--token-file` every request but GET /metrics must carry
+// the token as `Authorization: Bearer <token>`; without one the default
+// address only accepts local connections. One script runs at a time.
+
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::runs::{RunError, Runs};
+use crate::{grpc, metrics, webhook, websocket, Options};
+
+// Largest script accepted by PUT /scripts/<name>
+const MAX_UPLOAD_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct RunRequest {
+    script: String,
+}
+
+#[derive(Serialize)]
+struct Reply<'a> {
+    ok: bool,
+    message: &'a str,
+}
+
+// Who may use the API, the WebSocket channel and the gRPC service
+pub struct Access {
+    token: Option<String>,
+    // Origins of the pages allowed to send requests, e.g. "http://localhost:3000"
+    origins: Vec<String>,
+}
+
+impl Access {
+    pub fn new(token_file: Option<&str>, origins: &[String]) -> Result<Access, Box<dyn Error>> {
+        let token = token_file.map(|path| -> Result<String, Box<dyn Error>> {
+            let token = fs::read_to_string(path).map_err(|e| format!("Failed to read API token '{}': {}", path, e))?.trim().to_string();
+            if token.len() < 16 {
+                return Err(format!("API token in '{}' must be at least 16 characters", path).into());
+            }
+            Ok(token)
+        }).transpose()?;
+        let origins = origins.iter().map(|origin| origin.trim().trim_end_matches('/').to_string()).collect();
+        Ok(Access { token, origins })
+    }
+
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    // Tools send no origin; a browser sends the page's
+    pub fn origin_allowed(&self, origin: Option<&str>) -> bool {
+        origin.is_none_or(|origin| self.origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin.trim().trim_end_matches('/'))))
+    }
+
+    pub fn token_accepted(&self, presented: Option<&str>) -> bool {
+        match &self.token {
+            Some(token) => presented.is_some_and(|presented| webhook::constant_time_eq(presented.trim().as_bytes(), token.as_bytes())),
+            None => true,
+        }
+    }
+
+    // The status and reason to refuse a request with, if it is refused
+    fn check(&self, request: &Request) -> Result<(), (u16, &'static str)> {
+        if !self.origin_allowed(header(request, "Origin")) {
+            return Err((403, "requests from other web pages are refused; allow the page's origin with --allow-origin"));
+        }
+        if !self.token_accepted(header(request, "Authorization").and_then(|value| value.strip_prefix("Bearer "))) {
+            return Err((401, "bad or missing token"));
+        }
+        Ok(())
+    }
+}
+
+pub fn serve(address: &str, access: Access, options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    let (ws_address, grpc_address) = (options.ws_address.clone(), options.grpc_address.clone());
+    let runs = Runs::new(options, config)?;
+    let access = Arc::new(access);
+
+    let server = Server::http(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    info!("HTTP API listening on http://{} (scripts in {})", address, runs.scripts_dir().display());
+    if !access.has_token() && !is_loopback(address) {
+        warn!("{} accepts connections from other machines and there is no --token-file; anyone who can reach it can run scripts", address);
+    }
+
+    if let Some(ws_address) = &ws_address {
+        websocket::serve(ws_address, Arc::clone(&runs), Arc::clone(&access))?;
+    }
+    if let Some(grpc_address) = &grpc_address {
+        grpc::serve(grpc_address, Arc::clone(&runs), Arc::clone(&access))?;
+    }
+
+    for mut request in server.incoming_requests() {
+        if request.url() == "/metrics" {
+            let response = Response::from_string(runs.metrics().render()).with_header(metrics::content_type());
+            if let Err(error) = request.respond(response) {
+                warn!("failed to send HTTP response: {}", error);
+            }
+            continue;
+        }
+        let (status, body) = match access.check(&request) {
+            Ok(()) => route(&mut request, &runs),
+            Err((status, message)) => {
+                warn!("Refused {} {}: {}", request.method(), request.url(), message);
+                reply(status, false, message)
+            },
+        };
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(json_header());
+        if let Err(error) = request.respond(response) {
+            warn!("failed to send HTTP response: {}", error);
+        }
+    }
+    Ok(())
+}
+
+fn route(request: &mut Request, runs: &Runs) -> (u16, String) {
+    let url = request.url().split('?').next().unwrap_or("").to_string();
+    let method = request.method().clone();
+
+    match (method, url.as_str()) {
+        (Method::Get, "/status") => (200, to_json(&runs.status())),
+        (Method::Get, "/scripts") => match runs.list_scripts() {
+            Ok(names) => (200, to_json(&names)),
+            Err(error) => reply(500, false, &error.to_string()),
+        },
+        (Method::Put, path) if path.starts_with("/scripts/") => {
+            let name = &path["/scripts/".len()..];
+            match read_body(request).and_then(|body| runs.save_script(name, &body)) {
+                Ok(()) => reply(200, true, "script saved"),
+                Err(error) => reply(400, false, &error.to_string()),
+            }
+        },
+        (Method::Post, "/run") => start(request, runs),
+        (Method::Post, "/pause") => outcome(runs.with_active(|controls| controls.set_paused(true)), "paused"),
+        (Method::Post, "/resume") => outcome(runs.with_active(|controls| controls.request_resume()), "resumed"),
+        (Method::Post, "/stop") => outcome(runs.with_active(|controls| controls.request_abort()), "stopping"),
+        _ => reply(404, false, "not found"),
+    }
+}
+
+fn start(request: &mut Request, runs: &Runs) -> (u16, String) {
+    // A page can post a form or plain text to any origin, but not JSON
+    let json = header(request, "Content-Type").is_some_and(|value| value.trim().to_ascii_lowercase().starts_with("application/json"));
+    if !json {
+        return reply(415, false, "expected Content-Type: application/json");
+    }
+    let body = match read_body(request) {
+        Ok(body) => body,
+        Err(error) => return reply(400, false, &error.to_string()),
+    };
+    let run_request: RunRequest = match serde_json::from_str(&body) {
+        Ok(run_request) => run_request,
+        Err(error) => return reply(400, false, &format!("expected {{\"script\": \"<name>\"}}: {}", error)),
+    };
+
+    match runs.start(&run_request.script) {
+        Ok(()) => reply(202, true, "started"),
+        Err(error) => reply(error_status(&error), false, &error.to_string()),
+    }
+}
+
+fn outcome(result: Result<(), RunError>, done: &str) -> (u16, String) {
+    match result {
+        Ok(()) => reply(200, true, done),
+        Err(error) => reply(error_status(&error), false, &error.to_string()),
+    }
+}
+
+pub fn error_status(error: &RunError) -> u16 {
+    match error {
+        RunError::Busy | RunError::Idle => 409,
+        RunError::NotFound => 404,
+        RunError::BadRequest(_) => 400,
+        RunError::Invalid(_) => 422,
+        RunError::Forbidden(_) => 403,
+        RunError::Failed(_) => 500,
+    }
+}
+
+fn read_body(request: &mut Request) -> Result<String, Box<dyn Error>> {
+    let mut body = String::new();
+    request.as_reader().take(MAX_UPLOAD_BYTES).read_to_string(&mut body)?;
+    Ok(body)
+}
+
+pub fn reply(status: u16, ok: bool, message: &str) -> (u16, String) {
+    (status, to_json(&Reply { ok, message }))
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string())
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.as_str())
+}
+
+// Whether only this machine can connect to the address
+pub fn is_loopback(address: &str) -> bool {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    matches!(host.trim_matches(['[', ']']), "localhost" | "::1") || host.starts_with("127.")
+}
+
+pub fn json_header() -> Header {
+    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("static header is valid")
+}
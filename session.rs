@@ -0,0 +1,121 @@
+// Detection of a locked session or active screensaver.
+
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use tracing::info;
+use crate::controls::Controls;
+
+// How often the lock state is polled during playback
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+// What to do when the session locks mid-run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnLock {
+    // Wait until the session is unlocked, then continue
+    #[default]
+    Pause,
+    // Stop the run with an error
+    Abort,
+    // Keep injecting regardless
+    Ignore,
+}
+
+impl OnLock {
+    pub fn parse(name: &str) -> Option<OnLock> {
+        match name {
+            "pause" => Some(OnLock::Pause),
+            "abort" => Some(OnLock::Abort),
+            "ignore" => Some(OnLock::Ignore),
+            _ => None,
+        }
+    }
+}
+
+pub struct LockWatch {
+    on_lock: OnLock,
+    last_check: Option<Instant>,
+}
+
+impl LockWatch {
+    pub fn new(on_lock: OnLock) -> Self {
+        LockWatch { on_lock, last_check: None }
+    }
+
+    // Call before injecting an action; blocks or fails while the session is locked
+    pub fn check(&mut self, step: usize, controls: &Controls) -> Result<(), Box<dyn Error>> {
+        if self.on_lock == OnLock::Ignore {
+            return Ok(());
+        }
+        if self.last_check.is_some_and(|at| at.elapsed() < CHECK_INTERVAL) {
+            return Ok(());
+        }
+        self.last_check = Some(Instant::now());
+
+        if !is_locked() {
+            return Ok(());
+        }
+        if self.on_lock == OnLock::Abort {
+            return Err(format!("Session locked before step {}; aborting", step).into());
+        }
+
+        info!("Paused: the session is locked or the screensaver is active (before step {}).", step);
+        while is_locked() {
+            if controls.abort_requested() {
+                return Ok(());
+            }
+            controls.heartbeat();
+            thread::sleep(Duration::from_secs(1));
+        }
+        info!("Session unlocked. Resuming playback at step {}", step);
+        Ok(())
+    }
+}
+
+// Ask logind whether our session is locked
+#[cfg(target_os = "linux")]
+pub fn is_locked() -> bool {
+    use std::process::Command;
+
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint", "--value"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "yes")
+        .unwrap_or(false)
+}
+
+// The session dictionary in the IO registry carries the screen lock flag
+#[cfg(target_os = "macos")]
+pub fn is_locked() -> bool {
+    use std::process::Command;
+
+    Command::new("ioreg")
+        .args(["-n", "Root", "-d1"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("\"CGSSessionScreenIsLocked\"=Yes"))
+        .unwrap_or(false)
+}
+
+// The input desktop can't be opened while the lock screen or screensaver owns it
+#[cfg(windows)]
+pub fn is_locked() -> bool {
+    use windows_sys::Win32::System::StationsAndDesktops::{CloseDesktop, OpenInputDesktop, DESKTOP_SWITCHDESKTOP};
+
+    // SAFETY: the handle is only checked for null and closed again
+    unsafe {
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+        if desktop == 0 {
+            return true;
+        }
+        CloseDesktop(desktop);
+    }
+    false
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn is_locked() -> bool {
+    false
+}
@@ -0,0 +1,64 @@
+// Ed25519 signing of automation scripts.
+//
+// Keys and signatures are stored as hex text. A script's signature lives next
+// to it as `<script>.sig` and covers the exact bytes of the file.
+
+use std::error::Error;
+use std::fs;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use tracing::info;
+
+// Create a new key pair and write it to the given files
+pub fn keygen(secret_path: &str, public_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed)?;
+    let signing_key = SigningKey::from_bytes(&seed);
+
+    fs::write(secret_path, hex::encode(signing_key.to_bytes()))?;
+    fs::write(public_path, hex::encode(signing_key.verifying_key().to_bytes()))?;
+    info!("Wrote secret key to '{}' and public key to '{}'", secret_path, public_path);
+    Ok(())
+}
+
+// Sign a script with a secret key, writing `<script>.sig`
+pub fn sign(script_path: &str, secret_path: &str) -> Result<(), Box<dyn Error>> {
+    let signing_key = load_signing_key(secret_path)?;
+    let signature = signing_key.sign(&fs::read(script_path)?);
+
+    let signature_path = signature_path(script_path);
+    fs::write(&signature_path, hex::encode(signature.to_bytes()))?;
+    info!("Signed '{}' -> '{}'", script_path, signature_path);
+    Ok(())
+}
+
+// Check a script against its `<script>.sig` using a public key
+pub fn verify(script_path: &str, public_path: &str) -> Result<(), Box<dyn Error>> {
+    let verifying_key = load_verifying_key(public_path)?;
+
+    let signature_path = signature_path(script_path);
+    let signature = read_hex::<64>(&signature_path)
+        .map_err(|e| format!("Script '{}' is not signed ({}): {}", script_path, signature_path, e))?;
+
+    verifying_key.verify(&fs::read(script_path)?, &Signature::from_bytes(&signature))
+        .map_err(|_| format!("Signature check failed for '{}'", script_path))?;
+    Ok(())
+}
+
+pub fn load_signing_key(secret_path: &str) -> Result<SigningKey, Box<dyn Error>> {
+    Ok(SigningKey::from_bytes(&read_hex::<32>(secret_path)?))
+}
+
+pub fn load_verifying_key(public_path: &str) -> Result<VerifyingKey, Box<dyn Error>> {
+    Ok(VerifyingKey::from_bytes(&read_hex::<32>(public_path)?)?)
+}
+
+pub fn signature_path(script_path: &str) -> String {
+    format!("{}.sig", script_path)
+}
+
+// Read a hex file holding exactly N bytes
+fn read_hex<const N: usize>(path: &str) -> Result<[u8; N], Box<dyn Error>> {
+    let bytes = hex::decode(fs::read_to_string(path)?.trim())?;
+    bytes.try_into()
+        .map_err(|_| format!("'{}' does not contain a {}-byte hex value", path, N).into())
+}
@@ -0,0 +1,166 @@
+// Strokes: the points a drag_to or drawing row moves through with the button held.
+//
+// The `path` column of a drag_to row lists points as `x,y` separated by
+// spaces, and the drag passes through them in order on its way from its
+// start to its drop point: "300,420 500,380 640,400". With `bezier` in front
+// the points are control points instead, and the drag follows the smooth
+// curve they pull it along without passing through them: one control point
+// makes a quadratic curve, two a cubic one.
+//
+// The drawing rows generate their points instead, for whiteboard and
+// drawing applications:
+//
+// - draw_circle: centered on x_position,y_position, `width` across, or an
+//   ellipse `width` by `height`, starting and ending at its right edge
+// - draw_rectangle: from x_position,y_position, `width` by `height`
+// - draw_polyline: from x_position,y_position through the points of `path`,
+//   which may be a Bezier curve too
+//
+// Only the defining points (start, corners, path points) are translated onto
+// the desktop and checked against the bounds; the rest is worked out from
+// them. A stroke is traced at an even speed along its whole length, so a long
+// segment takes longer than a short one.
+
+use std::f64::consts::TAU;
+use crate::MouseAction;
+
+pub const SHAPES: &[&str] = &["draw_circle", "draw_rectangle", "draw_polyline"];
+
+// How long a stroke takes unless the row sets duration_ms; applications don't
+// take a button that jumps while held for a drag
+const DRAG_DURATION_MS: u64 = 300;
+const DRAW_DURATION_MS: u64 = 1000;
+// Segments a curve or circle is traced as
+const CURVE_SEGMENTS: u32 = 64;
+
+pub fn is_shape(action: &str) -> bool {
+    SHAPES.contains(&action)
+}
+
+pub fn duration_ms(record: &MouseAction) -> u64 {
+    record.duration_ms.unwrap_or(if is_shape(&record.action) { DRAW_DURATION_MS } else { DRAG_DURATION_MS })
+}
+
+pub struct Stroke {
+    pub points: Vec<(i32, i32)>,
+    pub bezier: bool,
+}
+
+impl Stroke {
+    pub fn parse(text: &str) -> Result<Stroke, String> {
+        let text = text.trim();
+        let (bezier, text) = match text.strip_prefix("bezier") {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let points = text.split_whitespace()
+            .map(|point| {
+                point.split_once(',')
+                    .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+                    .ok_or_else(|| format!("path point '{}' isn't x,y", point))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if points.is_empty() {
+            return Err("path needs at least one x,y point".to_string());
+        }
+        Ok(Stroke { points, bezier })
+    }
+
+    // The points to move through from `from`, ending at `to` or the last path point
+    pub fn polyline(&self, from: (i32, i32), to: Option<(i32, i32)>) -> Vec<(i32, i32)> {
+        let mut points = vec![from];
+        points.extend(&self.points);
+        points.extend(to);
+        if !self.bezier {
+            return points;
+        }
+        (0..=CURVE_SEGMENTS).map(|segment| bezier(&points, f64::from(segment) / f64::from(CURVE_SEGMENTS))).collect()
+    }
+}
+
+// The points a drag_to or drawing row moves through, with `place` putting
+// each defining point on the desktop
+pub fn outline(record: &MouseAction, mut place: impl FnMut(i32, i32) -> Result<(i32, i32), String>) -> Result<Vec<(i32, i32)>, String> {
+    let action = record.action.as_str();
+    let Some((x, y)) = record.x_position.zip(record.y_position) else {
+        return Err(match action {
+            "drag_to" => "drag_to needs x_position, y_position, to_x and to_y".to_string(),
+            _ => format!("{} needs x_position and y_position", action),
+        });
+    };
+    match action {
+        "draw_circle" => {
+            let width = record.width.ok_or("draw_circle needs a width, its diameter")? as i32;
+            let height = record.height.map_or(width, |height| height as i32);
+            let center = place(x, y)?;
+            let corner = place(x + width / 2, y + height / 2)?;
+            let (radius_x, radius_y) = (f64::from(corner.0 - center.0), f64::from(corner.1 - center.1));
+            Ok((0..=CURVE_SEGMENTS)
+                .map(|segment| {
+                    let angle = TAU * f64::from(segment) / f64::from(CURVE_SEGMENTS);
+                    (center.0 + (radius_x * angle.cos()).round() as i32, center.1 + (radius_y * angle.sin()).round() as i32)
+                })
+                .collect())
+        },
+        "draw_rectangle" => {
+            let (Some(width), Some(height)) = (record.width, record.height) else {
+                return Err("draw_rectangle needs a width and height".to_string());
+            };
+            let start = place(x, y)?;
+            let corner = place(x + width as i32 - 1, y + height as i32 - 1)?;
+            Ok(vec![start, (corner.0, start.1), corner, (start.0, corner.1), start])
+        },
+        "draw_polyline" => {
+            let start = place(x, y)?;
+            let stroke = placed_path(record, &mut place)?.ok_or("draw_polyline needs a path")?;
+            Ok(stroke.polyline(start, None))
+        },
+        _ => {
+            let (to_x, to_y) = record.to_x.zip(record.to_y).ok_or("drag_to needs x_position, y_position, to_x and to_y")?;
+            let (start, to) = (place(x, y)?, place(to_x, to_y)?);
+            Ok(match placed_path(record, &mut place)? {
+                Some(stroke) => stroke.polyline(start, Some(to)),
+                None => vec![start, to],
+            })
+        },
+    }
+}
+
+fn placed_path(record: &MouseAction, place: &mut impl FnMut(i32, i32) -> Result<(i32, i32), String>) -> Result<Option<Stroke>, String> {
+    let Some(path) = record.path.as_deref() else { return Ok(None) };
+    let mut stroke = Stroke::parse(path)?;
+    for point in &mut stroke.points {
+        *point = place(point.0, point.1)?;
+    }
+    Ok(Some(stroke))
+}
+
+// The point `fraction` of the way along a polyline, by length
+pub fn along(points: &[(i32, i32)], fraction: f64) -> (i32, i32) {
+    let lengths: Vec<f64> = points.windows(2).map(|pair| distance(pair[0], pair[1])).collect();
+    let mut remaining = lengths.iter().sum::<f64>() * fraction.clamp(0.0, 1.0);
+    for (pair, &length) in points.windows(2).zip(&lengths) {
+        if remaining <= length && length > 0.0 {
+            let progress = remaining / length;
+            return (
+                pair[0].0 + (f64::from(pair[1].0 - pair[0].0) * progress).round() as i32,
+                pair[0].1 + (f64::from(pair[1].1 - pair[0].1) * progress).round() as i32,
+            );
+        }
+        remaining -= length;
+    }
+    points.last().copied().unwrap_or_default()
+}
+
+fn distance(a: (i32, i32), b: (i32, i32)) -> f64 {
+    f64::from(b.0 - a.0).hypot(f64::from(b.1 - a.1))
+}
+
+// De Casteljau: blend neighboring control points until one is left
+fn bezier(points: &[(i32, i32)], t: f64) -> (i32, i32) {
+    let mut blended: Vec<(f64, f64)> = points.iter().map(|&(x, y)| (f64::from(x), f64::from(y))).collect();
+    while blended.len() > 1 {
+        blended = blended.windows(2).map(|pair| (pair[0].0 + (pair[1].0 - pair[0].0) * t, pair[0].1 + (pair[1].1 - pair[0].1) * t)).collect();
+    }
+    blended.first().map_or((0, 0), |&(x, y)| (x.round() as i32, y.round() as i32))
+}
@@ -0,0 +1,69 @@
+// Tera templates that render to a script, for scripts too repetitive to write
+// by hand.
+//
+// A script whose file name ends in `.tera` (grid.csv.tera) is rendered before
+// it is parsed, with every `--var`/`--set` value available to the template.
+// Values that look like numbers or booleans are passed as such, so
+// `{% for row in range(end=rows) %}` works with `--set rows=20`. Templates
+// can include others from the same directory.
+
+use std::error::Error;
+use std::path::Path;
+use serde::Serialize;
+use tera::{Context, Tera};
+use crate::vars::Vars;
+
+const EXTENSION: &str = "tera";
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Value<'a> {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Text(&'a str),
+}
+
+pub fn is_template(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == EXTENSION)
+}
+
+pub fn render(path: &Path, vars: &Vars) -> Result<String, Box<dyn Error>> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().ok_or_else(|| format!("'{}' is not a template file", path.display()))?;
+    // Load the whole directory so includes resolve
+    let tera = Tera::new(&format!("{}/**/*.{}", dir.display(), EXTENSION))
+        .map_err(|e| format!("Failed to load templates from '{}': {}", dir.display(), describe(&e)))?;
+
+    let mut context = Context::new();
+    for (key, value) in vars {
+        context.insert(key.as_str(), &typed(value));
+    }
+    let rendered = tera.render(&name.to_string_lossy(), &context)
+        .map_err(|e| format!("Failed to render '{}': {}", path.display(), describe(&e)))?;
+    Ok(rendered)
+}
+
+fn typed(value: &str) -> Value<'_> {
+    if let Ok(integer) = value.parse() {
+        Value::Integer(integer)
+    } else if let Ok(float) = value.parse() {
+        Value::Float(float)
+    } else if let Ok(boolean) = value.parse() {
+        Value::Bool(boolean)
+    } else {
+        Value::Text(value)
+    }
+}
+
+// Tera keeps the useful part of its errors (line, missing variable) in the source chain
+fn describe(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
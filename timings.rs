@@ -0,0 +1,124 @@
+// End-of-run timing report, for `--timings`.
+//
+// Compares what each row was planned to take with what it actually took,
+// over every time it ran. The plan comes from the row's columns: its delay,
+// the glide to its position, a click_and_hold's hold, the gaps between the
+// clicks of a double click and between typed characters, scaled by --speed.
+// Waits for images and windows are planned as taking no time at all, so they
+// show up as the rows running over. The actual time runs from the start of
+// the row's delay to the end of its action, retries included.
+//
+// The report is printed when the run ends, and written to `--timings-file`
+// too, with the total and the slowest rows at the end to show where a script
+// spends its time.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::time::{Duration, Instant};
+use tracing::info;
+use crate::{lint, stroke, MouseAction};
+
+// Rows listed as the slowest
+const SLOWEST: usize = 5;
+
+#[derive(Default)]
+struct RowTiming {
+    runs: u64,
+    actual: Duration,
+}
+
+pub struct Timings {
+    started: Instant,
+    move_duration: Duration,
+    speed: f64,
+    // By index, so the report lists rows in script order
+    rows: BTreeMap<usize, RowTiming>,
+}
+
+impl Timings {
+    pub fn new(move_duration: Duration, speed: f64) -> Timings {
+        Timings { started: Instant::now(), move_duration, speed, rows: BTreeMap::new() }
+    }
+
+    pub fn record(&mut self, index: usize, duration: Duration) {
+        let row = self.rows.entry(index).or_default();
+        row.runs += 1;
+        row.actual += duration;
+    }
+
+    // Print the report, and write it to `path` too if one is given
+    pub fn finish(&self, actions: &[MouseAction], path: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let text = self.render(actions)?;
+        print!("{}", text);
+        if let Some(path) = path {
+            fs::write(path, &text).map_err(|e| format!("Failed to write timings '{}': {}", path, e))?;
+            info!("Wrote timings to {}", path);
+        }
+        Ok(())
+    }
+
+    fn render(&self, actions: &[MouseAction]) -> Result<String, Box<dyn Error>> {
+        // Planned and actual milliseconds of every row that ran, over all its runs
+        let rows: Vec<(usize, &RowTiming, u64, u64)> = self.rows.iter()
+            .map(|(&index, row)| {
+                let planned = (planned_ms(&actions[index], self.move_duration) as f64 * self.speed) as u64 * row.runs;
+                (index, row, planned, row.actual.as_millis() as u64)
+            })
+            .collect();
+
+        let mut text = String::new();
+        writeln!(text, "Timings (planned vs actual):")?;
+        writeln!(text, "  {:>4}  {:>4}  {:<22} {:>5} {:>10} {:>10} {:>11}", "Step", "Row", "Action", "Runs", "Planned", "Actual", "Difference")?;
+        for &(index, row, planned, actual) in &rows {
+            writeln!(text, "  {:>4}  {:>4}  {:<22} {:>5} {:>10} {:>10} {:>11}", index + 1, lint::row_number(index), actions[index].action,
+                row.runs, format!("{} ms", planned), format!("{} ms", actual), format!("{:+} ms", actual as i64 - planned as i64))?;
+        }
+
+        let planned: u64 = rows.iter().map(|&(_, _, planned, _)| planned).sum();
+        let actual: u64 = rows.iter().map(|&(_, _, _, actual)| actual).sum();
+        writeln!(text, "  Total: planned {:.1}s, steps took {:.1}s, run took {:.1}s",
+            planned as f64 / 1000.0, actual as f64 / 1000.0, self.started.elapsed().as_secs_f64())?;
+
+        let mut slowest = rows.clone();
+        slowest.sort_by_key(|&(_, _, _, actual)| Reverse(actual));
+        writeln!(text, "  Slowest steps:")?;
+        for &(index, row, planned, actual) in slowest.iter().take(SLOWEST) {
+            let runs = if row.runs > 1 { format!(" over {} runs", row.runs) } else { String::new() };
+            writeln!(text, "    row {} ({}): {:.1}s{}, planned {:.1}s", lint::row_number(index), actions[index].action,
+                actual as f64 / 1000.0, runs, planned as f64 / 1000.0)?;
+        }
+        Ok(text)
+    }
+}
+
+// Milliseconds one run of a row is planned to take, before --speed
+pub fn planned_ms(record: &MouseAction, move_duration: Duration) -> u64 {
+    let repeats = u64::from(record.repeat_count.unwrap_or(1));
+    let moves = matches!(record.action.as_str(), "move" | "click" | "double_click" | "triple_click" | "click_and_hold" | "right_click" | "drag" | "release")
+        && record.x_position.is_some() && record.y_position.is_some();
+    let glide = if moves {
+        // A click_and_hold's duration_ms is how long it holds
+        let duration_ms = record.duration_ms.filter(|_| record.action != "click_and_hold");
+        duration_ms.unwrap_or(move_duration.as_millis() as u64) * repeats
+    } else if record.action == "drag_to" || stroke::is_shape(&record.action) {
+        (move_duration.as_millis() as u64 + stroke::duration_ms(record)) * repeats
+    } else if record.action == "scroll" {
+        record.duration_ms.unwrap_or(0)
+    } else {
+        0
+    };
+    let delay = record.delay_ms.unwrap_or(0) + glide;
+    match record.action.as_str() {
+        "double_click" => delay + record.interval_ms.unwrap_or(10) * repeats,
+        "triple_click" => delay + 2 * record.interval_ms.unwrap_or(10) * repeats,
+        "click_and_hold" => delay + record.duration_ms.unwrap_or(0) * repeats,
+        "type_text" | "snippet" => {
+            let characters = record.text.as_deref().map_or(0, |text| text.chars().count()) as u64;
+            delay + characters * record.interval_ms.unwrap_or(0) * repeats
+        },
+        _ => delay,
+    }
+}
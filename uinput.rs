@@ -0,0 +1,358 @@
+// Input through the kernel's /dev/uinput, for `--backend uinput` (Linux only).
+//
+// Two virtual devices are created, an absolute pointer with five buttons and
+// a wheel, and a keyboard, so events reach whatever reads input devices: the
+// console, a kiosk compositor or a Wayland session where XTest injection goes
+// nowhere. The pointer's axes span the screen in pixels, so a position lands
+// where it would with the other backends as long as the screen size is right.
+// uinput can't read the cursor back, so relative moves and saved positions
+// need a move to a position first, and cursor assertions, --restore-cursor,
+// --pause-on-user-input and --failsafe are refused. Keys are sent as the keys
+// of a US keyboard, which the session's layout then interprets, so text only
+// comes out as written on a US layout, and characters a US keyboard can't
+// type are left out with a warning.
+//
+// The devices need write access to /dev/uinput, which is root only on most
+// distributions; `check` explains the udev rule that grants it.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::mem;
+use enigo::{Key, MouseButton};
+use tracing::warn;
+use crate::backend::InputBackend;
+
+const PATH: &str = "/dev/uinput";
+
+// From linux/input-event-codes.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BTN_SIDE: u16 = 0x113;
+const BTN_EXTRA: u16 = 0x114;
+const KEY_LEFTSHIFT: u16 = 42;
+// Keyboard keys are the codes below the first button
+const BTN_MISC: u16 = 0x100;
+
+pub struct UinputBackend {
+    pointer: File,
+    keyboard: File,
+    size: (i32, i32),
+    cursor: (i32, i32),
+}
+
+impl UinputBackend {
+    #[cfg(target_os = "linux")]
+    pub fn open(size: (i32, i32)) -> Result<UinputBackend, Box<dyn Error>> {
+        use std::thread;
+        use std::time::Duration;
+        use tracing::info;
+
+        let pointer = linux::create("mouse-automation pointer", |device| {
+            linux::enable(device, linux::UI_SET_EVBIT, &[EV_KEY, EV_REL, EV_ABS])?;
+            linux::enable(device, linux::UI_SET_KEYBIT, &[BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA])?;
+            linux::enable(device, linux::UI_SET_RELBIT, &[REL_WHEEL, REL_HWHEEL])?;
+            linux::enable(device, linux::UI_SET_ABSBIT, &[ABS_X, ABS_Y])?;
+            linux::axis(device, ABS_X, size.0 - 1)?;
+            linux::axis(device, ABS_Y, size.1 - 1)
+        })?;
+        let keyboard = linux::create("mouse-automation keyboard", |device| {
+            linux::enable(device, linux::UI_SET_EVBIT, &[EV_KEY])?;
+            linux::enable(device, linux::UI_SET_KEYBIT, &(1..BTN_MISC).collect::<Vec<u16>>())
+        })?;
+        // Events sent before the session has picked up a new device are lost
+        thread::sleep(Duration::from_millis(250));
+        info!("Injecting through uinput on a {}x{} screen", size.0, size.1);
+        Ok(UinputBackend { pointer, keyboard, size, cursor: (0, 0) })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn open(_size: (i32, i32)) -> Result<UinputBackend, Box<dyn Error>> {
+        Err("--backend uinput is only supported on Linux".into())
+    }
+
+    // Scroll-wheel "buttons" 4-7 are wheel notches rather than presses
+    fn scroll(&mut self, button: MouseButton) {
+        match button {
+            MouseButton::ScrollUp => self.scroll_y(1),
+            MouseButton::ScrollDown => self.scroll_y(-1),
+            MouseButton::ScrollLeft => self.scroll_x(-1),
+            MouseButton::ScrollRight => self.scroll_x(1),
+            _ => {},
+        }
+    }
+
+    fn key(&mut self, code: u16, value: i32) {
+        emit(&mut self.keyboard, &[(EV_KEY, code, value)]);
+    }
+
+    // A key with shift held if the character needs it
+    fn tap(&mut self, key: Key) {
+        let Some((code, shifted)) = key_code(key) else {
+            warn!("{:?} can't be pressed through uinput", key);
+            return;
+        };
+        if shifted {
+            self.key(KEY_LEFTSHIFT, 1);
+        }
+        self.key(code, 1);
+        self.key(code, 0);
+        if shifted {
+            self.key(KEY_LEFTSHIFT, 0);
+        }
+    }
+}
+
+// Whether this user may create uinput devices, with how to fix it if not
+#[cfg(target_os = "linux")]
+pub fn check() -> Result<(), Box<dyn Error>> {
+    linux::open_device().map(drop)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn check() -> Result<(), Box<dyn Error>> {
+    Err("--backend uinput is only supported on Linux".into())
+}
+
+impl InputBackend for UinputBackend {
+    fn location(&self) -> (i32, i32) {
+        self.cursor
+    }
+
+    fn display_size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.cursor = (x.clamp(0, self.size.0 - 1), y.clamp(0, self.size.1 - 1));
+        emit(&mut self.pointer, &[(EV_ABS, ABS_X, self.cursor.0), (EV_ABS, ABS_Y, self.cursor.1)]);
+    }
+
+    fn move_relative(&mut self, dx: i32, dy: i32) {
+        self.move_to(self.cursor.0 + dx, self.cursor.1 + dy);
+    }
+
+    fn click(&mut self, button: MouseButton) {
+        self.button_down(button);
+        self.button_up(button);
+    }
+
+    fn button_down(&mut self, button: MouseButton) {
+        match button_code(button) {
+            Some(code) => emit(&mut self.pointer, &[(EV_KEY, code, 1)]),
+            None => self.scroll(button),
+        }
+    }
+
+    fn button_up(&mut self, button: MouseButton) {
+        if let Some(code) = button_code(button) {
+            emit(&mut self.pointer, &[(EV_KEY, code, 0)]);
+        }
+    }
+
+    fn scroll_x(&mut self, amount: i32) {
+        emit(&mut self.pointer, &[(EV_REL, REL_HWHEEL, amount)]);
+    }
+
+    fn scroll_y(&mut self, amount: i32) {
+        emit(&mut self.pointer, &[(EV_REL, REL_WHEEL, amount)]);
+    }
+
+    fn key_down(&mut self, key: Key) {
+        match key_code(key) {
+            Some((code, _)) => self.key(code, 1),
+            None => warn!("{:?} can't be pressed through uinput", key),
+        }
+    }
+
+    fn key_up(&mut self, key: Key) {
+        if let Some((code, _)) = key_code(key) {
+            self.key(code, 0);
+        }
+    }
+
+    fn key_click(&mut self, key: Key) {
+        self.tap(key);
+    }
+
+    fn key_sequence(&mut self, text: &str) {
+        for c in text.chars() {
+            match us_key(c) {
+                Some(_) => self.tap(Key::Layout(c)),
+                None => warn!("'{}' can't be typed through uinput, which types as a US keyboard", c),
+            }
+        }
+    }
+
+    fn reads_cursor(&self) -> bool {
+        false
+    }
+}
+
+// Events followed by the report that delivers them together
+fn emit(device: &mut File, events: &[(u16, u16, i32)]) {
+    // struct input_event: a timeval of two longs the kernel fills in, then type, code and value
+    let mut buffer = Vec::with_capacity((events.len() + 1) * (2 * mem::size_of::<usize>() + 8));
+    for &(kind, code, value) in events.iter().chain([(EV_SYN, SYN_REPORT, 0)].iter()) {
+        buffer.resize(buffer.len() + 2 * mem::size_of::<usize>(), 0);
+        buffer.extend_from_slice(&kind.to_ne_bytes());
+        buffer.extend_from_slice(&code.to_ne_bytes());
+        buffer.extend_from_slice(&value.to_ne_bytes());
+    }
+    if let Err(error) = device.write_all(&buffer) {
+        warn!("failed to inject through uinput: {}", error);
+    }
+}
+
+fn button_code(button: MouseButton) -> Option<u16> {
+    match button {
+        MouseButton::Left => Some(BTN_LEFT),
+        MouseButton::Right => Some(BTN_RIGHT),
+        MouseButton::Middle => Some(BTN_MIDDLE),
+        MouseButton::Back => Some(BTN_SIDE),
+        MouseButton::Forward => Some(BTN_EXTRA),
+        _ => None,
+    }
+}
+
+// The Linux key code for a key, and whether it needs shift
+fn key_code(key: Key) -> Option<(u16, bool)> {
+    let code = match key {
+        Key::Layout(c) => return us_key(c),
+        Key::Raw(code) => code,
+        Key::Escape => 1,
+        Key::Backspace => 14,
+        Key::Tab => 15,
+        Key::Return => 28,
+        Key::Control => 29,
+        Key::Shift => KEY_LEFTSHIFT,
+        Key::Alt | Key::Option => 56,
+        Key::Space => 57,
+        Key::CapsLock => 58,
+        Key::F1 => 59,
+        Key::F2 => 60,
+        Key::F3 => 61,
+        Key::F4 => 62,
+        Key::F5 => 63,
+        Key::F6 => 64,
+        Key::F7 => 65,
+        Key::F8 => 66,
+        Key::F9 => 67,
+        Key::F10 => 68,
+        Key::F11 => 87,
+        Key::F12 => 88,
+        Key::Home => 102,
+        Key::UpArrow => 103,
+        Key::PageUp => 104,
+        Key::LeftArrow => 105,
+        Key::RightArrow => 106,
+        Key::End => 107,
+        Key::DownArrow => 108,
+        Key::PageDown => 109,
+        Key::Delete => 111,
+        Key::Meta | Key::Command | Key::Super | Key::Windows => 125,
+        _ => return None,
+    };
+    Some((code, false))
+}
+
+// Where a character is on a US keyboard, and whether it needs shift
+fn us_key(c: char) -> Option<(u16, bool)> {
+    const LETTERS: [u16; 26] = [30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17, 45, 21, 44];
+    const UNSHIFTED: &str = "1234567890-=[];'`\\,./";
+    const SHIFTED: &str = "!@#$%^&*()_+{}:\"~|<>?";
+    const CODES: [u16; 21] = [2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 26, 27, 39, 40, 41, 43, 51, 52, 53];
+    match c {
+        'a'..='z' => Some((LETTERS[c as usize - 'a' as usize], false)),
+        'A'..='Z' => Some((LETTERS[c as usize - 'A' as usize], true)),
+        ' ' => Some((57, false)),
+        '\n' => Some((28, false)),
+        '\t' => Some((15, false)),
+        _ => UNSHIFTED.chars().position(|key| key == c).map(|index| (CODES[index], false))
+            .or_else(|| SHIFTED.chars().position(|key| key == c).map(|index| (CODES[index], true))),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::error::Error;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, ErrorKind};
+    use std::os::fd::AsRawFd;
+    use std::os::raw::{c_int, c_ulong};
+    use super::PATH;
+
+    // From linux/uinput.h: _IO('U', n) and _IOW('U', n, size)
+    const UI_DEV_CREATE: c_ulong = 0x5501;
+    const UI_DEV_SETUP: c_ulong = 0x405c_5503;
+    const UI_ABS_SETUP: c_ulong = 0x401c_5504;
+    pub const UI_SET_EVBIT: c_ulong = 0x4004_5564;
+    pub const UI_SET_KEYBIT: c_ulong = 0x4004_5565;
+    pub const UI_SET_RELBIT: c_ulong = 0x4004_5566;
+    pub const UI_SET_ABSBIT: c_ulong = 0x4004_5567;
+    const BUS_VIRTUAL: u16 = 0x06;
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    // SAFETY (for every call): `argument` is an integer, or a pointer to a
+    // live buffer of exactly the size the request encodes
+    fn control(device: &File, request: c_ulong, argument: usize) -> io::Result<()> {
+        if unsafe { ioctl(device.as_raw_fd(), request, argument) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn enable(device: &File, request: c_ulong, codes: &[u16]) -> io::Result<()> {
+        codes.iter().try_for_each(|&code| control(device, request, usize::from(code)))
+    }
+
+    // An absolute axis from 0 to `maximum`
+    pub fn axis(device: &File, code: u16, maximum: i32) -> io::Result<()> {
+        // struct uinput_abs_setup: the code, padding, then input_absinfo's value, minimum, maximum, fuzz, flat and resolution
+        let mut setup = [0u8; 28];
+        setup[0..2].copy_from_slice(&code.to_ne_bytes());
+        setup[12..16].copy_from_slice(&maximum.max(1).to_ne_bytes());
+        control(device, UI_ABS_SETUP, setup.as_ptr() as usize)
+    }
+
+    // A device set up by `configure`, created under `name`
+    pub fn create(name: &str, configure: impl FnOnce(&File) -> io::Result<()>) -> Result<File, Box<dyn Error>> {
+        let device = open_device()?;
+        configure(&device).map_err(|e| format!("Failed to set up the uinput device: {}", e))?;
+
+        // struct uinput_setup: input_id (bus, vendor, product, version), the name, ff_effects_max
+        let mut setup = [0u8; 92];
+        setup[0..2].copy_from_slice(&BUS_VIRTUAL.to_ne_bytes());
+        setup[8..8 + name.len()].copy_from_slice(name.as_bytes());
+        control(&device, UI_DEV_SETUP, setup.as_ptr() as usize)
+            .and_then(|()| control(&device, UI_DEV_CREATE, 0))
+            .map_err(|e| format!("Failed to create the uinput device: {} (uinput needs Linux 4.5 or later)", e))?;
+        Ok(device)
+    }
+
+    pub fn open_device() -> Result<File, Box<dyn Error>> {
+        OpenOptions::new().write(true).open(PATH).map_err(|error| match error.kind() {
+            ErrorKind::NotFound => format!("{} doesn't exist, so uinput isn't available.\n\
+                Fix: load the module with `sudo modprobe uinput`, and add `uinput` to /etc/modules-load.d/uinput.conf to load it at boot.", PATH).into(),
+            ErrorKind::PermissionDenied => format!("This user may not write to {}, so no input device can be created.\n\
+                Fix: allow the input group with a udev rule, e.g. in /etc/udev/rules.d/60-mouse-automation.rules:\n    \
+                KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"\n\
+                then run `sudo udevadm control --reload && sudo udevadm trigger`, \
+                add yourself to the group with `sudo usermod -aG input $USER` and log in again.", PATH).into(),
+            _ => format!("Failed to open {}: {}", PATH, error).into(),
+        })
+    }
+}
@@ -0,0 +1,232 @@
+// `${name}` placeholders in script fields, filled in before a row is parsed.
+//
+// Values come from `--var name=value`, from triggers (e.g. the file that a
+// folder watch picked up) and from `set_var` rows earlier in the script.
+// Substitution happens per CSV field, so values may contain commas or quotes.
+// Numeric columns may then hold integer arithmetic such as `${base_x}+40`,
+// which is worked out before the row is parsed. Positions may also be
+// percentages of the screen, or of the row's monitor: `50%` of the width in
+// `x_position` and `to_x`, of the height in `y_position` and `to_y`, measured
+// when the script loads.
+
+use std::collections::BTreeMap;
+use csv::StringRecord;
+use crate::monitors::Layout;
+
+pub type Vars = BTreeMap<String, String>;
+
+// Deepest nesting of parentheses and minus signs an expression may have
+const MAX_DEPTH: usize = 64;
+
+// Columns where arithmetic is worked out
+const NUMERIC_COLUMNS: &[&str] = &[
+    "x_position", "y_position", "delay_ms", "repeat_count", "interval_ms", "width", "height", "tolerance", "monitor",
+    "to_x", "to_y", "retries", "retry_delay_ms",
+];
+
+// Split a `name=value` assignment
+pub fn parse_assignment(text: &str) -> Option<(String, String)> {
+    let (name, value) = text.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((name.to_string(), value.to_string()))
+}
+
+// Replace every `${name}`; an unknown name is an error rather than silently left in
+pub fn substitute(text: &str, vars: &Vars) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| format!("unterminated '${{' in '{}'", text))?;
+        let name = &after[..end];
+        let value = vars.get(name).ok_or_else(|| format!("undefined variable '{}' (pass --var {}=... or set it with an earlier set_var)", name, name))?;
+        result.push_str(value);
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+// Substitute every field of a row, keeping its position for error messages
+pub fn substitute_record(row: &StringRecord, vars: &Vars) -> Result<StringRecord, String> {
+    let mut substituted = StringRecord::new();
+    for field in row.iter() {
+        substituted.push_field(&substitute(field, vars)?);
+    }
+    substituted.set_position(row.position().cloned());
+    Ok(substituted)
+}
+
+// Work out arithmetic and percentages in the numeric columns of a row
+pub fn evaluate_record(row: &StringRecord, headers: &StringRecord, screen: &mut Layout) -> Result<StringRecord, String> {
+    let monitor = headers.iter().position(|column| column == "monitor")
+        .and_then(|index| row.get(index))
+        .and_then(|field| field.trim().parse().ok());
+    let mut evaluated = StringRecord::new();
+    for (column, field) in headers.iter().zip(row.iter()) {
+        let plain = field.trim().is_empty() || field.trim().parse::<i64>().is_ok();
+        if plain || !NUMERIC_COLUMNS.contains(&column) {
+            evaluated.push_field(field);
+        } else {
+            let value = evaluate_column(column, field, monitor, screen).map_err(|e| format!("{} '{}': {}", column, field, e))?;
+            evaluated.push_field(&value.to_string());
+        }
+    }
+    evaluated.set_position(row.position().cloned());
+    Ok(evaluated)
+}
+
+pub fn is_numeric_column(column: &str) -> bool {
+    NUMERIC_COLUMNS.contains(&column)
+}
+
+// A numeric field's value, with percentages of the screen turned into pixels first
+pub fn evaluate_column(column: &str, text: &str, monitor: Option<u32>, screen: &mut Layout) -> Result<i64, String> {
+    if !text.contains('%') {
+        return evaluate(text);
+    }
+    let (width, height) = screen.size(monitor).map_err(|e| format!("percentages need the screen size: {}", e))?;
+    let extent = match column {
+        "x_position" | "to_x" => width,
+        "y_position" | "to_y" => height,
+        _ => return Err("only positions can be percentages".to_string()),
+    };
+    evaluate(&percentages(text, extent)?)
+}
+
+// Replace each "12.5%" with that share of `extent` pixels; 100% is the last pixel
+fn percentages(text: &str, extent: i32) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(end) = rest.find('%') {
+        let start = rest[..end].rfind(|c: char| !c.is_ascii_digit() && c != '.').map_or(0, |index| index + 1);
+        let percent: f64 = rest[start..end].parse().map_err(|_| "'%' needs a number before it".to_string())?;
+        result.push_str(&rest[..start]);
+        result.push_str(&((f64::from(extent - 1) * percent / 100.0).round() as i64).to_string());
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+// Integer arithmetic with + - * / and parentheses, e.g. "(100+40)*2"
+pub fn evaluate(text: &str) -> Result<i64, String> {
+    let tokens: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut position = 0;
+    let value = sum(&tokens, &mut position, 0)?;
+    match tokens.get(position) {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}'", c)),
+    }
+}
+
+fn sum(tokens: &[char], position: &mut usize, depth: usize) -> Result<i64, String> {
+    let mut value = product(tokens, position, depth)?;
+    while let Some(&operator @ ('+' | '-')) = tokens.get(*position) {
+        *position += 1;
+        let right = product(tokens, position, depth)?;
+        value = if operator == '+' { value.checked_add(right) } else { value.checked_sub(right) }.ok_or("overflow")?;
+    }
+    Ok(value)
+}
+
+fn product(tokens: &[char], position: &mut usize, depth: usize) -> Result<i64, String> {
+    let mut value = factor(tokens, position, depth)?;
+    while let Some(&operator @ ('*' | '/')) = tokens.get(*position) {
+        *position += 1;
+        let right = factor(tokens, position, depth)?;
+        value = if operator == '*' { value.checked_mul(right) } else { value.checked_div(right) }.ok_or("overflow or division by zero")?;
+    }
+    Ok(value)
+}
+
+fn factor(tokens: &[char], position: &mut usize, depth: usize) -> Result<i64, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("nested more than {} deep", MAX_DEPTH));
+    }
+    match tokens.get(*position) {
+        Some('-') => {
+            *position += 1;
+            factor(tokens, position, depth + 1)?.checked_neg().ok_or_else(|| "overflow".to_string())
+        },
+        Some('(') => {
+            *position += 1;
+            let value = sum(tokens, position, depth + 1)?;
+            if tokens.get(*position) != Some(&')') {
+                return Err("missing ')'".to_string());
+            }
+            *position += 1;
+            Ok(value)
+        },
+        Some(c) if c.is_ascii_digit() => {
+            let start = *position;
+            while tokens.get(*position).is_some_and(char::is_ascii_digit) {
+                *position += 1;
+            }
+            tokens[start..*position].iter().collect::<String>().parse().map_err(|_| "number too large".to_string())
+        },
+        Some(c) => Err(format!("unexpected '{}'", c)),
+        None => Err("expression ends early".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_follows_precedence() {
+        assert_eq!(evaluate("100+40*2"), Ok(180));
+        assert_eq!(evaluate("(100+40)*2"), Ok(280));
+        assert_eq!(evaluate("10-4-3"), Ok(3));
+        assert_eq!(evaluate("7/2*2"), Ok(6));
+        assert_eq!(evaluate("-3*-(2+1)"), Ok(9));
+        assert_eq!(evaluate(" 1 + 2 "), Ok(3));
+    }
+
+    #[test]
+    fn evaluate_rejects_bad_expressions() {
+        assert!(evaluate("10/0").is_err());
+        assert!(evaluate("10/(5-5)").is_err());
+        assert!(evaluate("(1+2").is_err());
+        assert!(evaluate("1+").is_err());
+        assert!(evaluate("2x").is_err());
+    }
+
+    #[test]
+    fn evaluate_reports_overflow() {
+        assert_eq!(evaluate("-(0-9223372036854775807-1)"), Err("overflow".to_string()));
+        assert_eq!(evaluate("9223372036854775807+1"), Err("overflow".to_string()));
+        assert!(evaluate("9223372036854775807*2").is_err());
+        assert!(evaluate("(0-9223372036854775807-1)/-1").is_err());
+        assert!(evaluate("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn evaluate_limits_nesting() {
+        assert_eq!(evaluate(&format!("{}1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH))), Ok(1));
+        assert!(evaluate(&format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000))).is_err());
+        assert!(evaluate(&format!("{}1", "-".repeat(10_000))).is_err());
+    }
+
+    #[test]
+    fn substitute_fills_in_known_names_only() {
+        let vars = Vars::from([("base_x".to_string(), "100".to_string())]);
+        assert_eq!(substitute("${base_x}+40", &vars), Ok("100+40".to_string()));
+        assert!(substitute("${base_y}+40", &vars).unwrap_err().contains("undefined variable 'base_y'"));
+        assert!(substitute("${base_x", &vars).is_err());
+    }
+
+    #[test]
+    fn percentages_scale_to_the_last_pixel() {
+        assert_eq!(percentages("0%", 1920), Ok("0".to_string()));
+        assert_eq!(percentages("50%", 1921), Ok("960".to_string()));
+        assert_eq!(percentages("100%", 1080), Ok("1079".to_string()));
+        assert_eq!(percentages("12.5%+10", 801), Ok("100+10".to_string()));
+        assert!(percentages("%", 1920).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+// Screen recording of playback through ffmpeg, for `--record-video`.
+//
+// ffmpeg must be on the PATH. The whole screen is captured (x11grab on
+// Linux, avfoundation on macOS, gdigrab on Windows); on Windows a single
+// window can be captured instead by its title. Recording stops, and the file
+// is finalized, when the VideoRecording is dropped.
+
+use std::error::Error;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const FRAME_RATE: &str = "15";
+// How long ffmpeg gets to finish writing the file before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct VideoRecording {
+    ffmpeg: Child,
+    path: String,
+}
+
+impl VideoRecording {
+    // `screen` is the capture size, which x11grab needs
+    pub fn start(path: &str, window: Option<&str>, screen: (i32, i32)) -> Result<VideoRecording, Box<dyn Error>> {
+        let mut command = Command::new("ffmpeg");
+        command.args(["-y", "-loglevel", "error", "-framerate", FRAME_RATE]);
+        input_args(&mut command, window, screen)?;
+        command.args(["-c:v", "libx264", "-preset", "ultrafast", "-pix_fmt", "yuv420p", path]);
+
+        let ffmpeg = command.stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start ffmpeg for --record-video (is it installed and on the PATH?): {}", e))?;
+        info!("Recording the screen to {}", path);
+        Ok(VideoRecording { ffmpeg, path: path.to_string() })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn input_args(command: &mut Command, window: Option<&str>, (width, height): (i32, i32)) -> Result<(), Box<dyn Error>> {
+    if window.is_some() {
+        return Err("--video-window is only supported on Windows".into());
+    }
+    let display = std::env::var("DISPLAY").unwrap_or_else(|_| ":0".to_string());
+    command.args(["-f", "x11grab", "-video_size", &format!("{}x{}", width, height), "-i", &display]);
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn input_args(command: &mut Command, window: Option<&str>, _screen: (i32, i32)) -> Result<(), Box<dyn Error>> {
+    if window.is_some() {
+        return Err("--video-window is only supported on Windows".into());
+    }
+    // The terminal needs the Screen Recording permission
+    command.args(["-f", "avfoundation", "-capture_cursor", "1", "-i", "Capture screen 0:none"]);
+    Ok(())
+}
+
+#[cfg(windows)]
+fn input_args(command: &mut Command, window: Option<&str>, _screen: (i32, i32)) -> Result<(), Box<dyn Error>> {
+    let input = match window {
+        Some(title) => format!("title={}", title),
+        None => "desktop".to_string(),
+    };
+    command.args(["-f", "gdigrab", "-i", &input]);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn input_args(_command: &mut Command, _window: Option<&str>, _screen: (i32, i32)) -> Result<(), Box<dyn Error>> {
+    Err("--record-video is not supported on this platform".into())
+}
+
+impl Drop for VideoRecording {
+    fn drop(&mut self) {
+        // `q` asks ffmpeg to stop and write the file's index; killing it would leave an unplayable file
+        if let Some(stdin) = self.ffmpeg.stdin.as_mut() {
+            let _ = stdin.write_all(b"q");
+        }
+        let started = Instant::now();
+        while started.elapsed() < STOP_TIMEOUT {
+            match self.ffmpeg.try_wait() {
+                Ok(Some(status)) if status.success() => {
+                    info!("Saved screen recording to {}", self.path);
+                    return;
+                },
+                Ok(Some(status)) => {
+                    warn!("ffmpeg exited with {} while recording {}", status, self.path);
+                    return;
+                },
+                Ok(None) => thread::sleep(Duration::from_millis(100)),
+                Err(_) => break,
+            }
+        }
+        warn!("ffmpeg didn't stop in time; {} may be incomplete", self.path);
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+    }
+}
@@ -0,0 +1,176 @@
+// SVG rendering of a script's trajectory, the `visualize` subcommand.
+//
+// Follows the cursor through the script and draws moves as thin lines, drags
+// as thick red lines, clicks as circles colored by button (a ring for double
+// clicks) and scrolls as arrows, each labeled with its step number. An
+// optional screenshot is embedded as the background. `--dry-run --overlay`
+// draws the same over a capture of the live screen.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+use enigo::MouseButton;
+use tracing::info;
+use crate::stroke;
+use crate::{parse_button, screen, scroll_direction, MouseAction};
+
+// Padding when the canvas is sized to fit the drawing
+const MARGIN: i32 = 40;
+
+pub fn render(actions: &[MouseAction], output: &str, background: Option<&str>, screen: Option<(i32, i32)>) -> Result<(), Box<dyn Error>> {
+    let (shapes, extent) = draw(actions, 0..actions.len(), |_, x, y| Ok((x, y)))?;
+    let (width, height) = match background.and_then(png_size).or(screen) {
+        Some(size) => size,
+        None => (extent.0 + MARGIN, extent.1 + MARGIN),
+    };
+    let mut svg = String::new();
+    writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height)?;
+    match background {
+        Some(path) => {
+            let image = screen::data_uri(Path::new(path)).map_err(|e| format!("Failed to read background '{}': {}", path, e))?;
+            writeln!(svg, "  <image x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" href=\"{}\" />", width, height, image)?;
+        },
+        None => writeln!(svg, "  <rect width=\"100%\" height=\"100%\" fill=\"white\" />")?,
+    }
+    svg.push_str(&shapes);
+    svg.push_str("</svg>\n");
+
+    fs::write(output, svg).map_err(|e| format!("Failed to write '{}': {}", output, e))?;
+    info!("Wrote {} ({}x{})", output, width, height);
+    Ok(())
+}
+
+// The rows a dry run plays drawn translucently over a capture of the live
+// screen, for `--overlay`; `place` puts a row's coordinates on the desktop as
+// playback would, and rows it can't place aren't drawn
+pub fn overlay(actions: &[MouseAction], rows: Range<usize>, output: &str, place: impl FnMut(&MouseAction, i32, i32) -> Result<(i32, i32), String>) -> Result<(), Box<dyn Error>> {
+    let (shapes, _) = draw(actions, rows, place)?;
+    // The capture goes through a file to be embedded like a --background
+    let capture = env::temp_dir().join(format!("mouse-automation-overlay-{}.png", process::id()));
+    screen::save(&capture)?;
+    let image = screen::data_uri(&capture);
+    let _ = fs::remove_file(&capture);
+    let image = image.map_err(|e| format!("Failed to read the screen capture: {}", e))?;
+    let (width, height) = screen::size().ok_or("Failed to find the screen size")?;
+
+    let mut svg = String::new();
+    writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height)?;
+    writeln!(svg, "  <image x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" href=\"{}\" />", width, height, image)?;
+    writeln!(svg, "  <g opacity=\"0.7\">")?;
+    svg.push_str(&shapes);
+    svg.push_str("  </g>\n</svg>\n");
+
+    fs::write(output, svg).map_err(|e| format!("Failed to write overlay '{}': {}", output, e))?;
+    println!("Dry run: wrote the overlay to {}", output);
+    Ok(())
+}
+
+// Shapes for the rows in `rows`, labeled with their step numbers, and the furthest point drawn
+fn draw(actions: &[MouseAction], rows: Range<usize>, mut place: impl FnMut(&MouseAction, i32, i32) -> Result<(i32, i32), String>) -> Result<(String, (i32, i32)), Box<dyn Error>> {
+    let mut shapes = String::new();
+    let mut position: Option<(i32, i32)> = None;
+    let mut dragging = false;
+    // Furthest point drawn, to size the canvas without a background
+    let mut extent = (0, 0);
+
+    for (index, record) in actions.iter().enumerate().take(rows.end).skip(rows.start) {
+        let step = index + 1;
+        let repeats = record.repeat_count.unwrap_or(1) as i32;
+        let coordinates = record.x_position.zip(record.y_position);
+        let placed = match record.action.as_str() {
+            "move_relative" => Ok(position.zip(coordinates).map(|((x, y), (dx, dy))| (x + dx * repeats, y + dy * repeats))),
+            "scroll" | "wait" | "checkpoint" | "assert_region_matches" | "if_pixel" | "assert_pixel" | "assert_cursor_position" | "screenshot" | "move_window" => Ok(None),
+            // A circle starts at its edge rather than its center
+            action if stroke::is_shape(action) => stroke::outline(record, |x, y| place(record, x, y)).map(|points| Some(points[0])),
+            _ => coordinates.map(|(x, y)| place(record, x, y)).transpose(),
+        };
+        // Where the cursor is after a row that can't be placed isn't known
+        let Ok(target) = placed else {
+            position = None;
+            continue;
+        };
+
+        if let Some((x, y)) = target {
+            if let Some((from_x, from_y)) = position.filter(|&from| from != (x, y)) {
+                let style = if dragging { "stroke=\"#d62728\" stroke-width=\"4\"" } else { "stroke=\"#1f77b4\" stroke-width=\"1.5\" stroke-dasharray=\"6 3\"" };
+                writeln!(shapes, "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {} />", from_x, from_y, x, y, style)?;
+            }
+            position = Some((x, y));
+            extent = (extent.0.max(x), extent.1.max(y));
+        }
+
+        let Some((x, y)) = position else { continue };
+        match record.action.as_str() {
+            "click" | "double_click" | "triple_click" | "click_and_hold" | "right_click" => {
+                let button = if record.action == "right_click" { "right" } else { record.button.as_deref().unwrap_or("left") };
+                let color = match parse_button(button) {
+                    Some(MouseButton::Right) => "#ff7f0e",
+                    Some(MouseButton::Middle) => "#2ca02c",
+                    Some(MouseButton::Back | MouseButton::Forward) => "#7f7f7f",
+                    _ => "#1f77b4",
+                };
+                writeln!(shapes, "  <circle cx=\"{}\" cy=\"{}\" r=\"6\" fill=\"{}\" fill-opacity=\"0.8\" />", x, y, color)?;
+                if record.action == "double_click" || record.action == "triple_click" {
+                    writeln!(shapes, "  <circle cx=\"{}\" cy=\"{}\" r=\"10\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\" />", x, y, color)?;
+                }
+                if record.action == "triple_click" {
+                    writeln!(shapes, "  <circle cx=\"{}\" cy=\"{}\" r=\"14\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\" />", x, y, color)?;
+                }
+                label(&mut shapes, x, y, step)?;
+            },
+            "drag" => {
+                dragging = true;
+                writeln!(shapes, "  <rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"#d62728\" />", x - 5, y - 5)?;
+                label(&mut shapes, x, y, step)?;
+            },
+            "drag_to" | "draw_circle" | "draw_rectangle" | "draw_polyline" => {
+                writeln!(shapes, "  <rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"#d62728\" />", x - 5, y - 5)?;
+                label(&mut shapes, x, y, step)?;
+                if let Ok(points) = stroke::outline(record, |x, y| place(record, x, y)) {
+                    let listed: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+                    writeln!(shapes, "  <polyline points=\"{}\" fill=\"none\" stroke=\"#d62728\" stroke-width=\"4\" />", listed.join(" "))?;
+                    for &(x, y) in &points {
+                        extent = (extent.0.max(x), extent.1.max(y));
+                    }
+                    let (end_x, end_y) = points[points.len() - 1];
+                    writeln!(shapes, "  <rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"none\" stroke=\"#d62728\" stroke-width=\"2\" />", end_x - 5, end_y - 5)?;
+                    position = Some((end_x, end_y));
+                }
+            },
+            "release" => {
+                dragging = false;
+                writeln!(shapes, "  <rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"none\" stroke=\"#d62728\" stroke-width=\"2\" />", x - 5, y - 5)?;
+                label(&mut shapes, x, y, step)?;
+            },
+            "scroll" => {
+                // Points along the scroll direction
+                let (dx, dy) = scroll_direction(record);
+                let (tip_x, tip_y) = (x + dx * 12, y - dy * 12);
+                let (side_x, side_y) = (dy.abs() * 7, dx.abs() * 7);
+                writeln!(shapes, "  <polygon points=\"{},{} {},{} {},{}\" fill=\"#9467bd\" />", x - side_x, y - side_y, x + side_x, y + side_y, tip_x, tip_y)?;
+                label(&mut shapes, x, y, step)?;
+            },
+            _ => {},
+        }
+    }
+
+    Ok((shapes, extent))
+}
+
+fn label(shapes: &mut String, x: i32, y: i32, step: usize) -> std::fmt::Result {
+    writeln!(shapes, "  <text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"11\" fill=\"#333\">{}</text>", x + 9, y - 9, step)
+}
+
+// Width and height from a PNG's IHDR chunk; other formats fall back to the screen size
+fn png_size(path: &str) -> Option<(i32, i32)> {
+    let header = fs::read(path).ok()?;
+    if header.get(..8)? != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let number = |at: usize| -> Option<i32> { Some(u32::from_be_bytes(header.get(at..at + 4)?.try_into().ok()?) as i32) };
+    Some((number(16)?, number(20)?))
+}
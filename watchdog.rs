@@ -0,0 +1,110 @@
+// Detects playback that has stopped making progress, or run for too long.
+//
+// Every step is given a budget from what it asks for: its delay, the moves,
+// holds, strokes and typing it plays, the timeout of a wait for an image or a
+// window, and STEP_ALLOWANCE on top for the injection itself. A failed step's
+// retries each get the budget again, plus their backoff. A step that runs for
+// more than `--watchdog` times its budget is stuck, e.g. a vision wait that
+// polls forever because of a bug, even though it keeps sleeping between polls.
+// Pauses it didn't ask for (paused over control, for user input, a lock screen
+// or a foreground condition) restart its clock. The watchdog stops the step,
+// whose waits and sleeps return as they do on an abort, and the step fails
+// like any other: it is retried if the row has retries, and --keep-going and
+// --test carry on past it. If the stuck call never returns, the process exits
+// once the step has run for twice its limit.
+//
+// The deadline for `--max-duration` is wall-clock time instead, pauses
+// included, so CI gets a hard upper bound on a run. It aborts the same way,
+// which lets the run release its held buttons and keys, and exits after
+// DEADLINE_GRACE if the current step doesn't return.
+
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+use crate::controls::Controls;
+use crate::{locate, stroke, window, MouseAction};
+
+// How long an aborted run gets to wind down after its deadline
+const DEADLINE_GRACE: Duration = Duration::from_secs(10);
+// Budget for injecting a step on top of what it asks for
+const STEP_ALLOWANCE: Duration = Duration::from_secs(5);
+
+pub fn spawn(controls: Arc<Controls>, multiple: f64) {
+    thread::spawn(move || {
+        let mut tripped = false;
+        loop {
+            thread::sleep(Duration::from_millis(250));
+            if controls.is_finished() {
+                return;
+            }
+
+            let budget = controls.step_budget();
+            let limit = Duration::try_from_secs_f64(budget.as_secs_f64() * multiple).unwrap_or(Duration::MAX);
+            let stalled = controls.since_progress();
+            if stalled < limit {
+                tripped = false;
+                continue;
+            }
+
+            if !tripped {
+                let reason = format!("stopped by the watchdog after {:.1}s, over {} times the {:.1}s the step should take",
+                    stalled.as_secs_f64(), multiple, budget.as_secs_f64());
+                warn!("Watchdog: step {} {}", controls.step(), reason);
+                controls.cancel_step(reason);
+                tripped = true;
+            } else if stalled >= limit.saturating_mul(2) {
+                error!("Watchdog: step {} is still stuck after being stopped; exiting. \
+                    Mouse buttons held by the script may need to be released by hand.", controls.step());
+                process::exit(2);
+            }
+        }
+    });
+}
+
+// How long one attempt at a row should take at most, not counting its delay
+pub fn budget(record: &MouseAction, move_duration: Duration, delay_scale: f64) -> Duration {
+    let action = record.action.as_str();
+    // Moves are scaled by --speed; a click_and_hold's hold isn't, so slowing down never shortens the budget
+    let motion = match action {
+        "drag_to" | "draw_circle" | "draw_rectangle" | "draw_polyline" => Duration::from_millis(stroke::duration_ms(record)),
+        _ => record.duration_ms.map_or(move_duration, Duration::from_millis),
+    };
+    let typing = Duration::from_millis(record.interval_ms.unwrap_or(0))
+        .saturating_mul(record.text.as_deref().map_or(0, |text| text.chars().count() as u32));
+    let wait = match action {
+        "wait_for_image" => record.timeout_ms.map_or(locate::DEFAULT_TIMEOUT, Duration::from_millis),
+        // A launched application gets the default timeout to open its window too
+        _ if window::is_window_action(action) => record.timeout_ms.map_or(window::DEFAULT_TIMEOUT, Duration::from_millis),
+        _ => Duration::ZERO,
+    };
+    let played = scaled(motion.saturating_add(typing), delay_scale.max(1.0)).saturating_mul(record.repeat_count.unwrap_or(1).max(1));
+    STEP_ALLOWANCE.saturating_add(played).saturating_add(wait)
+}
+
+// `duration` times `factor`, as long as a Duration goes
+fn scaled(duration: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
+
+pub fn spawn_deadline(controls: Arc<Controls>, limit: Duration) {
+    let started = Instant::now();
+    thread::spawn(move || {
+        while !controls.is_finished() && started.elapsed() < limit {
+            thread::sleep(Duration::from_millis(250));
+        }
+        if controls.is_finished() {
+            return;
+        }
+        warn!("Max duration: the run has taken {}s (limit {}s); aborting at step {}",
+            started.elapsed().as_secs(), limit.as_secs(), controls.step());
+        controls.request_abort();
+        thread::sleep(DEADLINE_GRACE);
+        if !controls.is_finished() {
+            error!("Max duration: step {} still hasn't stopped after the abort request; exiting. \
+                Mouse buttons held by the script may need to be released by hand.", controls.step());
+            process::exit(2);
+        }
+    });
+}
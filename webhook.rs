@@ -0,0 +1,90 @@
+// Webhook listener, started by the `webhook` subcommand.
+//
+//   POST /hooks/<script>   start a script from the scripts directory
+//
+// Every request must carry the shared secret, either as
+// `Authorization: Bearer <secret>` or as an `X-Webhook-Secret` header, so CI
+// jobs and chatops bots can trigger runs without the full HTTP API. Keep it
+// behind TLS termination when exposed beyond localhost.
+
+use std::error::Error;
+use std::fs;
+use serde::Deserialize;
+use tiny_http::{Method, Request, Response, Server};
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::runs::Runs;
+use crate::{server, Options};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    // File holding the shared secret
+    pub secret_file: Option<String>,
+}
+
+pub fn listen(address: &str, secret_file: Option<&str>, options: Options, config: Config) -> Result<(), Box<dyn Error>> {
+    let secret_file = secret_file.or(config.webhook.secret_file.as_deref())
+        .ok_or("webhook requires --secret-file <path> or secret_file in the [webhook] config")?;
+    let secret = fs::read_to_string(secret_file)
+        .map_err(|e| format!("Failed to read webhook secret '{}': {}", secret_file, e))?
+        .trim()
+        .to_string();
+    if secret.len() < 16 {
+        return Err(format!("Webhook secret in '{}' must be at least 16 characters", secret_file).into());
+    }
+
+    let runs = Runs::new(options, config)?;
+    let server = Server::http(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    info!("Webhook listener on http://{}/hooks/<script> (scripts in {})", address, runs.scripts_dir().display());
+
+    for request in server.incoming_requests() {
+        let (status, body) = route(&request, &secret, &runs);
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(server::json_header());
+        if let Err(error) = request.respond(response) {
+            warn!("failed to send HTTP response: {}", error);
+        }
+    }
+    Ok(())
+}
+
+fn route(request: &Request, secret: &str, runs: &Runs) -> (u16, String) {
+    let url = request.url().split('?').next().unwrap_or("");
+    let Some(script) = url.strip_prefix("/hooks/") else {
+        return server::reply(404, false, "not found");
+    };
+    if *request.method() != Method::Post {
+        return server::reply(405, false, "use POST");
+    }
+    if !authorized(request, secret) {
+        warn!("Rejected webhook for '{}': bad or missing secret", script);
+        return server::reply(401, false, "bad or missing secret");
+    }
+
+    info!("Webhook: starting {}", script);
+    match runs.start(script) {
+        Ok(()) => server::reply(202, true, "started"),
+        Err(error) => server::reply(server::error_status(&error), false, &error.to_string()),
+    }
+}
+
+fn authorized(request: &Request, secret: &str) -> bool {
+    request.headers().iter().any(|header| {
+        let value = header.value.as_str();
+        let presented = if header.field.equiv("Authorization") {
+            value.strip_prefix("Bearer ")
+        } else if header.field.equiv("X-Webhook-Secret") {
+            Some(value)
+        } else {
+            None
+        };
+        presented.is_some_and(|presented| constant_time_eq(presented.trim().as_bytes(), secret.as_bytes()))
+    })
+}
+
+// Compare without returning early so response timing doesn't reveal how much of the secret matched
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
@@ -0,0 +1,133 @@
+// WebSocket channel for browser control panels, started by `serve --ws`.
+//
+// Every client receives the progress events of the current run as JSON text
+// messages and can send commands:
+//   {"command": "pause"} | {"command": "resume"} | {"command": "abort"}
+//   {"command": "speed", "value": 0.5}
+//
+// Browsers let any page open a WebSocket to any address, so the upgrade is
+// refused like an HTTP request would be: from an origin not allowed with
+// `--allow-origin`, or without the `--token-file` token, sent as
+// `Authorization: Bearer <token>` or, since browsers can't set headers on a
+// WebSocket, as `?token=<token>`.
+
+use std::error::Error;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tungstenite::Message;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tracing::{info, warn};
+use crate::controls::{MAX_DELAY_SCALE, MIN_DELAY_SCALE};
+use crate::runs::Runs;
+use crate::server::Access;
+
+#[derive(Deserialize)]
+struct ClientCommand {
+    command: String,
+    value: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Reply<'a> {
+    event: &'a str,
+    command: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+}
+
+pub fn serve(address: &str, runs: Arc<Runs>, access: Arc<Access>) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Failed to listen for WebSocket clients on {}: {}", address, e))?;
+    info!("WebSocket channel listening on ws://{}", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (runs, access) = (Arc::clone(&runs), Arc::clone(&access));
+            thread::spawn(move || {
+                if let Err(error) = handle(stream, &runs, &access) {
+                    warn!("WebSocket client disconnected: {}", error);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(stream: TcpStream, runs: &Runs, access: &Access) -> Result<(), Box<dyn Error>> {
+    let mut socket = tungstenite::accept_hdr(stream, |request: &Request, response: Response| check(request, access).map(|()| response))
+        .map_err(|e| e.to_string())?;
+    // Short read timeouts let one thread both read commands and forward events
+    socket.get_ref().set_read_timeout(Some(Duration::from_millis(100)))?;
+    let updates = runs.events().subscribe();
+
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let reply = apply(&text, runs);
+                socket.send(Message::Text(reply))?;
+            },
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {},
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {},
+            Err(e) => return Err(e.into()),
+        }
+
+        while let Ok(update) = updates.try_recv() {
+            socket.send(Message::Text(update))?;
+        }
+    }
+}
+
+// Refuse the upgrade from another page, or without the token
+fn check(request: &Request, access: &Access) -> Result<(), ErrorResponse> {
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let refuse = |status: StatusCode, message: &str| {
+        warn!("Refused a WebSocket client: {}", message);
+        let mut response = ErrorResponse::new(Some(message.to_string()));
+        *response.status_mut() = status;
+        Err(response)
+    };
+    if !access.origin_allowed(header("Origin")) {
+        return refuse(StatusCode::FORBIDDEN, "connections from other web pages are refused; allow the page's origin with --allow-origin");
+    }
+    let token = header("Authorization").and_then(|value| value.strip_prefix("Bearer ")).or_else(|| {
+        request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix("token="))
+    });
+    if !access.token_accepted(token) {
+        return refuse(StatusCode::UNAUTHORIZED, "bad or missing token");
+    }
+    Ok(())
+}
+
+// Apply one client command to the current run and describe the outcome
+fn apply(text: &str, runs: &Runs) -> String {
+    let reply = |command: &str, message: Option<&str>| {
+        let event = if message.is_some() { "error" } else { "ack" };
+        serde_json::to_string(&Reply { event, command, message }).unwrap_or_default()
+    };
+
+    let command: ClientCommand = match serde_json::from_str(text) {
+        Ok(command) => command,
+        Err(_) => return reply("", Some("expected {\"command\": \"...\"}")),
+    };
+
+    let result = match (command.command.as_str(), command.value) {
+        ("pause", _) => runs.with_active(|controls| controls.set_paused(true)),
+        ("resume", _) => runs.with_active(|controls| controls.request_resume()),
+        ("abort", _) => runs.with_active(|controls| controls.request_abort()),
+        ("speed", Some(factor)) if (MIN_DELAY_SCALE..=MAX_DELAY_SCALE).contains(&factor) => {
+            runs.with_active(|controls| controls.set_delay_scale(factor))
+        },
+        ("speed", _) => return reply("speed", Some("speed needs a \"value\" from 0.01 to 100")),
+        (other, _) => return reply(other, Some("unknown command")),
+    };
+    match result {
+        Ok(()) => reply(&command.command, None),
+        Err(error) => reply(&command.command, Some(&error.to_string())),
+    }
+}
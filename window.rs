@@ -0,0 +1,455 @@
+// Top-level windows found by title, for rows with a `window` column and the
+// window actions.
+//
+// A title matches the first visible window containing it, ignoring case, or
+// is a regular expression between slashes (`/^Untitled - Notepad$/`). A row
+// with a `window` has coordinates relative to the top-left corner of that
+// window's client area, so a click lands on the same button wherever the
+// application opened. The window is looked up again for every row, in case it
+// moved.
+//
+// `focus_window` brings the window to the front, first running the command
+// in its `text` column if no window matches yet, and waiting for one to
+// appear. `wait_for_window` only waits for it, and `assert_window_exists`
+// fails the run unless there is one. A window row with a
+// `timeout_ms` waits up to that long for its window instead of failing
+// straight away, so a dialog a click opens can be waited for. `move_window`, `resize_window`, `maximize_window` and `close_window`
+// put a window where later absolute coordinates expect it; a moved or resized
+// window is un-maximized first, and its position and size are of its frame.
+//
+// Linux needs `wmctrl`; on macOS the area includes the title bar, and System
+// Events needs the accessibility permission.
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+use regex::Regex;
+use tracing::info;
+use crate::bounds::{Bounds, OutOfBounds};
+use crate::controls::Controls;
+use crate::MouseAction;
+
+pub const ACTIONS: &[&str] = &[
+    "focus_window", "wait_for_window", "move_window", "resize_window", "maximize_window", "close_window", "assert_window_exists",
+];
+
+// How long a launched application gets to open its window, and wait_for_window waits
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub fn is_window_action(action: &str) -> bool {
+    ACTIONS.contains(&action)
+}
+
+// A rectangle on the virtual desktop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+pub struct Window {
+    pub title: String,
+    pub area: Rect,
+    // What the platform knows the window by
+    handle: Handle,
+}
+
+// What a window title in a script matches
+pub fn pattern(title: &str) -> Result<Regex, String> {
+    match title.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+        Some(expression) => Regex::new(expression).map_err(|e| format!("invalid window pattern '{}': {}", title, e)),
+        None => Regex::new(&format!("(?i){}", regex::escape(title))).map_err(|e| e.to_string()),
+    }
+}
+
+// Desktop position of (x, y) in the window that `title` names
+pub fn resolve(title: &str, x: i32, y: i32, policy: OutOfBounds) -> Result<(i32, i32), String> {
+    let window = find(title)?.ok_or_else(|| format!("no window matches '{}'", title))?;
+    let area = window.area;
+    let (x, y) = Bounds::new(policy, (area.width, area.height)).resolve(x, y)
+        .map_err(|e| format!("{} of window '{}'", e, window.title))?;
+    Ok((area.x + x, area.y + y))
+}
+
+// The first visible window that `title` matches
+pub fn find(title: &str) -> Result<Option<Window>, String> {
+    let pattern = pattern(title)?;
+    Ok(list()?.into_iter().find(|window| pattern.is_match(&window.title)))
+}
+
+// Bring the window to the front, launching `command` first if it isn't open
+pub fn focus(title: &str, command: Option<&str>, timeout: Option<Duration>, controls: &Controls) -> Result<(), String> {
+    let window = match (find(title)?, command) {
+        (Some(window), _) => window,
+        (None, None) => find_within(title, timeout, controls)?,
+        (None, Some(command)) => {
+            info!("No window matches '{}'; running {}", title, command);
+            launch(command)?;
+            wait_for(title, timeout.unwrap_or(DEFAULT_TIMEOUT), controls)?
+        },
+    };
+    activate(&window)?;
+    info!("Focused '{}'", window.title);
+    Ok(())
+}
+
+// What a window management row does to its window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    // Either may be left as it is
+    Geometry { position: Option<(i32, i32)>, size: Option<(u32, u32)> },
+    Maximize,
+    Close,
+}
+
+// The change a move_window, resize_window, maximize_window or close_window row asks for
+pub fn change(record: &MouseAction) -> Result<Change, String> {
+    match record.action.as_str() {
+        "move_window" => {
+            let position = record.x_position.zip(record.y_position).ok_or("move_window needs x_position and y_position")?;
+            Ok(Change::Geometry { position: Some(position), size: record.width.zip(record.height) })
+        },
+        "resize_window" => {
+            let size = record.width.zip(record.height).ok_or("resize_window needs width and height")?;
+            Ok(Change::Geometry { position: None, size: Some(size) })
+        },
+        "maximize_window" => Ok(Change::Maximize),
+        "close_window" => Ok(Change::Close),
+        other => Err(format!("{} doesn't change a window", other)),
+    }
+}
+
+// Apply a window management row to the window it names
+pub fn arrange(record: &MouseAction, controls: &Controls) -> Result<(), String> {
+    let title = record.window.as_deref().ok_or_else(|| format!("{} needs a window title", record.action))?;
+    let change = change(record)?;
+    let window = find_within(title, record.timeout_ms.map(Duration::from_millis), controls)?;
+    apply(&window, change)?;
+    match change {
+        Change::Geometry { position, size } => {
+            let position = position.map_or(String::new(), |(x, y)| format!(" to ({}, {})", x, y));
+            let size = size.map_or(String::new(), |(width, height)| format!(" at {}x{}", width, height));
+            info!("Placed '{}'{}{}", window.title, position, size);
+        },
+        Change::Maximize => info!("Maximized '{}'", window.title),
+        Change::Close => info!("Closed '{}'", window.title),
+    }
+    Ok(())
+}
+
+// The window `title` names, waiting up to `timeout` for it if one is given
+pub fn find_within(title: &str, timeout: Option<Duration>, controls: &Controls) -> Result<Window, String> {
+    match timeout {
+        Some(timeout) => wait_for(title, timeout, controls),
+        None => find(title)?.ok_or_else(|| format!("no window matches '{}'", title)),
+    }
+}
+
+fn wait_for(title: &str, timeout: Duration, controls: &Controls) -> Result<Window, String> {
+    let started = Instant::now();
+    loop {
+        if let Some(window) = find(title)? {
+            return Ok(window);
+        }
+        if controls.abort_requested() {
+            return Err("aborted".to_string());
+        }
+        if started.elapsed() >= timeout {
+            return Err(format!("no window matching '{}' appeared within {} ms", title, timeout.as_millis()));
+        }
+        controls.sleep(POLL_INTERVAL);
+    }
+}
+
+// Started through the shell and left running
+fn launch(command: &str) -> Result<(), String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", "start", "", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    shell.spawn().map_err(|e| format!("Failed to run '{}': {}", command, e))?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+type Handle = String;
+
+// `wmctrl -lG`: id, desktop, x, y, width, height, host, then the title
+#[cfg(target_os = "linux")]
+fn list() -> Result<Vec<Window>, String> {
+    let listing = wmctrl(&["-lG"])?;
+    Ok(listing.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |index: usize| fields.get(index)?.parse().ok();
+            Some(Window {
+                title: fields.get(7..)?.join(" "),
+                area: Rect { x: number(2)?, y: number(3)?, width: number(4)?, height: number(5)? },
+                handle: fields.first()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+fn activate(window: &Window) -> Result<(), String> {
+    wmctrl(&["-i", "-a", &window.handle]).map(|_| ())
+}
+
+#[cfg(target_os = "linux")]
+fn apply(window: &Window, change: Change) -> Result<(), String> {
+    const MAXIMIZED: &str = "maximized_vert,maximized_horz";
+    let id = window.handle.as_str();
+    match change {
+        Change::Geometry { position, size } => {
+            // A maximized window ignores new geometry; -1 keeps a value as it is
+            wmctrl(&["-i", "-r", id, "-b", &format!("remove,{}", MAXIMIZED)])?;
+            let (x, y) = position.unwrap_or((-1, -1));
+            let (width, height) = size.map_or((-1, -1), |(width, height)| (width as i64, height as i64));
+            wmctrl(&["-i", "-r", id, "-e", &format!("0,{},{},{},{}", x, y, width, height)])?;
+        },
+        Change::Maximize => {
+            wmctrl(&["-i", "-r", id, "-b", &format!("add,{}", MAXIMIZED)])?;
+        },
+        Change::Close => {
+            wmctrl(&["-i", "-c", id])?;
+        },
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn wmctrl(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("wmctrl").args(args).output().map_err(|e| format!("Failed to run wmctrl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("wmctrl failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// The process name and the window's number within it
+#[cfg(target_os = "macos")]
+type Handle = (String, usize);
+
+#[cfg(target_os = "macos")]
+const LIST_WINDOWS: &str = r#"
+set listing to ""
+tell application "System Events"
+    repeat with proc in (application processes whose visible is true)
+        set n to 0
+        repeat with win in windows of proc
+            set n to n + 1
+            try
+                set {x, y} to position of win
+                set {w, h} to size of win
+                set listing to listing & (name of proc) & tab & n & tab & x & tab & y & tab & w & tab & h & tab & (name of win) & linefeed
+            end try
+        end repeat
+    end repeat
+end tell
+return listing
+"#;
+
+// The process name and window number are passed as arguments, never pasted into the script
+#[cfg(target_os = "macos")]
+const ACTIVATE_WINDOW: &str = r#"
+on run argv
+    tell application "System Events"
+        tell process (item 1 of argv)
+            set frontmost to true
+            perform action "AXRaise" of window ((item 2 of argv) as integer)
+        end tell
+    end tell
+end run
+"#;
+
+// Arguments: process name, window number, what to do, then its values
+#[cfg(target_os = "macos")]
+const ARRANGE_WINDOW: &str = r#"
+on run argv
+    set change to item 3 of argv
+    tell application "System Events"
+        tell window ((item 2 of argv) as integer) of process (item 1 of argv)
+            if change is "position" then set position to {(item 4 of argv) as integer, (item 5 of argv) as integer}
+            if change is "size" then set size to {(item 4 of argv) as integer, (item 5 of argv) as integer}
+            if change is "zoom" then click (first button whose subrole is "AXZoomButton")
+            if change is "close" then click (first button whose subrole is "AXCloseButton")
+        end tell
+    end tell
+end run
+"#;
+
+#[cfg(target_os = "macos")]
+fn list() -> Result<Vec<Window>, String> {
+    let listing = osascript(LIST_WINDOWS, &[])?;
+    Ok(listing.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.splitn(7, '\t').collect();
+            let number = |index: usize| fields.get(index)?.trim().parse().ok();
+            Some(Window {
+                title: fields.get(6)?.to_string(),
+                area: Rect { x: number(2)?, y: number(3)?, width: number(4)?, height: number(5)? },
+                handle: (fields.first()?.to_string(), fields.get(1)?.trim().parse().ok()?),
+            })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "macos")]
+fn activate(window: &Window) -> Result<(), String> {
+    let (process, number) = &window.handle;
+    osascript(ACTIVATE_WINDOW, &[process, &number.to_string()]).map(|_| ())
+}
+
+// The zoom button maximizes; on an already zoomed window it restores the old size
+#[cfg(target_os = "macos")]
+fn apply(window: &Window, change: Change) -> Result<(), String> {
+    let (process, number) = &window.handle;
+    let number = number.to_string();
+    let arrange = |args: &[&str]| osascript(ARRANGE_WINDOW, &[&[process.as_str(), number.as_str()], args].concat()).map(|_| ());
+    match change {
+        Change::Geometry { position, size } => {
+            if let Some((x, y)) = position {
+                arrange(&["position", &x.to_string(), &y.to_string()])?;
+            }
+            if let Some((width, height)) = size {
+                arrange(&["size", &width.to_string(), &height.to_string()])?;
+            }
+            Ok(())
+        },
+        Change::Maximize => arrange(&["zoom"]),
+        Change::Close => arrange(&["close"]),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn osascript(script: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("osascript").args(["-e", script]).args(args).output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("System Events failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(windows)]
+type Handle = windows_sys::Win32::Foundation::HWND;
+
+// Visible titled windows, front to back, with their client areas
+#[cfg(windows)]
+fn list() -> Result<Vec<Window>, String> {
+    use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM, POINT, RECT};
+    use windows_sys::Win32::Graphics::Gdi::ClientToScreen;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{EnumWindows, GetClientRect, GetWindowTextW, IsWindowVisible};
+
+    unsafe extern "system" fn collect(window: HWND, found: LPARAM) -> BOOL {
+        // SAFETY: `found` is the Vec passed to EnumWindows below, alive for the whole enumeration
+        let found = unsafe { &mut *(found as *mut Vec<Window>) };
+        let mut buffer = [0u16; 512];
+        // SAFETY: the window handle comes from EnumWindows and every out-parameter is a live local
+        unsafe {
+            if IsWindowVisible(window) == 0 {
+                return 1;
+            }
+            let length = GetWindowTextW(window, buffer.as_mut_ptr(), buffer.len() as i32);
+            let mut client: RECT = std::mem::zeroed();
+            let mut origin = POINT { x: 0, y: 0 };
+            if length <= 0 || GetClientRect(window, &mut client) == 0 || ClientToScreen(window, &mut origin) == 0 {
+                return 1;
+            }
+            found.push(Window {
+                title: String::from_utf16_lossy(&buffer[..length as usize]),
+                area: Rect { x: origin.x, y: origin.y, width: client.right, height: client.bottom },
+                handle: window,
+            });
+        }
+        1
+    }
+
+    let mut found: Vec<Window> = Vec::new();
+    // SAFETY: the callback only runs during this call, while `found` is borrowed
+    unsafe {
+        EnumWindows(Some(collect), &mut found as *mut Vec<Window> as LPARAM);
+    }
+    Ok(found)
+}
+
+// Restored first if minimized; Windows may only flash the taskbar button if another app holds the foreground
+#[cfg(windows)]
+fn activate(window: &Window) -> Result<(), String> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{IsIconic, SetForegroundWindow, ShowWindow, SW_RESTORE};
+
+    // SAFETY: the handle was listed by EnumWindows; a window closed since then only makes the calls fail
+    unsafe {
+        if IsIconic(window.handle) != 0 {
+            ShowWindow(window.handle, SW_RESTORE);
+        }
+        if SetForegroundWindow(window.handle) == 0 {
+            return Err(format!("Windows refused to bring '{}' to the front", window.title));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn apply(window: &Window, change: Change) -> Result<(), String> {
+    use windows_sys::Win32::Foundation::RECT;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetWindowRect, IsZoomed, PostMessageW, SetWindowPos, ShowWindow, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+        SWP_NOZORDER, SW_MAXIMIZE, SW_RESTORE, WM_CLOSE,
+    };
+
+    // SAFETY: the handle was listed by EnumWindows; a window closed since then only makes the calls fail
+    let ok = unsafe {
+        match change {
+            Change::Geometry { position, size } => {
+                if IsZoomed(window.handle) != 0 {
+                    ShowWindow(window.handle, SW_RESTORE);
+                }
+                let mut frame: RECT = std::mem::zeroed();
+                GetWindowRect(window.handle, &mut frame);
+                let (x, y) = position.unwrap_or((frame.left, frame.top));
+                let (width, height) = size.map_or((frame.right - frame.left, frame.bottom - frame.top), |(width, height)| (width as i32, height as i32));
+                let mut flags = SWP_NOZORDER | SWP_NOACTIVATE;
+                if position.is_none() {
+                    flags |= SWP_NOMOVE;
+                }
+                if size.is_none() {
+                    flags |= SWP_NOSIZE;
+                }
+                SetWindowPos(window.handle, 0, x, y, width, height, flags) != 0
+            },
+            Change::Maximize => {
+                ShowWindow(window.handle, SW_MAXIMIZE);
+                true
+            },
+            Change::Close => PostMessageW(window.handle, WM_CLOSE, 0, 0) != 0,
+        }
+    };
+    if ok { Ok(()) } else { Err(format!("Windows refused to change '{}'", window.title)) }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+type Handle = ();
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn list() -> Result<Vec<Window>, String> {
+    Err("finding windows isn't supported on this platform".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn activate(_window: &Window) -> Result<(), String> {
+    Err("focusing windows isn't supported on this platform".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn apply(_window: &Window, _change: Change) -> Result<(), String> {
+    Err("managing windows isn't supported on this platform".to_string())
+}
@@ -0,0 +1,146 @@
+// YAML scripts (`.yaml` or `.yml`), for scripts too long to read as CSV rows.
+//
+// A script is a list of steps, or a mapping with `steps` and optional
+// `defaults`. Each step is either an action with the same fields as a CSV
+// row, or a block: a mapping with its own `steps`, an optional `block` name
+// and `defaults` that apply to every step inside it, nested blocks included.
+// A field set on a step wins over the defaults of the blocks around it.
+//
+//     defaults:
+//       delay_ms: 200
+//     steps:
+//       - { action: move, x_position: 100, y_position: 200 }
+//       - block: login form
+//         defaults: { button: left, delay_ms: 100 }
+//         steps:
+//           - { action: click, x_position: 300, y_position: 220 }
+//           - { action: type_text, text: "${user}" }
+//
+// `${name}` placeholders are filled in step by step after the YAML is parsed,
+// so a `set_var` step applies to the steps after it. A placeholder has to sit
+// in a string, so a numeric field that uses one needs quoting:
+// `x_position: "${base_x}+40"`.
+
+use std::error::Error;
+use std::path::Path;
+use serde_yaml::{Mapping, Value};
+use tracing::error;
+use crate::monitors::Layout;
+use crate::vars::{self, Vars};
+use crate::{set_var, template, MouseAction};
+
+// By extension, looking through a `.tera` suffix (login.yaml.tera)
+pub fn is_yaml(path: &Path) -> bool {
+    let path = if template::is_template(path) { path.file_stem().map(Path::new).unwrap_or(path) } else { path };
+    path.extension().is_some_and(|extension| extension == "yaml" || extension == "yml")
+}
+
+// Flatten a script into rows; `source` names it in error messages
+pub fn parse(text: &str, source: &str, vars: &Vars) -> Result<Vec<MouseAction>, Box<dyn Error>> {
+    let document: Value = serde_yaml::from_str(text).map_err(|e| format!("{}: {}", source, e))?;
+
+    // Collect every bad step so they can all be fixed in one go
+    let mut actions = Vec::new();
+    let mut problems = Vec::new();
+    let mut vars = vars.clone();
+    flatten(&document, &Mapping::new(), "", &mut vars, &mut Layout::default(), &mut actions, &mut problems);
+
+    if problems.is_empty() {
+        Ok(actions)
+    } else {
+        for problem in &problems {
+            error!("{}: {}", source, problem);
+        }
+        Err(format!("{} has {} invalid step(s)", source, problems.len()).into())
+    }
+}
+
+// Append the actions of a block, with `inherited` defaults from the blocks around it
+fn flatten(block: &Value, inherited: &Mapping, location: &str, vars: &mut Vars, screen: &mut Layout, actions: &mut Vec<MouseAction>, problems: &mut Vec<String>) {
+    let mut defaults = inherited.clone();
+    let steps = match block {
+        Value::Sequence(steps) => steps,
+        Value::Mapping(block) => {
+            if let Some(own) = block.get("defaults") {
+                let Value::Mapping(own) = own else {
+                    problems.push(format!("{}defaults must be a mapping of fields", location));
+                    return;
+                };
+                for (field, value) in own {
+                    defaults.insert(field.clone(), value.clone());
+                }
+            }
+            let Some(Value::Sequence(steps)) = block.get("steps") else {
+                problems.push(format!("{}steps must be a list", location));
+                return;
+            };
+            steps
+        },
+        _ => {
+            problems.push(format!("{}expected a list of steps", location));
+            return;
+        },
+    };
+
+    for (index, step) in steps.iter().enumerate() {
+        let here = format!("{}step {}", location, index + 1);
+        match step {
+            Value::Mapping(fields) if fields.contains_key("steps") => {
+                let name = fields.get("block").and_then(Value::as_str).map_or(here.clone(), |name| format!("{} ('{}')", here, name));
+                flatten(step, &defaults, &format!("{} > ", name), vars, screen, actions, problems);
+            },
+            Value::Mapping(fields) => {
+                let mut merged = defaults.clone();
+                for (field, value) in fields {
+                    merged.insert(field.clone(), value.clone());
+                }
+                let record = substitute(&merged, vars, screen)
+                    .and_then(|merged| serde_yaml::from_value::<MouseAction>(Value::Mapping(merged)).map_err(|e| e.to_string()));
+                match record {
+                    Ok(record) => {
+                        set_var(&record, vars);
+                        actions.push(record);
+                    },
+                    Err(error) => problems.push(format!("{}: {}", here, error)),
+                }
+            },
+            _ => problems.push(format!("{}: expected an action or a block", here)),
+        }
+    }
+}
+
+// Fill in the placeholders of a step's string fields, working out numeric ones
+fn substitute(fields: &Mapping, vars: &Vars, screen: &mut Layout) -> Result<Mapping, String> {
+    let monitor = fields.get("monitor").and_then(Value::as_u64).and_then(|monitor| u32::try_from(monitor).ok());
+    let mut substituted = Mapping::new();
+    for (field, value) in fields {
+        let value = match value {
+            Value::String(text) => {
+                let text = vars::substitute(text, vars)?;
+                match field.as_str() {
+                    Some(column) if vars::is_numeric_column(column) => {
+                        let number = vars::evaluate_column(column, &text, monitor, screen).map_err(|e| format!("{} '{}': {}", column, text, e))?;
+                        Value::Number(number.into())
+                    },
+                    _ => Value::String(text),
+                }
+            },
+            _ => value.clone(),
+        };
+        substituted.insert(field.clone(), value);
+    }
+    Ok(substituted)
+}
+
+// A flat list of steps with only the fields each row sets
+pub fn to_string(actions: &[MouseAction]) -> Result<String, Box<dyn Error>> {
+    let mut steps = Vec::new();
+    for record in actions {
+        let mut step = serde_yaml::to_value(record)?;
+        if let Value::Mapping(fields) = &mut step {
+            fields.retain(|_, value| !value.is_null());
+        }
+        steps.push(step);
+    }
+    Ok(serde_yaml::to_string(&steps)?)
+}